@@ -0,0 +1,194 @@
+//! Checkpoint of completed files so an interrupted batch can be resumed
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::config::ConversionOptions;
+use crate::utils::write_atomically;
+
+/// File name of the checkpoint inside the output directory
+pub const CHECKPOINT_FILE_NAME: &str = ".webpify-checkpoint.json";
+
+/// The checkpoint is written after this many completed files
+const CHECKPOINT_INTERVAL: usize = 100;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CheckpointFile {
+    key: String,
+    completed: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default)]
+struct CheckpointState {
+    completed: HashSet<PathBuf>,
+    unsaved: usize,
+}
+
+/// Source files completed in the current batch, saved periodically to the output directory
+///
+/// A checkpoint only applies to a run with the same input and output directories and
+/// encoder settings; it is removed once a run finishes without being cancelled.
+#[derive(Debug)]
+pub struct Checkpoint {
+    path: PathBuf,
+    key: String,
+    state: Mutex<CheckpointState>,
+}
+
+impl Checkpoint {
+    /// Start a checkpoint for this run in `output_dir`
+    ///
+    /// With `resume`, files completed by an interrupted earlier run with the same settings
+    /// are loaded; a missing, corrupt or mismatched checkpoint starts empty.
+    pub fn open(options: &ConversionOptions, output_dir: &Path, resume: bool) -> Self {
+        let path = output_dir.join(CHECKPOINT_FILE_NAME);
+        let key = checkpoint_key(options, output_dir);
+
+        let completed = if resume {
+            match Self::read_completed(&path, &key) {
+                Ok(completed) => completed,
+                Err(e) => {
+                    log::warn!("Ignoring checkpoint and converting all files: {e:#}");
+                    HashSet::new()
+                }
+            }
+        } else {
+            HashSet::new()
+        };
+        if !completed.is_empty() {
+            log::info!(
+                "Resuming: {} files were completed by the interrupted run",
+                completed.len()
+            );
+        }
+
+        Self {
+            path,
+            key,
+            state: Mutex::new(CheckpointState {
+                completed,
+                unsaved: 0,
+            }),
+        }
+    }
+
+    fn read_completed(path: &Path, key: &str) -> Result<HashSet<PathBuf>> {
+        if !path.exists() {
+            return Ok(HashSet::new());
+        }
+
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read checkpoint: {}", path.display()))?;
+        let file: CheckpointFile = serde_json::from_str(&contents)
+            .with_context(|| format!("Corrupt checkpoint: {}", path.display()))?;
+        if file.key != key {
+            anyhow::bail!(
+                "Checkpoint {} was written with different directories or settings",
+                path.display()
+            );
+        }
+        Ok(file.completed.into_iter().collect())
+    }
+
+    /// Whether `source` was completed before the run was interrupted
+    pub fn is_completed(&self, source: &Path) -> bool {
+        self.state
+            .lock()
+            .is_ok_and(|state| state.completed.contains(source))
+    }
+
+    /// Mark `source` as completed, writing the checkpoint every few files
+    pub fn record(&self, source: &Path) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        if !state.completed.insert(source.to_path_buf()) {
+            return;
+        }
+
+        state.unsaved += 1;
+        if state.unsaved >= CHECKPOINT_INTERVAL {
+            // Saving under the lock keeps concurrent workers from racing on the file
+            match self.write(&state) {
+                Ok(()) => state.unsaved = 0,
+                Err(e) => log::warn!("Failed to save checkpoint: {e:#}"),
+            }
+        }
+    }
+
+    /// Write the checkpoint now, e.g. when the run is cancelled
+    pub fn save(&self) -> Result<()> {
+        let state = self
+            .state
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Checkpoint lock poisoned"))?;
+        self.write(&state)
+    }
+
+    /// Delete the checkpoint after the batch has finished
+    pub fn remove(&self) -> Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e)
+                .with_context(|| format!("Failed to remove checkpoint: {}", self.path.display())),
+            _ => Ok(()),
+        }
+    }
+
+    fn write(&self, state: &CheckpointState) -> Result<()> {
+        let file = CheckpointFile {
+            key: self.key.clone(),
+            completed: state.completed.iter().cloned().collect(),
+        };
+        let contents = serde_json::to_string(&file)?;
+
+        // An interrupted save keeps the old checkpoint
+        write_atomically(&self.path, contents.as_bytes())
+            .with_context(|| format!("Failed to write checkpoint: {}", self.path.display()))
+    }
+}
+
+/// Hash of the directories and the settings that decide which outputs a run writes
+fn checkpoint_key(options: &ConversionOptions, output_dir: &Path) -> String {
+    let settings = format!(
+        "{:?}",
+        (
+            (
+                (options.input_roots(), &options.input_files),
+                output_dir,
+                options.effective_formats(),
+                (options.preserve_structure, options.in_place),
+                (&options.filename_template, &options.output_extension),
+            ),
+            (
+                (
+                    options.quality,
+                    &options.mode,
+                    // Sorted so the key doesn't depend on hash map order
+                    options.format_overrides.iter().collect::<BTreeMap<_, _>>(),
+                    &options.quality_ramp,
+                ),
+                (options.target_ssim, options.smallest_min_ssim),
+                (options.effort, options.lossless_level),
+                (
+                    options.alpha_quality,
+                    options.filter_strength,
+                    options.sharpness
+                ),
+                (
+                    options.preserve_metadata,
+                    options.strip_metadata,
+                    options.auto_orient,
+                ),
+                options.animation,
+                (&options.ico_size, &options.tiff_pages),
+                options.max_dimension,
+                (&options.resize_filter, options.background),
+                (options.auto_color_threshold, options.auto_sample_size),
+            ),
+        )
+    );
+    blake3::hash(settings.as_bytes()).to_hex().to_string()
+}
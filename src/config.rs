@@ -1,11 +1,134 @@
-use serde::Deserialize;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::{CompressionMode, ReplaceInputMode, ReportFormat};
+use crate::{
+    CollisionPolicy, CompressionMode, IcoSizeSelection, RecycleFallback, ReplaceInputMode,
+    ReportFormat, ResizeFilter, SortOrder, TiffPageSelection,
+    control::ConversionControl,
+    converter::{
+        DEFAULT_AUTO_COLOR_THRESHOLD, DEFAULT_AUTO_SAMPLE_SIZE, DEFAULT_EFFORT, MAX_EFFORT,
+        MAX_FILTER_STRENGTH, MAX_LOSSLESS_LEVEL, MAX_SHARPNESS,
+    },
+    output_path::OutputPathResolver,
+    utils::{absolute_path, common_ancestor, parse_date_time, parse_dimensions},
+};
+
+/// Extension of the files webpify writes, unless another one is set with
+/// [`ConversionOptions::with_output_extension`]
+pub const OUTPUT_FORMAT: &str = "webp";
+
+/// Format keyword that stands for every supported input extension
+pub const ALL_FORMATS: &str = "all";
+
+/// Input formats scanned unless others are set
+///
+/// HEIC/HEIF are only included when built with the `heic` feature.
+pub const DEFAULT_FORMATS: &[&str] = &[
+    "jpg",
+    "jpeg",
+    "png",
+    "gif",
+    "bmp",
+    "tiff",
+    "webp",
+    #[cfg(feature = "heic")]
+    "heic",
+    #[cfg(feature = "heic")]
+    "heif",
+    "ico",
+];
+
+/// Smallest output, as a fraction of the source size, that may replace the source
+pub const DEFAULT_REPLACE_MIN_RATIO: f64 = 0.01;
+/// Largest output, as a fraction of the source size, that may replace the source
+pub const DEFAULT_REPLACE_MAX_RATIO: f64 = 2.0;
+
+/// Check that a filename template yields distinct file names with the output extension
+///
+/// The template must use `{stem}` or `{index}` so each input gets its own name, and
+/// must not contain path separators.
+fn validate_filename_template(template: &str, extension: &str) -> Result<()> {
+    let mut unique = false;
+    let file_name = expand_filename_template(template, |token| {
+        unique |= matches!(token, "stem" | "index");
+        token.to_string()
+    })?;
+
+    if !unique {
+        anyhow::bail!(
+            "Filename template '{template}' must contain {{stem}} or {{index}} \
+             so that different inputs get different names"
+        );
+    }
+    if file_name.contains(['/', '\\']) {
+        anyhow::bail!("Filename template '{template}' must not contain path separators");
+    }
+    if !Path::new(&file_name)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+    {
+        anyhow::bail!("Filename template '{template}' must end with .{extension}");
+    }
+    Ok(())
+}
+
+/// Build the output file name for `input_path` from a filename template
+///
+/// `index` is the 1-based position of the file in the scan.
+pub(crate) fn render_filename_template(
+    template: &str,
+    input_path: &Path,
+    index: usize,
+) -> Result<String> {
+    let lossy = |name: Option<&std::ffi::OsStr>| {
+        name.map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+
+    expand_filename_template(template, |token| match token {
+        "stem" => lossy(input_path.file_stem()),
+        "ext" => lossy(input_path.extension()),
+        "parent" => lossy(input_path.parent().and_then(Path::file_name)),
+        _ => index.to_string(),
+    })
+}
+
+/// Replace every `{token}` in `template` with its value, failing on unknown tokens
+fn expand_filename_template(
+    template: &str,
+    mut value: impl FnMut(&str) -> String,
+) -> Result<String> {
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after
+            .find('}')
+            .with_context(|| format!("Unclosed '{{' in filename template '{template}'"))?;
+        let token = &after[..end];
+        if !matches!(token, "stem" | "ext" | "parent" | "index") {
+            anyhow::bail!(
+                "Unknown token {{{token}}} in filename template '{template}' \
+                 (available: {{stem}}, {{ext}}, {{parent}}, {{index}})"
+            );
+        }
+        expanded.push_str(&value(token));
+        rest = &after[end + 1..];
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
 
 /// Main configuration structure loaded from config files
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct Config {
     pub general: Option<GeneralConfig>,
     pub compression: Option<CompressionConfig>,
@@ -14,8 +137,100 @@ pub struct Config {
     pub profiles: Option<HashMap<String, ProfileConfig>>,
 }
 
+impl Config {
+    /// Load a configuration from a TOML file
+    pub fn from_file(path: &Path) -> Result<Config> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse config file: {}", path.display()))
+    }
+
+    /// Write the configuration to a TOML file, leaving out unset keys
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let contents = toml::to_string_pretty(self).context("Failed to serialize config")?;
+        std::fs::write(path, contents)
+            .with_context(|| format!("Failed to write config file: {}", path.display()))
+    }
+
+    /// Find the first config file in the standard search locations
+    ///
+    /// Searches `./webpify.config.toml`, the per-user config directory
+    /// (`~/.config/webpify/config.toml` or `%APPDATA%\webpify\config.toml`) and,
+    /// outside Windows, `/etc/webpify/config.toml`.
+    pub fn discover() -> Option<PathBuf> {
+        let mut candidates = vec![PathBuf::from("webpify.config.toml")];
+        if let Some(dir) = user_config_dir() {
+            candidates.push(dir.join("config.toml"));
+        }
+        #[cfg(not(windows))]
+        candidates.push(PathBuf::from("/etc/webpify/config.toml"));
+
+        candidates.into_iter().find(|path| path.is_file())
+    }
+
+    /// Merge profiles from `profiles.toml` files in the standard search locations
+    ///
+    /// Searches `./profiles.toml`, the directory of `config_path` and the per-user config
+    /// directory. Profiles defined in the config file itself take precedence, followed by
+    /// earlier locations in the search order.
+    pub fn load_profile_files(&mut self, config_path: Option<&Path>) -> Result<()> {
+        let mut candidates = vec![PathBuf::from("profiles.toml")];
+        if let Some(dir) = config_path.and_then(Path::parent) {
+            candidates.push(dir.join("profiles.toml"));
+        }
+        if let Some(dir) = user_config_dir() {
+            candidates.push(dir.join("profiles.toml"));
+        }
+
+        for path in candidates.iter().filter(|path| path.is_file()) {
+            let file = Config::from_file(path)?;
+            let Some(file_profiles) = file.profiles else {
+                continue;
+            };
+
+            let profiles = self.profiles.get_or_insert_with(HashMap::new);
+            for (name, profile) in file_profiles {
+                profiles.entry(name).or_insert(profile);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Look up a named profile
+    pub fn resolve_profile(&self, name: &str) -> Result<ProfileConfig> {
+        let Some(profiles) = &self.profiles else {
+            anyhow::bail!(
+                "Profile '{name}' was requested but no [profiles] table was found \
+                 in the config file or any profiles.toml"
+            );
+        };
+
+        profiles.get(name).cloned().with_context(|| {
+            let mut available: Vec<&str> = profiles.keys().map(String::as_str).collect();
+            available.sort_unstable();
+            format!(
+                "Profile '{}' not found (available profiles: {})",
+                name,
+                available.join(", ")
+            )
+        })
+    }
+}
+
+/// Per-user webpify config directory
+pub(crate) fn user_config_dir() -> Option<PathBuf> {
+    #[cfg(windows)]
+    let base = dirs::config_dir();
+    #[cfg(not(windows))]
+    let base = dirs::home_dir().map(|home| home.join(".config"));
+
+    base.map(|dir| dir.join("webpify"))
+}
+
 /// Configuration profile for predefined settings
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ProfileConfig {
     pub description: Option<String>,
     pub quality: Option<u8>,
@@ -24,94 +239,337 @@ pub struct ProfileConfig {
     pub preserve_structure: Option<bool>,
     pub formats: Option<Vec<String>>,
     pub threads: Option<usize>,
+    pub strip_metadata: Option<bool>,
 }
 
 /// General configuration options
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct GeneralConfig {
     pub input_dir: Option<String>,
     pub output_dir: Option<String>,
     pub preserve_structure: Option<bool>,
+    pub in_place: Option<bool>,
     pub overwrite: Option<bool>,
+    pub keep_smaller: Option<bool>,
+    pub preserve_timestamps: Option<bool>,
     pub threads: Option<usize>,
+    pub max_memory_mb: Option<u64>,
+    pub low_priority: Option<bool>,
+    pub timeout_secs: Option<u64>,
     pub prescan: Option<bool>,
+    pub space_check: Option<bool>,
     pub replace_input: Option<String>,
+    pub replace_min_ratio: Option<f64>,
+    pub replace_max_ratio: Option<f64>,
+    pub recycle_quarantine_dir: Option<String>,
+    pub verify: Option<bool>,
     pub reencode_webp: Option<bool>,
     pub dry_run: Option<bool>,
+    pub dry_run_accurate: Option<bool>,
+    pub filename_template: Option<String>,
+    pub output_extension: Option<String>,
+    pub collision_policy: Option<String>,
+    pub sort_order: Option<String>,
+    pub max_files: Option<usize>,
+    pub largest_first: Option<bool>,
+    pub incremental: Option<bool>,
+    pub manifest: Option<bool>,
+    pub follow_symlinks: Option<bool>,
+    pub max_depth: Option<usize>,
 }
 
 /// Compression-related configuration
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct CompressionConfig {
     pub quality: Option<u8>,
     pub mode: Option<String>,
+    pub max_dimension: Option<u32>,
+    pub resize_filter: Option<String>,
+    pub effort: Option<u8>,
+    pub alpha_quality: Option<u8>,
+    pub lossless_level: Option<u8>,
+    pub filter_strength: Option<u8>,
+    pub sharpness: Option<u8>,
+    pub auto_color_threshold: Option<usize>,
+    pub auto_sample_size: Option<u32>,
+    pub preserve_metadata: Option<bool>,
+    pub strip_metadata: Option<bool>,
+    pub animation: Option<bool>,
+    pub background: Option<String>,
+    pub auto_orient: Option<bool>,
+    pub format_overrides: Option<Vec<String>>,
+    /// `[min_size_kb, quality]` steps, e.g. `[[0, 90], [1024, 75]]`
+    pub quality_ramp: Option<Vec<(u64, u8)>>,
 }
 
 /// File filtering configuration
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct FilteringConfig {
     pub formats: Option<Vec<String>>,
+    pub exclude_formats: Option<Vec<String>>,
+    pub detect_by_content: Option<bool>,
     pub min_size: Option<u64>,
     pub max_size: Option<u64>,
+    pub passthrough_below: Option<u64>,
+    pub include: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+    pub modified_after: Option<String>,
+    pub modified_before: Option<String>,
+    pub min_dimensions: Option<String>,
+    pub max_dimensions: Option<String>,
 }
 
 /// Output and reporting configuration
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct OutputConfig {
     pub verbose: Option<bool>,
     pub quiet: Option<bool>,
     pub generate_report: Option<bool>,
     pub report_format: Option<String>,
+    pub report_timestamp: Option<bool>,
+    pub track_outputs: Option<bool>,
+    pub track_files: Option<bool>,
+    pub log_file: Option<PathBuf>,
 }
 
 /// Conversion options that can be passed to the core library
 #[derive(Debug, Clone)]
 pub struct ConversionOptions {
     pub input_dir: PathBuf,
+    /// Further input directories converted in the same run
+    pub additional_input_dirs: Vec<PathBuf>,
+    /// Files to convert instead of walking the input directories
+    pub input_files: Vec<PathBuf>,
+    /// Directory the output structure of `input_files` is relative to
+    pub base_dir: Option<PathBuf>,
     pub output_dir: Option<PathBuf>,
     pub quality: u8,
     pub mode: CompressionMode,
+    /// Whether `mode` was chosen rather than left at its default
+    pub mode_explicit: bool,
+    /// Mode and quality per lowercase input extension, replacing `mode` and `quality`
+    pub format_overrides: HashMap<String, (CompressionMode, u8)>,
+    /// Quality by source size as ascending `(min_size_kb, quality)` steps, replacing
+    /// `quality` for formats without an override
+    pub quality_ramp: Vec<(u64, u8)>,
     pub threads: Option<usize>,
+    /// Input extensions to convert; `all` stands for every supported one
     pub formats: Vec<String>,
+    /// Extensions removed from `formats`
+    pub excluded_formats: Vec<String>,
+    /// Pick inputs and decode them by their header, ignoring a wrong or missing extension
+    pub detect_by_content: bool,
     pub overwrite: bool,
+    /// Copy the source to the output instead of a WebP that would be larger
+    pub keep_smaller: bool,
+    /// Give outputs the modification and access times of their source
+    pub preserve_timestamps: bool,
+    /// Skip files whose output is newer than the source and re-convert stale outputs
+    pub incremental: bool,
+    /// Skip files whose content hash matches the manifest in the output directory
+    pub manifest: bool,
+    /// Skip files completed by an interrupted run with the same settings (see `checkpoint`)
+    pub resume: bool,
     pub preserve_structure: bool,
+    /// Write each output next to its source instead of into the output directory
+    pub in_place: bool,
+    /// Custom placement of outputs, replacing the built-in layout options when set
+    pub output_path_resolver: Option<Arc<dyn OutputPathResolver>>,
+    /// How inputs mapping to the same flattened output are handled (needs prescan)
+    pub collision_policy: CollisionPolicy,
+    /// Order of the prescanned files, which also decides `{index}` in filename templates
+    pub sort_order: SortOrder,
+    /// Convert only the first this many matching files (after sorting)
+    pub max_files: Option<usize>,
+    /// Start the largest prescanned files first (unless a sort order is set)
+    pub largest_first: bool,
     pub max_size: Option<u64>,
     pub min_size: u64,
+    /// Files smaller than this many KB are copied to the output unchanged instead of
+    /// being converted
+    pub passthrough_below: Option<u64>,
+    /// Glob patterns a file's path relative to its input directory must match (any of)
+    pub include_globs: Vec<String>,
+    /// Glob patterns excluding files by their path relative to their input directory
+    pub exclude_globs: Vec<String>,
+    /// Only convert files last modified at or after this time
+    pub modified_after: Option<DateTime<Utc>>,
+    /// Only convert files last modified before this time
+    pub modified_before: Option<DateTime<Utc>>,
+    /// Skip images narrower or shorter than this (width, height) in pixels
+    pub min_dimensions: Option<(u32, u32)>,
+    /// Skip images wider or taller than this (width, height) in pixels
+    pub max_dimensions: Option<(u32, u32)>,
     pub prescan: bool,
+    /// Refuse to start when the estimated output exceeds the free space (only warn if false)
+    pub space_check: bool,
+    /// Walk into symlinked directories (each directory is still scanned at most once)
+    pub follow_symlinks: bool,
+    /// How many directory levels below each input directory are scanned (0 = top level only)
+    pub max_depth: Option<usize>,
     pub replace_input: ReplaceInputMode,
+    /// Sources are only recycled/deleted when their output is at least this fraction of their size
+    pub replace_min_ratio: f64,
+    /// Sources are only recycled/deleted when their output is at most this fraction of their size
+    pub replace_max_ratio: f64,
+    /// What happens to sources the recycle bin refused
+    pub recycle_fallback: RecycleFallback,
+    /// Decode every written WebP again after the run and check its dimensions
+    pub verify: bool,
     pub reencode_webp: bool,
     pub dry_run: bool,
+    /// Encode in memory during dry runs so the reported output sizes are exact
+    pub dry_run_accurate: bool,
+    /// Output file name template (e.g. `{stem}_optimized.webp`); `<stem>.webp` if not set
+    pub filename_template: Option<String>,
+    /// Extension of the written files (e.g. `WEBP`); the contents are WebP either way
+    pub output_extension: String,
     pub generate_report: bool,
     pub report_format: ReportFormat,
+    /// Report file or directory; defaults to the output directory
+    pub report_path: Option<PathBuf>,
+    /// Insert the run's start time into the report file name
+    pub report_timestamp: bool,
+    /// List every written output file in the report (memory grows with the batch)
+    pub track_outputs: bool,
+    /// Collect a result per file for the report (memory grows with the batch)
+    pub track_files: bool,
+    /// Append timestamped per-file results to this file
+    pub log_file: Option<PathBuf>,
+    /// When set, lossy encoding searches per image for the lowest quality reaching this SSIM
+    pub target_ssim: Option<f64>,
+    /// In smallest mode, a lossy result is only picked when its SSIM reaches this
+    pub smallest_min_ssim: Option<f64>,
+    /// Run conversion threads at a reduced scheduling priority
+    pub low_priority: bool,
+    /// Handle used to pause and resume the run from another thread
+    pub control: Option<ConversionControl>,
+    /// Maximum output write rate in MB/s (unlimited if not set)
+    pub write_limit: Option<u64>,
+    /// Memory budget in MB for images decoded at the same time (unlimited if not set)
+    pub max_memory_mb: Option<u64>,
+    /// Keep ICC color profiles and EXIF data from the source images
+    pub preserve_metadata: bool,
+    /// Never write ICC, EXIF or XMP metadata, overriding `preserve_metadata`
+    pub strip_metadata: bool,
+    /// Turn images upright according to their EXIF orientation
+    pub auto_orient: bool,
+    /// How often a file is retried after a transient I/O failure
+    pub max_retries: u32,
+    /// Give up on a single file after this long (no limit if not set)
+    pub per_file_timeout: Option<Duration>,
+    /// Convert animated GIF/APNG and HEIC sequence inputs to animated WebP instead of their first frame
+    pub animation: bool,
+    /// Which resolutions of ICO inputs are converted
+    pub ico_size: IcoSizeSelection,
+    /// Which pages of multi-page TIFF inputs are converted
+    pub tiff_pages: TiffPageSelection,
+    /// Longest edge of the output in pixels; larger images are downscaled
+    pub max_dimension: Option<u32>,
+    /// Resampling filter used when downscaling
+    pub resize_filter: ResizeFilter,
+    /// Color transparent images are flattened onto before encoding (keeps alpha if not set)
+    pub background: Option<image::Rgba<u8>>,
+    /// Encoder effort from 0 (fastest) to 6 (slowest, smallest files)
+    pub effort: u8,
+    /// Quality of the alpha channel in lossy mode (0-100, 100 keeps alpha lossless)
+    pub alpha_quality: u8,
+    /// Lossless compression level (0-9); when unset lossless encodes use `effort`
+    pub lossless_level: Option<u8>,
+    /// Lossy deblocking filter strength (0-100); the encoder default (60) when unset
+    pub filter_strength: Option<u8>,
+    /// Lossy filter sharpness (0-7); the encoder default (0) when unset
+    pub sharpness: Option<u8>,
+    /// Auto mode encodes images with fewer distinct sampled colors than this losslessly
+    pub auto_color_threshold: usize,
+    /// Number of pixels auto mode samples to count colors
+    pub auto_sample_size: u32,
 }
 
 impl Default for ConversionOptions {
     fn default() -> Self {
         Self {
             input_dir: PathBuf::new(),
+            additional_input_dirs: Vec::new(),
+            input_files: Vec::new(),
+            base_dir: None,
             output_dir: None,
             quality: 80,
             mode: CompressionMode::Lossless,
+            mode_explicit: false,
+            format_overrides: HashMap::new(),
+            quality_ramp: Vec::new(),
             threads: None,
-            formats: vec![
-                "jpg".to_string(),
-                "jpeg".to_string(),
-                "png".to_string(),
-                "gif".to_string(),
-                "bmp".to_string(),
-                "tiff".to_string(),
-                "webp".to_string(),
-            ],
+            formats: DEFAULT_FORMATS.iter().map(|f| f.to_string()).collect(),
+            excluded_formats: Vec::new(),
+            detect_by_content: false,
             overwrite: false,
+            keep_smaller: false,
+            preserve_timestamps: false,
+            incremental: false,
+            manifest: false,
+            resume: false,
             preserve_structure: true,
+            in_place: false,
+            output_path_resolver: None,
+            collision_policy: CollisionPolicy::Rename,
+            sort_order: SortOrder::None,
+            max_files: None,
+            largest_first: true,
             max_size: None,
             min_size: 1,
+            passthrough_below: None,
+            include_globs: Vec::new(),
+            exclude_globs: Vec::new(),
+            modified_after: None,
+            modified_before: None,
+            min_dimensions: None,
+            max_dimensions: None,
             prescan: true,
+            space_check: true,
+            follow_symlinks: false,
+            max_depth: None,
             replace_input: ReplaceInputMode::Off,
+            replace_min_ratio: DEFAULT_REPLACE_MIN_RATIO,
+            replace_max_ratio: DEFAULT_REPLACE_MAX_RATIO,
+            recycle_fallback: RecycleFallback::Keep,
+            verify: false,
             reencode_webp: false,
             dry_run: false,
+            dry_run_accurate: false,
+            filename_template: None,
+            output_extension: OUTPUT_FORMAT.to_string(),
             generate_report: false,
             report_format: ReportFormat::Json,
+            report_path: None,
+            report_timestamp: false,
+            track_outputs: false,
+            track_files: false,
+            log_file: None,
+            target_ssim: None,
+            smallest_min_ssim: None,
+            low_priority: false,
+            control: None,
+            write_limit: None,
+            max_memory_mb: None,
+            preserve_metadata: false,
+            strip_metadata: false,
+            auto_orient: true,
+            max_retries: 0,
+            per_file_timeout: None,
+            animation: true,
+            ico_size: IcoSizeSelection::Largest,
+            tiff_pages: TiffPageSelection::First,
+            max_dimension: None,
+            resize_filter: ResizeFilter::Lanczos3,
+            background: None,
+            effort: DEFAULT_EFFORT,
+            alpha_quality: 100,
+            lossless_level: None,
+            filter_strength: None,
+            sharpness: None,
+            auto_color_threshold: DEFAULT_AUTO_COLOR_THRESHOLD,
+            auto_sample_size: DEFAULT_AUTO_SAMPLE_SIZE,
         }
     }
 }
@@ -125,6 +583,312 @@ impl ConversionOptions {
         }
     }
 
+    /// Create conversion options from a loaded config file
+    ///
+    /// Settings missing from the file keep their defaults. Fails if a mode or
+    /// format string in the file is not recognized.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut options = Self::default();
+
+        if let Some(general) = &config.general {
+            if let Some(input_dir) = &general.input_dir {
+                options.input_dir = PathBuf::from(input_dir);
+            }
+            if let Some(output_dir) = &general.output_dir {
+                options = options.with_output_dir(PathBuf::from(output_dir));
+            }
+            if let Some(preserve_structure) = general.preserve_structure {
+                options = options.with_preserve_structure(preserve_structure);
+            }
+            if let Some(in_place) = general.in_place {
+                options = options.with_in_place(in_place);
+            }
+            if let Some(overwrite) = general.overwrite {
+                options = options.with_overwrite(overwrite);
+            }
+            if let Some(keep_smaller) = general.keep_smaller {
+                options = options.with_keep_smaller(keep_smaller);
+            }
+            if let Some(preserve_timestamps) = general.preserve_timestamps {
+                options = options.with_preserve_timestamps(preserve_timestamps);
+            }
+            if let Some(threads) = general.threads {
+                options = options.with_threads(threads);
+            }
+            if let Some(max_memory_mb) = general.max_memory_mb {
+                options = options.with_max_memory_mb(max_memory_mb);
+            }
+            if let Some(low_priority) = general.low_priority {
+                options = options.with_low_priority(low_priority);
+            }
+            if let Some(timeout_secs) = general.timeout_secs {
+                options = options.with_per_file_timeout(Duration::from_secs(timeout_secs));
+            }
+            if let Some(prescan) = general.prescan {
+                options = options.with_prescan(prescan);
+            }
+            if let Some(space_check) = general.space_check {
+                options = options.with_space_check(space_check);
+            }
+            if let Some(replace_input) = &general.replace_input {
+                options = options.with_replace_input_mode(replace_input.parse()?);
+            }
+            if general.replace_min_ratio.is_some() || general.replace_max_ratio.is_some() {
+                let min_ratio = general
+                    .replace_min_ratio
+                    .unwrap_or(options.replace_min_ratio);
+                let max_ratio = general
+                    .replace_max_ratio
+                    .unwrap_or(options.replace_max_ratio);
+                options = options.with_replace_size_limits(min_ratio, max_ratio)?;
+            }
+            if let Some(quarantine_dir) = &general.recycle_quarantine_dir {
+                options = options
+                    .with_recycle_fallback(RecycleFallback::Quarantine(quarantine_dir.into()))?;
+            }
+            if let Some(verify) = general.verify {
+                options = options.with_verify(verify);
+            }
+            if let Some(reencode_webp) = general.reencode_webp {
+                options = options.with_reencode_webp(reencode_webp);
+            }
+            if let Some(dry_run) = general.dry_run {
+                options = options.with_dry_run(dry_run);
+            }
+            if let Some(dry_run_accurate) = general.dry_run_accurate {
+                options = options.with_dry_run_accurate(dry_run_accurate);
+            }
+            // Before the template, which must end with the output extension
+            if let Some(output_extension) = &general.output_extension {
+                options = options.with_output_extension(output_extension.clone())?;
+            }
+            if let Some(filename_template) = &general.filename_template {
+                options = options.with_filename_template(filename_template.clone())?;
+            }
+            if let Some(incremental) = general.incremental {
+                options = options.with_incremental(incremental);
+            }
+            if let Some(manifest) = general.manifest {
+                options = options.with_manifest(manifest);
+            }
+            if let Some(follow_symlinks) = general.follow_symlinks {
+                options = options.with_follow_symlinks(follow_symlinks);
+            }
+            if let Some(max_depth) = general.max_depth {
+                options = options.with_max_depth(Some(max_depth));
+            }
+            if let Some(collision_policy) = &general.collision_policy {
+                options = options.with_collision_policy(collision_policy.parse()?);
+            }
+            if let Some(sort_order) = &general.sort_order {
+                options = options.with_sort_order(sort_order.parse()?);
+            }
+            if let Some(max_files) = general.max_files {
+                options = options.with_max_files(max_files)?;
+            }
+            if let Some(largest_first) = general.largest_first {
+                options = options.with_largest_first(largest_first);
+            }
+        }
+
+        if let Some(compression) = &config.compression {
+            if let Some(quality) = compression.quality {
+                options = options.with_quality(quality);
+            }
+            if let Some(mode) = &compression.mode {
+                options = options.with_mode(mode.parse()?);
+            }
+            if let Some(max_dimension) = compression.max_dimension {
+                options = options.with_max_dimension(max_dimension)?;
+            }
+            if let Some(effort) = compression.effort {
+                options = options.with_effort(effort);
+            }
+            if let Some(alpha_quality) = compression.alpha_quality {
+                options = options.with_alpha_quality(alpha_quality);
+            }
+            if let Some(lossless_level) = compression.lossless_level {
+                options = options.with_lossless_level(lossless_level);
+            }
+            if let Some(filter_strength) = compression.filter_strength {
+                options = options.with_filter_strength(filter_strength);
+            }
+            if let Some(sharpness) = compression.sharpness {
+                options = options.with_sharpness(sharpness);
+            }
+            if let Some(auto_color_threshold) = compression.auto_color_threshold {
+                options = options.with_auto_color_threshold(auto_color_threshold);
+            }
+            if let Some(auto_sample_size) = compression.auto_sample_size {
+                options = options.with_auto_sample_size(auto_sample_size);
+            }
+            if let Some(preserve_metadata) = compression.preserve_metadata {
+                options = options.with_preserve_metadata(preserve_metadata);
+            }
+            if let Some(strip_metadata) = compression.strip_metadata {
+                options = options.with_strip_metadata(strip_metadata);
+            }
+            if let Some(animation) = compression.animation {
+                options = options.with_animation(animation);
+            }
+            for format_override in compression.format_overrides.iter().flatten() {
+                let (extension, mode, quality) =
+                    crate::utils::parse_format_override(format_override)?;
+                options = options.with_format_override(&extension, mode, quality);
+            }
+            if let Some(quality_ramp) = &compression.quality_ramp {
+                options = options.with_quality_ramp(quality_ramp.clone())?;
+            }
+            if let Some(auto_orient) = compression.auto_orient {
+                options = options.with_auto_orient(auto_orient);
+            }
+            if let Some(background) = &compression.background {
+                options = options.with_background(Some(crate::utils::parse_color(background)?));
+            }
+            if let Some(resize_filter) = &compression.resize_filter {
+                options = options.with_resize_filter(resize_filter.parse()?);
+            }
+        }
+
+        if let Some(filtering) = &config.filtering {
+            if let Some(formats) = &filtering.formats {
+                options = options.with_supported_formats(formats.clone());
+            }
+            if let Some(exclude_formats) = &filtering.exclude_formats {
+                options = options.with_excluded_formats(exclude_formats.clone());
+            }
+            if let Some(detect_by_content) = filtering.detect_by_content {
+                options = options.with_detect_by_content(detect_by_content);
+            }
+            if let Some(min_size) = filtering.min_size {
+                options = options.with_min_size_kb(min_size);
+            }
+            // A max_size of 0 means unlimited
+            if let Some(max_size) = filtering.max_size.filter(|&size| size > 0) {
+                options = options.with_max_size_mb(max_size);
+            }
+            if let Some(passthrough_below) = filtering.passthrough_below {
+                options = options.with_passthrough_below(Some(passthrough_below));
+            }
+            if let Some(include) = &filtering.include {
+                options = options.with_include_globs(include.clone());
+            }
+            if let Some(exclude) = &filtering.exclude {
+                options = options.with_exclude_globs(exclude.clone());
+            }
+            if let Some(modified_after) = &filtering.modified_after {
+                options = options.with_modified_after(parse_date_time(modified_after)?);
+            }
+            if let Some(modified_before) = &filtering.modified_before {
+                options = options.with_modified_before(parse_date_time(modified_before)?);
+            }
+            if let Some(min_dimensions) = &filtering.min_dimensions {
+                let (width, height) = parse_dimensions(min_dimensions)?;
+                options = options.with_min_dimensions(width, height);
+            }
+            if let Some(max_dimensions) = &filtering.max_dimensions {
+                let (width, height) = parse_dimensions(max_dimensions)?;
+                options = options.with_max_dimensions(width, height);
+            }
+        }
+
+        if let Some(output) = &config.output {
+            if let Some(generate_report) = output.generate_report {
+                options = options.with_generate_report(generate_report);
+            }
+            if let Some(report_format) = &output.report_format {
+                options = options.with_report_format(report_format.parse()?);
+            }
+            if let Some(report_timestamp) = output.report_timestamp {
+                options = options.with_report_timestamp(report_timestamp);
+            }
+            if let Some(track_outputs) = output.track_outputs {
+                options = options.with_track_outputs(track_outputs);
+            }
+            if let Some(track_files) = output.track_files {
+                options = options.with_track_files(track_files);
+            }
+            if let Some(log_file) = &output.log_file {
+                options = options.with_log_file(log_file.clone());
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// Apply the settings of a profile on top of the current options
+    ///
+    /// A `max_size` of 0 removes the size limit and `threads = 0` uses all cores.
+    pub fn with_profile(mut self, profile: &ProfileConfig) -> Result<Self> {
+        if let Some(quality) = profile.quality {
+            self = self.with_quality(quality);
+        }
+        if let Some(mode) = &profile.mode {
+            self = self.with_mode(mode.parse()?);
+        }
+        if let Some(max_size) = profile.max_size {
+            self.max_size = (max_size > 0).then_some(max_size);
+        }
+        if let Some(preserve_structure) = profile.preserve_structure {
+            self = self.with_preserve_structure(preserve_structure);
+        }
+        if let Some(formats) = &profile.formats {
+            self = self.with_supported_formats(formats.clone());
+        }
+        if let Some(threads) = profile.threads {
+            self.threads = (threads > 0).then_some(threads);
+        }
+        if let Some(strip_metadata) = profile.strip_metadata {
+            self = self.with_strip_metadata(strip_metadata);
+        }
+
+        Ok(self)
+    }
+
+    /// Builder pattern for setting input directory
+    pub fn with_input_dir(mut self, input_dir: PathBuf) -> Self {
+        self.input_dir = input_dir;
+        self
+    }
+
+    /// Builder pattern for converting several input directories in one run
+    ///
+    /// The first directory becomes `input_dir` (which the default output directory is
+    /// derived from); the rest are scanned after it.
+    pub fn with_input_dirs(mut self, input_dirs: Vec<PathBuf>) -> Self {
+        let mut input_dirs = input_dirs.into_iter();
+        self.input_dir = input_dirs.next().unwrap_or_default();
+        self.additional_input_dirs = input_dirs.collect();
+        self
+    }
+
+    /// Builder pattern for converting exactly these files instead of walking directories
+    ///
+    /// The files still pass the format, size, date, dimension and glob filters, and
+    /// duplicates are dropped. Relative paths are resolved against the working directory
+    /// first, so a list mixing relative and absolute paths shares one base. Output paths
+    /// are relative to the base directory, which defaults to the deepest common ancestor
+    /// of the files unless it was set with [`with_base_dir`](Self::with_base_dir); a run
+    /// fails before converting anything if a listed file is outside of it.
+    pub fn with_input_files(mut self, input_files: Vec<PathBuf>) -> Self {
+        let mut seen = HashSet::new();
+        self.input_files = input_files
+            .into_iter()
+            .map(absolute_path)
+            .filter(|path| seen.insert(path.clone()))
+            .collect();
+        if self.base_dir.is_none() {
+            self.base_dir = Some(common_ancestor(&self.input_files));
+        }
+        self
+    }
+
+    /// Builder pattern for the directory the outputs of listed input files mirror
+    pub fn with_base_dir(mut self, base_dir: PathBuf) -> Self {
+        self.base_dir = Some(absolute_path(base_dir));
+        self
+    }
+
     /// Builder pattern for setting quality
     pub fn with_quality(mut self, quality: u8) -> Self {
         self.quality = quality;
@@ -134,9 +898,59 @@ impl ConversionOptions {
     /// Builder pattern for setting compression mode
     pub fn with_mode(mut self, mode: CompressionMode) -> Self {
         self.mode = mode;
+        self.mode_explicit = true;
+        self
+    }
+
+    /// Builder pattern for converting one input format with its own mode and quality
+    ///
+    /// `extension` is matched case-insensitively, e.g. lossy JPEGs and lossless PNGs in one
+    /// run. Formats without an override use the global mode and quality.
+    pub fn with_format_override(
+        mut self,
+        extension: &str,
+        mode: CompressionMode,
+        quality: u8,
+    ) -> Self {
+        self.format_overrides
+            .insert(extension.to_lowercase(), (mode, quality));
         self
     }
 
+    /// Builder pattern for picking the quality by source file size
+    ///
+    /// Each `(min_size_kb, quality)` step applies to files of at least `min_size_kb` KB
+    /// up to the next step, so small images can stay sharp while large ones are shrunk
+    /// harder, e.g. `[(0, 90), (500, 80), (2048, 70)]`. Files below the first step keep
+    /// the global quality, and formats with an override keep theirs. Thresholds must be
+    /// strictly ascending.
+    pub fn with_quality_ramp(mut self, quality_ramp: Vec<(u64, u8)>) -> Result<Self> {
+        if let Some(window) = quality_ramp
+            .windows(2)
+            .find(|window| window[0].0 >= window[1].0)
+        {
+            anyhow::bail!(
+                "Quality ramp thresholds must be ascending ({} KB is followed by {} KB)",
+                window[0].0,
+                window[1].0
+            );
+        }
+        if let Some((_, quality)) = quality_ramp.iter().find(|(_, quality)| *quality > 100) {
+            anyhow::bail!("Invalid quality {quality} in quality ramp (expected 0-100)");
+        }
+        self.quality_ramp = quality_ramp;
+        Ok(self)
+    }
+
+    /// Quality of the quality ramp step a source of `size` bytes falls in, if any
+    pub fn ramp_quality(&self, size: u64) -> Option<u8> {
+        self.quality_ramp
+            .iter()
+            .rev()
+            .find(|(min_size_kb, _)| size >= min_size_kb.saturating_mul(1024))
+            .map(|&(_, quality)| quality)
+    }
+
     /// Builder pattern for setting output directory
     pub fn with_output_dir(mut self, output_dir: PathBuf) -> Self {
         self.output_dir = Some(output_dir);
@@ -155,37 +969,462 @@ impl ConversionOptions {
         self
     }
 
+    /// Builder pattern for exact dry run sizes (encodes every image, so it is slower)
+    ///
+    /// Only has an effect together with dry run mode.
+    pub fn with_dry_run_accurate(mut self, dry_run_accurate: bool) -> Self {
+        self.dry_run_accurate = dry_run_accurate;
+        self
+    }
+
+    /// Builder pattern for setting the target SSIM for lossy encoding
+    ///
+    /// Only lossy encodes use it, so it has no effect in lossless and near-lossless mode.
+    pub fn with_target_ssim(mut self, target_ssim: f64) -> Self {
+        self.target_ssim = Some(target_ssim);
+        self
+    }
+
+    /// Builder pattern for bounding the quality loss of smallest mode
+    ///
+    /// `CompressionMode::Smallest` keeps the lossy encode when it is smaller, unless its
+    /// SSIM against the source is below `min_ssim`; the lossless encode is kept then.
+    /// Checking costs one extra decode per image where lossy is smaller.
+    pub fn with_smallest_min_ssim(mut self, min_ssim: f64) -> Self {
+        self.smallest_min_ssim = Some(min_ssim);
+        self
+    }
+
+    /// Builder pattern for running at reduced process priority
+    pub fn with_low_priority(mut self, low_priority: bool) -> Self {
+        self.low_priority = low_priority;
+        self
+    }
+
+    /// Builder pattern for attaching a pause/resume control handle
+    pub fn with_control(mut self, control: ConversionControl) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    /// Builder pattern for limiting output write bandwidth in MB/s
+    pub fn with_write_limit_mb(mut self, write_limit: u64) -> Self {
+        self.write_limit = Some(write_limit);
+        self
+    }
+
+    /// Builder pattern for capping the memory of images decoded at the same time
+    ///
+    /// Each file reserves its estimated decoded size (width x height x 4 bytes) before
+    /// conversion and waits while other workers hold the rest of the budget, independent
+    /// of the thread count. This keeps batches of 4K/8K images from running out of memory
+    /// at the cost of some parallelism. The encoder needs additional working memory, so
+    /// leave headroom below the available RAM.
+    pub fn with_max_memory_mb(mut self, max_memory_mb: u64) -> Self {
+        self.max_memory_mb = Some(max_memory_mb);
+        self
+    }
+
+    /// Builder pattern for preserving ICC profiles and EXIF metadata
+    pub fn with_preserve_metadata(mut self, preserve_metadata: bool) -> Self {
+        self.preserve_metadata = preserve_metadata;
+        self
+    }
+
+    /// Builder pattern for writing outputs without any metadata
+    ///
+    /// No ICC profile, EXIF or XMP data is written, even with `preserve_metadata` set, and
+    /// `keep_smaller` converts instead of copying sources that still carry their metadata.
+    /// Besides saving a few kilobytes per file this keeps camera details and GPS
+    /// coordinates out of published images.
+    pub fn with_strip_metadata(mut self, strip_metadata: bool) -> Self {
+        self.strip_metadata = strip_metadata;
+        self
+    }
+
+    /// Builder pattern for applying the EXIF orientation of the source (on by default)
+    ///
+    /// Photos are often stored sideways with an orientation tag telling viewers how to
+    /// display them. The matching rotation and/or flip is applied to the pixels before
+    /// encoding and the tag is dropped (or reset when metadata is preserved). Disable this
+    /// to encode the pixels exactly as stored.
+    pub fn with_auto_orient(mut self, auto_orient: bool) -> Self {
+        self.auto_orient = auto_orient;
+        self
+    }
+
+    /// Builder pattern for retrying transient failures up to `max_retries` times
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Builder pattern for limiting how long a single file may take to convert
+    ///
+    /// Each file is converted on its own worker thread; once `timeout` has passed the file
+    /// is recorded as failed with a timeout error and the run moves on. The conversion
+    /// itself can't be interrupted, so the timed-out thread keeps running in the background
+    /// until the decoder or encoder returns, and may still write its output afterwards.
+    pub fn with_per_file_timeout(mut self, timeout: Duration) -> Self {
+        self.per_file_timeout = Some(timeout);
+        self
+    }
+
+    /// Builder pattern for keeping animations in GIF/APNG and HEIC sequence inputs
+    pub fn with_animation(mut self, animation: bool) -> Self {
+        self.animation = animation;
+        self
+    }
+
+    /// Builder pattern for capping the longest edge of the output (never upscales)
+    pub fn with_max_dimension(mut self, max_dimension: u32) -> Result<Self> {
+        if max_dimension == 0 {
+            anyhow::bail!("Invalid max dimension 0 (expected at least 1 pixel)");
+        }
+        self.max_dimension = Some(max_dimension);
+        Ok(self)
+    }
+
+    /// Builder pattern for setting the encoder effort (0-6)
+    ///
+    /// Higher effort is slower but produces smaller files at the same quality; values
+    /// above 6 are clamped.
+    pub fn with_effort(mut self, effort: u8) -> Self {
+        self.effort = effort.min(MAX_EFFORT);
+        self
+    }
+
+    /// Builder pattern for setting the lossless compression level (0-9, like `cwebp -z`)
+    ///
+    /// Applies to lossless, near-lossless and the lossless half of auto mode. Lossless
+    /// output is pixel-exact at every level, which is why `quality` has no effect there;
+    /// the level only trades encoding time for file size, overriding `effort`. Values
+    /// above 9 are clamped.
+    pub fn with_lossless_level(mut self, lossless_level: u8) -> Self {
+        self.lossless_level = Some(lossless_level.min(MAX_LOSSLESS_LEVEL));
+        self
+    }
+
+    /// Builder pattern for the lossy deblocking filter strength
+    ///
+    /// 0 turns the filter off and keeps fine detail but shows block edges at low
+    /// quality; 100 smooths the most and can blur texture. Applies to lossy encodes
+    /// only; libwebp's default is 60. Values above 100 are clamped.
+    pub fn with_filter_strength(mut self, filter_strength: u8) -> Self {
+        self.filter_strength = Some(filter_strength.min(MAX_FILTER_STRENGTH));
+        self
+    }
+
+    /// Builder pattern for the lossy filter sharpness
+    ///
+    /// 0 (libwebp's default) filters most smoothly; up to 7, the filter spares more
+    /// edges, keeping them crisper at the cost of more visible blocking. Applies to lossy
+    /// encodes only. Values above 7 are clamped.
+    pub fn with_sharpness(mut self, sharpness: u8) -> Self {
+        self.sharpness = Some(sharpness.min(MAX_SHARPNESS));
+        self
+    }
+
+    /// Builder pattern for the color count below which auto mode encodes losslessly
+    ///
+    /// Auto mode counts the distinct colors among the sampled pixels; images with fewer
+    /// than `threshold` (64 by default) are treated as graphics and encoded losslessly,
+    /// others as photos and encoded lossy. Raise it to keep more gradients lossless.
+    pub fn with_auto_color_threshold(mut self, threshold: usize) -> Self {
+        self.auto_color_threshold = threshold;
+        self
+    }
+
+    /// Builder pattern for the number of pixels auto mode samples (4096 by default)
+    ///
+    /// The samples are spread evenly over the whole image; more samples classify large
+    /// images more reliably at a small cost per image. Values below 1 are raised to 1.
+    pub fn with_auto_sample_size(mut self, sample_size: u32) -> Self {
+        self.auto_sample_size = sample_size.max(1);
+        self
+    }
+
+    /// Builder pattern for setting the alpha channel quality of lossy output (0-100)
+    ///
+    /// Lower values shrink transparent images but can cause halos around edges.
+    pub fn with_alpha_quality(mut self, alpha_quality: u8) -> Self {
+        self.alpha_quality = alpha_quality.min(100);
+        self
+    }
+
+    /// Builder pattern for setting the resampling filter used when downscaling
+    pub fn with_resize_filter(mut self, resize_filter: ResizeFilter) -> Self {
+        self.resize_filter = resize_filter;
+        self
+    }
+
+    /// Builder pattern for flattening transparency onto a solid background color
+    ///
+    /// Images with an alpha channel (including animation frames) are composited onto
+    /// `background` and encoded as opaque output, avoiding fringed edges in lossy mode.
+    /// Images without alpha are encoded unchanged. Use `utils::parse_color` to read
+    /// `#RRGGBB` strings.
+    pub fn with_background(mut self, background: Option<image::Rgba<u8>>) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Builder pattern for choosing which resolutions of ICO inputs are converted
+    pub fn with_ico_size_selection(mut self, ico_size: IcoSizeSelection) -> Self {
+        self.ico_size = ico_size;
+        self
+    }
+
+    /// Builder pattern for choosing which pages of multi-page TIFF inputs are converted
+    pub fn with_tiff_page_selection(mut self, tiff_pages: TiffPageSelection) -> Self {
+        self.tiff_pages = tiff_pages;
+        self
+    }
+
     /// Builder pattern for setting overwrite behavior
     pub fn with_overwrite(mut self, overwrite: bool) -> Self {
         self.overwrite = overwrite;
         self
     }
 
+    /// Builder pattern for keeping sources that do not shrink as WebP
+    ///
+    /// When the encoded WebP is larger than the source, the source is copied to the
+    /// output directory under its own extension instead, and counted in
+    /// `ConversionReport::kept_originals`.
+    pub fn with_keep_smaller(mut self, keep_smaller: bool) -> Self {
+        self.keep_smaller = keep_smaller;
+        self
+    }
+
+    /// Builder pattern for giving outputs the timestamps of their source
+    ///
+    /// After an output is written, its modification and access times are set to the
+    /// source's, so sorting by date still works on the WebP files. An output then counts
+    /// as up to date in incremental mode until the source changes. Dry runs write
+    /// nothing and so touch no timestamps.
+    pub fn with_preserve_timestamps(mut self, preserve_timestamps: bool) -> Self {
+        self.preserve_timestamps = preserve_timestamps;
+        self
+    }
+
     /// Builder pattern for setting preserve structure
     pub fn with_preserve_structure(mut self, preserve_structure: bool) -> Self {
         self.preserve_structure = preserve_structure;
         self
     }
 
+    /// Builder pattern for writing each WebP next to its source
+    ///
+    /// `photos/cat.jpg` becomes `photos/cat.webp` and the output directory only holds
+    /// reports, the manifest and checkpoints (it defaults to the input directory then).
+    /// Sources such as `cat.jpg` and `cat.png` in one directory collide and are handled
+    /// by the collision policy. With `ReplaceInputMode::Delete` the source is only
+    /// removed after its WebP was written and checked, so a failed conversion never
+    /// loses the original.
+    pub fn with_in_place(mut self, in_place: bool) -> Self {
+        self.in_place = in_place;
+        self
+    }
+
+    /// Builder pattern for deciding the output path of each file with a custom resolver
+    ///
+    /// The resolver replaces the output directory layout, in-place output and filename
+    /// templates, e.g. to shard outputs by hash or into date-based folders. Reports, the
+    /// manifest and checkpoints still go to the output directory.
+    pub fn with_output_path_resolver(
+        mut self,
+        resolver: impl OutputPathResolver + 'static,
+    ) -> Self {
+        self.output_path_resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Builder pattern for enabling incremental mode
+    ///
+    /// A file is skipped when its output exists and is at least as new as the source;
+    /// outputs older than their source are re-converted even without `overwrite`.
+    pub fn with_incremental(mut self, incremental: bool) -> Self {
+        self.incremental = incremental;
+        self
+    }
+
+    /// Builder pattern for enabling the content-hash manifest
+    ///
+    /// The manifest (`.webpify-manifest.json` in the output directory) records the hash
+    /// of every converted source. Files whose contents are unchanged since then are
+    /// skipped even if their modification time differs.
+    pub fn with_manifest(mut self, manifest: bool) -> Self {
+        self.manifest = manifest;
+        self
+    }
+
+    /// Builder pattern for resuming an interrupted batch
+    ///
+    /// Every run keeps a checkpoint (`.webpify-checkpoint.json` in the output directory)
+    /// of completed files and removes it when it finishes. With `resume`, files listed in
+    /// a checkpoint left by an interrupted run with the same settings are skipped.
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Builder pattern for choosing how colliding flattened outputs are handled
+    ///
+    /// Collisions are detected during the prescan; without it later files are skipped
+    /// (or overwrite earlier ones with `overwrite`).
+    pub fn with_collision_policy(mut self, collision_policy: CollisionPolicy) -> Self {
+        self.collision_policy = collision_policy;
+        self
+    }
+
+    /// Builder pattern for sorting the prescanned files before converting them
+    ///
+    /// A fixed order makes `{index}` in filename templates, collision renames and the
+    /// progress order reproducible across platforms. Sorting needs the full file list, so
+    /// it only applies when prescanning; streaming runs keep the walk order.
+    pub fn with_sort_order(mut self, sort_order: SortOrder) -> Self {
+        self.sort_order = sort_order;
+        self
+    }
+
+    /// Builder pattern for converting only the first `max_files` matching files
+    ///
+    /// Meant for trying settings on part of a large library. With prescan the cap applies
+    /// after sorting; streaming runs take the first files of the walk. The report notes
+    /// when files were left out.
+    pub fn with_max_files(mut self, max_files: usize) -> Result<Self> {
+        if max_files == 0 {
+            anyhow::bail!("Invalid file limit 0 (expected at least 1)");
+        }
+        self.max_files = Some(max_files);
+        Ok(self)
+    }
+
+    /// Builder pattern for starting the largest files first (on by default)
+    ///
+    /// With prescan the file sizes are known, so large images are handed to the workers
+    /// first and small ones fill the remaining gaps, instead of one huge image starting
+    /// last while the other cores idle. Only the processing order changes: `{index}` keeps
+    /// the scan position. An explicit sort order takes precedence, and streaming runs
+    /// (prescan off) convert files as they are found.
+    pub fn with_largest_first(mut self, largest_first: bool) -> Self {
+        self.largest_first = largest_first;
+        self
+    }
+
     /// Builder pattern for setting minimum file size in KB
     pub fn with_min_size_kb(mut self, min_size: u64) -> Self {
         self.min_size = min_size;
         self
     }
 
+    /// Builder pattern for copying files below a size in KB instead of converting them
+    ///
+    /// Unlike the minimum size, which leaves small files out of the output, files below
+    /// `passthrough_below` are copied to the output under their own extension so the
+    /// output tree stays complete. In place they are simply left as they are. The report
+    /// counts them in `passed_through`.
+    pub fn with_passthrough_below(mut self, passthrough_below: Option<u64>) -> Self {
+        self.passthrough_below = passthrough_below;
+        self
+    }
+
     /// Builder pattern for setting maximum file size in MB
     pub fn with_max_size_mb(mut self, max_size: u64) -> Self {
         self.max_size = Some(max_size);
         self
     }
 
+    /// Builder pattern for only converting files matching at least one of these globs
+    ///
+    /// Patterns are matched against the path relative to the input directory,
+    /// e.g. `photos/**/*.jpg`.
+    pub fn with_include_globs(mut self, include_globs: Vec<String>) -> Self {
+        self.include_globs = include_globs;
+        self
+    }
+
+    /// Builder pattern for skipping files matching any of these globs
+    ///
+    /// Patterns are matched against the path relative to the input directory,
+    /// e.g. `**/thumbnails/**`.
+    pub fn with_exclude_globs(mut self, exclude_globs: Vec<String>) -> Self {
+        self.exclude_globs = exclude_globs;
+        self
+    }
+
+    /// Builder pattern for only converting files modified at or after `modified_after`
+    pub fn with_modified_after(mut self, modified_after: DateTime<Utc>) -> Self {
+        self.modified_after = Some(modified_after);
+        self
+    }
+
+    /// Builder pattern for only converting files modified before `modified_before`
+    pub fn with_modified_before(mut self, modified_before: DateTime<Utc>) -> Self {
+        self.modified_before = Some(modified_before);
+        self
+    }
+
+    /// Builder pattern for skipping images smaller than `width` x `height` pixels
+    ///
+    /// Dimensions are read from the image header during the scan; an image is skipped
+    /// if either side is below the minimum.
+    pub fn with_min_dimensions(mut self, width: u32, height: u32) -> Self {
+        self.min_dimensions = Some((width, height));
+        self
+    }
+
+    /// Builder pattern for skipping images larger than `width` x `height` pixels
+    ///
+    /// An image is skipped if either side exceeds the maximum.
+    pub fn with_max_dimensions(mut self, width: u32, height: u32) -> Self {
+        self.max_dimensions = Some((width, height));
+        self
+    }
+
+    /// Builder pattern for following symlinks while scanning
+    ///
+    /// Symlink cycles and directories reachable through several links are skipped and
+    /// listed as warnings in the report.
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Builder pattern for limiting how deep the input directories are scanned
+    ///
+    /// `Some(0)` converts only files directly in the input directory, `Some(1)` also
+    /// those one subdirectory down, and `None` scans every level.
+    pub fn with_max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
     /// Builder pattern for enabling prescan
     pub fn with_prescan(mut self, prescan: bool) -> Self {
         self.prescan = prescan;
         self
     }
 
+    /// Builder pattern for checking the free space of the output volume before converting
+    ///
+    /// After the prescan, the output size is estimated from the input sizes and the
+    /// compression settings. If it exceeds the free space on the output volume the run
+    /// fails before writing anything; with the check disabled only a warning is logged.
+    /// The estimate is rough, so a run that passes can still fill the disk.
+    pub fn with_space_check(mut self, space_check: bool) -> Self {
+        self.space_check = space_check;
+        self
+    }
+
     /// Builder pattern for setting reencode WebP behavior
+    ///
+    /// Re-encoding a lossless WebP as lossy (or a lossy one losslessly) often makes it
+    /// larger, so WebP inputs are re-encoded losslessly unless a `webp` format override
+    /// or an explicit global mode sets the mode and quality. Whatever the mode, a
+    /// re-encode that is not smaller is dropped and the source kept (copied without its
+    /// metadata when metadata is stripped).
     pub fn with_reencode_webp(mut self, reencode_webp: bool) -> Self {
         self.reencode_webp = reencode_webp;
         self
@@ -197,17 +1436,295 @@ impl ConversionOptions {
         self
     }
 
+    /// Builder pattern for what happens to sources the recycle bin refused
+    ///
+    /// Applies to `ReplaceInputMode::Recycle` only. A source that could not be recycled
+    /// is never deleted: it is either kept with a warning in the report or moved into the
+    /// quarantine directory, which is created when needed. A source that can't be moved
+    /// there either is kept with a warning.
+    pub fn with_recycle_fallback(mut self, recycle_fallback: RecycleFallback) -> Result<Self> {
+        if let RecycleFallback::Quarantine(dir) = &recycle_fallback
+            && dir.as_os_str().is_empty()
+        {
+            anyhow::bail!("Quarantine directory must not be empty");
+        }
+        self.recycle_fallback = recycle_fallback;
+        Ok(self)
+    }
+
+    /// Builder pattern for the output sizes that may replace their source
+    ///
+    /// With `ReplaceInputMode::Recycle` or `Delete`, a source is only removed when its
+    /// output size is between `min_ratio` and `max_ratio` times the source size (1% to
+    /// 200% by default). A suspiciously tiny or bloated output points at an encoder
+    /// problem, so the source is kept and a warning is added to the report instead.
+    pub fn with_replace_size_limits(mut self, min_ratio: f64, max_ratio: f64) -> Result<Self> {
+        if !(min_ratio >= 0.0 && min_ratio <= max_ratio && max_ratio.is_finite()) {
+            anyhow::bail!(
+                "Invalid replace size limits {min_ratio}..{max_ratio} (expected 0 <= min <= max)"
+            );
+        }
+        self.replace_min_ratio = min_ratio;
+        self.replace_max_ratio = max_ratio;
+        Ok(self)
+    }
+
+    /// Builder pattern for verifying the outputs after the run
+    ///
+    /// Once every file is converted, each written WebP is decoded again and its size is
+    /// compared with that of the decoded source (after downscaling). Truncated or
+    /// mismatching outputs (e.g. from a disk that filled up without a write error) are
+    /// reported as errors. With `ReplaceInputMode::Recycle` or `Delete`, sources are only
+    /// removed in this pass, once their outputs have passed.
+    pub fn with_verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Builder pattern for enabling report generation
+    pub fn with_generate_report(mut self, generate_report: bool) -> Self {
+        self.generate_report = generate_report;
+        self
+    }
+
+    /// Builder pattern for setting the report format
+    pub fn with_report_format(mut self, report_format: ReportFormat) -> Self {
+        self.report_format = report_format;
+        self
+    }
+
+    /// Builder pattern for setting the report file or directory
+    pub fn with_report_path(mut self, report_path: Option<PathBuf>) -> Self {
+        self.report_path = report_path;
+        self
+    }
+
+    /// Builder pattern for inserting the run's start time into the report file name
+    ///
+    /// The UTC start time goes before the extension (`webpify_report_20240601_120000.json`,
+    /// or `runs/nightly_20240601_120000.html` for a report path of `runs/nightly.html`),
+    /// so every run keeps its own report.
+    pub fn with_report_timestamp(mut self, report_timestamp: bool) -> Self {
+        self.report_timestamp = report_timestamp;
+        self
+    }
+
+    /// Builder pattern for naming output files from a template
+    ///
+    /// Supported tokens are `{stem}`, `{ext}` (original extension), `{parent}` (name of
+    /// the containing directory) and `{index}` (1-based position in the scan). The template
+    /// must produce a file name that ends with the output extension (`.webp` by default)
+    /// and differs per input.
+    pub fn with_filename_template(mut self, filename_template: String) -> Result<Self> {
+        validate_filename_template(&filename_template, &self.output_extension)?;
+        self.filename_template = Some(filename_template);
+        Ok(self)
+    }
+
+    /// Builder pattern for the extension of the written files, without the leading dot
+    ///
+    /// For servers that expect e.g. `.WEBP` or a custom extension; the files still hold
+    /// WebP data. Extensions of other input formats (e.g. `png`) are rejected, since the
+    /// outputs would be picked up as inputs by later runs. A filename template set earlier
+    /// must end with the new extension.
+    pub fn with_output_extension(mut self, output_extension: String) -> Result<Self> {
+        let output_extension = output_extension
+            .strip_prefix('.')
+            .unwrap_or(&output_extension)
+            .to_string();
+        if output_extension.is_empty() {
+            anyhow::bail!("Output extension must not be empty");
+        }
+        if output_extension.contains(['/', '\\', '\0']) {
+            anyhow::bail!("Output extension '{output_extension}' must not contain path separators");
+        }
+        let lowercase = output_extension.to_lowercase();
+        if lowercase != OUTPUT_FORMAT
+            && crate::utils::supported_formats()
+                .iter()
+                .any(|format| crate::utils::is_same_format(format, &lowercase))
+        {
+            anyhow::bail!(
+                "Output extension '{output_extension}' is an input format; outputs would be \
+                 converted again by later runs"
+            );
+        }
+        if let Some(template) = &self.filename_template {
+            validate_filename_template(template, &output_extension)?;
+        }
+        self.output_extension = output_extension;
+        Ok(self)
+    }
+
+    /// Builder pattern for listing every written output file in the report
+    ///
+    /// Off by default because the list grows with the batch.
+    pub fn with_track_outputs(mut self, track_outputs: bool) -> Self {
+        self.track_outputs = track_outputs;
+        self
+    }
+
+    /// Builder pattern for collecting a result per file for the report
+    ///
+    /// The results appear as `files` in JSON reports and as a sibling `*.files.csv` with
+    /// one row per file next to CSV reports. Off by default because the list grows with
+    /// the batch.
+    pub fn with_track_files(mut self, track_files: bool) -> Self {
+        self.track_files = track_files;
+        self
+    }
+
+    /// Builder pattern for appending timestamped per-file results to a log file
+    ///
+    /// Works without a `log` backend, so it is also available to library users.
+    pub fn with_log_file(mut self, log_file: PathBuf) -> Self {
+        self.log_file = Some(log_file);
+        self
+    }
+
     /// Builder pattern for setting supported formats
+    ///
+    /// The keyword `all` stands for every extension webpify can read.
     pub fn with_supported_formats(mut self, formats: Vec<String>) -> Self {
         self.formats = formats;
         self
     }
 
+    /// Builder pattern for removing formats from the supported set, e.g. after `all`
+    pub fn with_excluded_formats(mut self, excluded_formats: Vec<String>) -> Self {
+        self.excluded_formats = excluded_formats;
+        self
+    }
+
+    /// Builder pattern for trusting the file header over the extension
+    ///
+    /// Every file passing the glob filters is checked for a supported signature, so a PNG
+    /// saved as `.jpg` (or an image without an extension) is converted as what it really
+    /// is and counted under its real format. The format filters apply to the detected
+    /// format. Reading a header from every file makes scans of mixed directories slower.
+    pub fn with_detect_by_content(mut self, detect_by_content: bool) -> Self {
+        self.detect_by_content = detect_by_content;
+        self
+    }
+
     /// Get the effective output directory (calculated if not set)
+    ///
+    /// In place, outputs go next to their sources and this directory only receives
+    /// reports and run state, so it defaults to the input directory.
     pub fn get_output_dir(&self) -> PathBuf {
-        self.output_dir
-            .clone()
-            .unwrap_or_else(|| self.input_dir.join("webp_output"))
+        self.output_dir.clone().unwrap_or_else(|| {
+            if self.in_place {
+                self.input_dir.clone()
+            } else {
+                self.input_dir.join("webp_output")
+            }
+        })
+    }
+
+    /// Every input directory with the output subdirectory its files are written to
+    ///
+    /// A single input directory maps straight onto the output directory. With several,
+    /// each gets a subdirectory named after it (`photos`, `photos_2`, ...) so that
+    /// same-named subtrees of different inputs do not collide. A directory given twice
+    /// (even by different paths) is only listed once. Listed input files have the base
+    /// directory as their only root.
+    pub fn input_roots(&self) -> Vec<(&Path, PathBuf)> {
+        // Listed files mirror their structure below the base directory
+        if !self.input_files.is_empty() {
+            let base_dir = self.base_dir.as_deref().unwrap_or(Path::new(""));
+            return vec![(base_dir, PathBuf::new())];
+        }
+
+        let dirs = std::iter::once(&self.input_dir).chain(&self.additional_input_dirs);
+        if self.additional_input_dirs.is_empty() {
+            return dirs.map(|dir| (dir.as_path(), PathBuf::new())).collect();
+        }
+
+        let mut used = HashMap::new();
+        let mut seen = HashSet::new();
+        dirs.filter_map(|dir| {
+            let canonical = dir.canonicalize().ok();
+            if !seen.insert(canonical.clone().unwrap_or_else(|| dir.clone())) {
+                return None;
+            }
+            let name = canonical
+                .and_then(|dir| {
+                    dir.file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                })
+                .unwrap_or_else(|| "input".to_string());
+            let count = used.entry(name.clone()).or_insert(0);
+            *count += 1;
+            let subdir = if *count == 1 {
+                name
+            } else {
+                format!("{name}_{count}")
+            };
+            Some((dir.as_path(), PathBuf::from(subdir)))
+        })
+        .collect()
+    }
+
+    /// Get the input formats that will actually be scanned
+    ///
+    /// `all` is expanded to every supported extension and excluded formats are removed.
+    /// The output format (WebP) is excluded unless `reencode_webp` opts back in,
+    /// so converted files are never picked up as inputs by accident. Front ends can show
+    /// this list to explain what a run will pick up.
+    pub fn effective_formats(&self) -> Vec<String> {
+        let mut formats: Vec<String> = Vec::new();
+        for format in &self.formats {
+            let format = format.to_lowercase();
+            let expanded = if format == ALL_FORMATS {
+                crate::utils::supported_formats()
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect()
+            } else {
+                vec![format]
+            };
+            for format in expanded {
+                if !formats.contains(&format) {
+                    formats.push(format);
+                }
+            }
+        }
+
+        formats
+            .into_iter()
+            .filter(|format| {
+                !self
+                    .excluded_formats
+                    .iter()
+                    .any(|excluded| excluded.eq_ignore_ascii_case(format))
+            })
+            .filter(|format| !self.excludes_format(format))
+            .collect()
+    }
+
+    /// Whether `format` is dropped from the input set because it is the output format
+    pub fn excludes_format(&self, format: &str) -> bool {
+        !self.reencode_webp && format.eq_ignore_ascii_case(OUTPUT_FORMAT)
+    }
+
+    /// Mode and quality per input format with an override
+    ///
+    /// Re-encoded WebP inputs always get an override, so they are only written when they
+    /// got smaller. Without an explicit `webp` override it uses the global mode when that
+    /// was set explicitly, and lossless otherwise, at the global quality.
+    pub fn effective_format_overrides(&self) -> HashMap<String, (CompressionMode, u8)> {
+        let mut overrides = self.format_overrides.clone();
+        if self.reencode_webp {
+            let mode = if self.mode_explicit {
+                self.mode.clone()
+            } else {
+                CompressionMode::Lossless
+            };
+            overrides
+                .entry(OUTPUT_FORMAT.to_string())
+                .or_insert((mode, self.quality));
+        }
+        overrides
     }
 
     /// Get the effective thread count (calculated if not set)
@@ -215,3 +1732,49 @@ impl ConversionOptions {
         self.threads.unwrap_or_else(num_cpus::get)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_extension_rejects_input_formats_and_separators() {
+        let options = || ConversionOptions::new(PathBuf::from("photos"));
+        assert_eq!(
+            options()
+                .with_output_extension(".WEBP".to_string())
+                .unwrap()
+                .output_extension,
+            "WEBP"
+        );
+        assert!(options().with_output_extension("img".to_string()).is_ok());
+        assert!(options().with_output_extension("png".to_string()).is_err());
+        assert!(options().with_output_extension("JPEG".to_string()).is_err());
+        assert!(options().with_output_extension("a/b".to_string()).is_err());
+        assert!(options().with_output_extension(String::new()).is_err());
+    }
+
+    #[test]
+    fn reencoded_webp_follows_an_explicit_global_mode() {
+        let options = ConversionOptions::new(PathBuf::from("photos")).with_reencode_webp(true);
+        assert_eq!(
+            options.effective_format_overrides().get(OUTPUT_FORMAT),
+            Some(&(CompressionMode::Lossless, 80))
+        );
+
+        let lossy = options
+            .clone()
+            .with_mode(CompressionMode::Lossy)
+            .with_quality(60);
+        assert_eq!(
+            lossy.effective_format_overrides().get(OUTPUT_FORMAT),
+            Some(&(CompressionMode::Lossy, 60))
+        );
+
+        let overridden = lossy.with_format_override("webp", CompressionMode::NearLossless, 70);
+        assert_eq!(
+            overridden.effective_format_overrides().get(OUTPUT_FORMAT),
+            Some(&(CompressionMode::NearLossless, 70))
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! Run-time control of an in-progress conversion
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+
+/// Shared handle for pausing, resuming and cancelling a running conversion
+///
+/// Clones refer to the same state, so a UI can keep one clone while the core holds
+/// another. Workers check the handle before starting each file: they park while paused
+/// and skip the remaining files once cancelled. Files already being encoded finish normally.
+#[derive(Debug, Clone, Default)]
+pub struct ConversionControl {
+    state: Arc<ControlState>,
+}
+
+#[derive(Debug, Default)]
+struct ControlState {
+    paused: Mutex<bool>,
+    resumed: Condvar,
+    cancelled: AtomicBool,
+}
+
+impl ConversionControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop workers from starting new files until [`resume`](Self::resume) is called
+    pub fn pause(&self) {
+        *self.lock_paused() = true;
+    }
+
+    /// Let parked workers continue with the remaining files
+    pub fn resume(&self) {
+        *self.lock_paused() = false;
+        self.state.resumed.notify_all();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.lock_paused()
+    }
+
+    /// Stop the run: no new files start, and paused workers are released
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::SeqCst);
+        self.resume();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Block the calling worker for as long as the conversion is paused and not cancelled
+    pub fn wait_while_paused(&self) {
+        let mut paused = self.lock_paused();
+        while *paused && !self.is_cancelled() {
+            paused = self
+                .state
+                .resumed
+                .wait(paused)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    fn lock_paused(&self) -> std::sync::MutexGuard<'_, bool> {
+        self.state
+            .paused
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn paused_workers_wait_until_resumed() {
+        let control = ConversionControl::new();
+        control.pause();
+
+        let worker_control = control.clone();
+        let worker = thread::spawn(move || worker_control.wait_while_paused());
+        thread::sleep(Duration::from_millis(50));
+        assert!(!worker.is_finished());
+
+        control.resume();
+        worker.join().unwrap();
+        assert!(!control.is_paused());
+    }
+
+    #[test]
+    fn cancelling_releases_paused_workers() {
+        let control = ConversionControl::new();
+        control.pause();
+
+        let worker_control = control.clone();
+        let worker = thread::spawn(move || worker_control.wait_while_paused());
+        control.cancel();
+        worker.join().unwrap();
+        assert!(control.is_cancelled());
+    }
+}
@@ -1,17 +1,160 @@
 use anyhow::{Context, Result};
-use image::{DynamicImage, GenericImageView};
-use std::path::Path;
-use webp::{Encoder, WebPMemory};
+use image::codecs::{gif::GifDecoder, png::PngDecoder};
+use image::{AnimationDecoder, Delay, DynamicImage, Frame, GenericImageView, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use webp::{AnimEncoder, AnimFrame, Encoder, WebPConfig, WebPMemory};
 
-use crate::CompressionMode;
+use crate::{
+    CompressionMode, IcoSizeSelection, ResizeFilter, TiffPageSelection,
+    config::{ConversionOptions, OUTPUT_FORMAT},
+    heif, ico,
+    metadata::{self, ImageMetadata},
+    metrics,
+    stats::ErrorKind,
+    tiff_pages,
+    utils::{WriteThrottle, input_format},
+};
 
+/// Maximum number of encodes tried while searching for a target SSIM
+const MAX_SSIM_ATTEMPTS: u32 = 7;
+
+/// Encoder effort used unless configured otherwise (libwebp's default method)
+pub const DEFAULT_EFFORT: u8 = 4;
+/// Highest encoder effort libwebp supports
+pub const MAX_EFFORT: u8 = 6;
+/// Highest lossless compression level (`cwebp -z`)
+pub const MAX_LOSSLESS_LEVEL: u8 = 9;
+/// Strongest lossy deblocking filter
+pub const MAX_FILTER_STRENGTH: u8 = 100;
+/// Highest lossy filter sharpness
+pub const MAX_SHARPNESS: u8 = 7;
+/// Auto mode picks lossless for images with fewer distinct sampled colors than this
+pub const DEFAULT_AUTO_COLOR_THRESHOLD: usize = 64;
+/// Number of pixels auto mode samples to count colors
+pub const DEFAULT_AUTO_SAMPLE_SIZE: u32 = 4096;
+
+/// Lossless effort used without a lossless level, libwebp's default (as in level 6)
+const DEFAULT_LOSSLESS_EFFORT: f32 = 75.0;
+
+/// Encoder method and lossless effort for each lossless level, as in libwebp's
+/// `WebPConfigLosslessPreset`
+const LOSSLESS_PRESETS: [(i32, f32); MAX_LOSSLESS_LEVEL as usize + 1] = [
+    (0, 0.0),
+    (1, 20.0),
+    (2, 25.0),
+    (3, 30.0),
+    (3, 50.0),
+    (4, 50.0),
+    (4, 75.0),
+    (4, 90.0),
+    (5, 90.0),
+    (6, 100.0),
+];
+
+/// Largest width and height WebP supports
+const MAX_WEBP_DIMENSION: u32 = 16383;
+
+/// Frame delays at or below this are treated as 100ms, matching how browsers play GIFs
+const MIN_FRAME_DELAY_MS: u32 = 10;
+const DEFAULT_FRAME_DELAY_MS: u32 = 100;
+
+/// Output files of one input, and the quality search made for them
+type EncodedOutputs = (Vec<EncodedOutput>, Option<QualitySearchResult>);
+
+/// One output file with its WebP data
+struct EncodedOutput {
+    path: PathBuf,
+    data: Vec<u8>,
+    /// Size of the decoded source image fitted into the dimension limits, which the
+    /// written file must have
+    expected_dimensions: (u32, u32),
+}
+
+/// Result of converting a single image
+#[derive(Debug, Clone, Default)]
+pub struct ConversionOutcome {
+    pub original_size: u64,
+    pub compressed_size: u64,
+    /// True when the output already existed and the file was left untouched
+    pub skipped: bool,
+    /// Quality picked by the SSIM search, when a target SSIM is configured
+    pub quality_search: Option<QualitySearchResult>,
+    /// Files written for this input; empty for skipped files and dry runs
+    pub output_paths: Vec<PathBuf>,
+    /// True when the WebP was larger than the source and the source was kept instead
+    pub kept_original: bool,
+    /// Width and height each file of `output_paths` must have: the decoded source size,
+    /// scaled down to the dimension limits. Empty when the source was kept instead
+    pub output_dimensions: Vec<(u32, u32)>,
+    /// True when the source was below the passthrough size and copied without converting
+    pub passed_through: bool,
+}
+
+/// Quality chosen for an image by the target SSIM search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QualitySearchResult {
+    pub quality: u8,
+    pub ssim: f64,
+    pub attempts: u32,
+}
+
+#[derive(Clone)]
 pub struct ImageConverter {
     quality: f32,
     mode: CompressionMode,
-    // Ultra-fast mode for maximum performance
-    ultra_fast: bool,
+    // Encoder effort (libwebp "method"): 0 is fastest, 6 gives the smallest files
+    effort: u8,
+    // Quality of the alpha channel in lossy mode (100 keeps it lossless)
+    alpha_quality: u8,
+    // Lossless compression level (0-9); overrides effort for lossless encodes when set
+    lossless_level: Option<u8>,
+    // Lossy deblocking filter strength (0-100); the encoder default when unset
+    filter_strength: Option<u8>,
+    // Lossy filter sharpness (0-7); the encoder default when unset
+    sharpness: Option<u8>,
+    // Auto mode: sampled distinct colors below which an image is encoded losslessly
+    auto_color_threshold: usize,
+    // Auto mode: number of pixels sampled to count colors
+    auto_sample_size: u32,
+    // Copy the source to the output instead when the WebP would be larger
+    keep_smaller: bool,
+    // Give outputs the modification and access times of their source
+    preserve_timestamps: bool,
+    // Take the input format from the file header instead of the extension
+    detect_by_content: bool,
     // Dry run mode - preview without actual conversion
     dry_run: bool,
+    // In dry run mode, encode in memory to report the real output size
+    dry_run_accurate: bool,
+    // Perceptual quality target for lossy encoding (searches quality per image)
+    target_ssim: Option<f64>,
+    // Lowest SSIM a lossy result may have to be picked in smallest mode
+    smallest_min_ssim: Option<f64>,
+    // Shared limit on output write bandwidth (clones share the same limit)
+    write_throttle: Option<Arc<WriteThrottle>>,
+    // Carry ICC profiles and EXIF data over to the output
+    preserve_metadata: bool,
+    // Never write metadata, even when preserve_metadata is set
+    strip_metadata: bool,
+    // Rotate/flip images upright according to their EXIF orientation
+    auto_orient: bool,
+    // Encode multi-frame GIF/APNG and HEIC sequence inputs as animated WebP
+    animation: bool,
+    // Which resolutions of an ICO file to convert
+    ico_size: IcoSizeSelection,
+    // Which pages of a multi-page TIFF to convert
+    tiff_pages: TiffPageSelection,
+    // Longest edge allowed in the output; larger images are downscaled
+    max_dimension: Option<u32>,
+    // Resampling filter used for any downscaling
+    resize_filter: ResizeFilter,
+    // Color that transparent images are flattened onto
+    background: Option<Rgba<u8>>,
 }
 
 impl ImageConverter {
@@ -19,52 +162,600 @@ impl ImageConverter {
         Self {
             quality: quality as f32,
             mode: mode.clone(),
-            ultra_fast: true,
+            effort: DEFAULT_EFFORT,
+            alpha_quality: 100,
+            lossless_level: None,
+            filter_strength: None,
+            sharpness: None,
+            auto_color_threshold: DEFAULT_AUTO_COLOR_THRESHOLD,
+            auto_sample_size: DEFAULT_AUTO_SAMPLE_SIZE,
+            keep_smaller: false,
+            preserve_timestamps: false,
+            detect_by_content: false,
             dry_run,
+            dry_run_accurate: false,
+            target_ssim: None,
+            smallest_min_ssim: None,
+            write_throttle: None,
+            preserve_metadata: false,
+            strip_metadata: false,
+            auto_orient: true,
+            animation: true,
+            ico_size: IcoSizeSelection::Largest,
+            tiff_pages: TiffPageSelection::First,
+            max_dimension: None,
+            resize_filter: ResizeFilter::Lanczos3,
+            background: None,
         }
     }
 
-    pub fn convert_to_webp(&self, input_path: &Path, output_path: &Path) -> Result<(u64, u64)> {
+    /// Create a converter configured from conversion options
+    pub fn from_options(options: &ConversionOptions) -> Self {
+        Self::new_with_dry_run(options.quality, &options.mode, options.dry_run)
+            .with_target_ssim(options.target_ssim)
+            .with_smallest_min_ssim(options.smallest_min_ssim)
+            .with_write_limit_mb(options.write_limit)
+            .with_preserve_metadata(options.preserve_metadata)
+            .with_strip_metadata(options.strip_metadata)
+            .with_auto_orient(options.auto_orient)
+            .with_animation(options.animation)
+            .with_ico_size_selection(options.ico_size.clone())
+            .with_tiff_page_selection(options.tiff_pages.clone())
+            .with_max_dimension(options.max_dimension)
+            .with_resize_filter(options.resize_filter.clone())
+            .with_background(options.background)
+            .with_dry_run_accurate(options.dry_run_accurate)
+            .with_effort(options.effort)
+            .with_alpha_quality(options.alpha_quality)
+            .with_lossless_level(options.lossless_level)
+            .with_filter_strength(options.filter_strength)
+            .with_sharpness(options.sharpness)
+            .with_auto_color_threshold(options.auto_color_threshold)
+            .with_auto_sample_size(options.auto_sample_size)
+            .with_keep_smaller(options.keep_smaller)
+            .with_preserve_timestamps(options.preserve_timestamps)
+            .with_detect_by_content(options.detect_by_content)
+    }
+
+    /// Builder pattern for setting the alpha channel quality of lossy output (0-100)
+    pub fn with_alpha_quality(mut self, alpha_quality: u8) -> Self {
+        self.alpha_quality = alpha_quality.min(100);
+        self
+    }
+
+    /// Builder pattern for setting the encoder effort (0-6, higher is slower but smaller)
+    pub fn with_effort(mut self, effort: u8) -> Self {
+        self.effort = effort.min(MAX_EFFORT);
+        self
+    }
+
+    /// Builder pattern for setting the lossless compression level (0-9, higher is slower but smaller)
+    pub fn with_lossless_level(mut self, lossless_level: Option<u8>) -> Self {
+        self.lossless_level = lossless_level.map(|level| level.min(MAX_LOSSLESS_LEVEL));
+        self
+    }
+
+    /// Builder pattern for setting the lossy deblocking filter strength (0-100)
+    pub fn with_filter_strength(mut self, filter_strength: Option<u8>) -> Self {
+        self.filter_strength = filter_strength.map(|strength| strength.min(MAX_FILTER_STRENGTH));
+        self
+    }
+
+    /// Builder pattern for setting the lossy filter sharpness (0-7, higher is sharper)
+    pub fn with_sharpness(mut self, sharpness: Option<u8>) -> Self {
+        self.sharpness = sharpness.map(|sharpness| sharpness.min(MAX_SHARPNESS));
+        self
+    }
+
+    /// Builder pattern for setting the color count below which auto mode picks lossless
+    pub fn with_auto_color_threshold(mut self, auto_color_threshold: usize) -> Self {
+        self.auto_color_threshold = auto_color_threshold;
+        self
+    }
+
+    /// Builder pattern for setting how many pixels auto mode samples (at least 1)
+    pub fn with_auto_sample_size(mut self, auto_sample_size: u32) -> Self {
+        self.auto_sample_size = auto_sample_size.max(1);
+        self
+    }
+
+    /// Builder pattern for keeping the source when its WebP would be larger
+    pub fn with_keep_smaller(mut self, keep_smaller: bool) -> Self {
+        self.keep_smaller = keep_smaller;
+        self
+    }
+
+    /// Builder pattern for giving written outputs the timestamps of their source
+    pub fn with_preserve_timestamps(mut self, preserve_timestamps: bool) -> Self {
+        self.preserve_timestamps = preserve_timestamps;
+        self
+    }
+
+    /// Builder pattern for decoding inputs by their header instead of their extension
+    pub fn with_detect_by_content(mut self, detect_by_content: bool) -> Self {
+        self.detect_by_content = detect_by_content;
+        self
+    }
+
+    /// Builder pattern for encoding in memory during dry runs to report exact sizes
+    pub fn with_dry_run_accurate(mut self, dry_run_accurate: bool) -> Self {
+        self.dry_run_accurate = dry_run_accurate;
+        self
+    }
+
+    /// Builder pattern for choosing the resampling filter used when downscaling
+    pub fn with_resize_filter(mut self, resize_filter: ResizeFilter) -> Self {
+        self.resize_filter = resize_filter;
+        self
+    }
+
+    /// Builder pattern for flattening transparency onto a solid background color
+    pub fn with_background(mut self, background: Option<Rgba<u8>>) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Builder pattern for capping the longest edge of the output
+    pub fn with_max_dimension(mut self, max_dimension: Option<u32>) -> Self {
+        self.max_dimension = max_dimension;
+        self
+    }
+
+    /// Builder pattern for choosing which resolutions of ICO files are converted
+    pub fn with_ico_size_selection(mut self, ico_size: IcoSizeSelection) -> Self {
+        self.ico_size = ico_size;
+        self
+    }
+
+    /// Builder pattern for choosing which pages of multi-page TIFF files are converted
+    pub fn with_tiff_page_selection(mut self, tiff_pages: TiffPageSelection) -> Self {
+        self.tiff_pages = tiff_pages;
+        self
+    }
+
+    /// Builder pattern for converting animated GIF/APNG and HEIC sequence inputs to animated WebP
+    pub fn with_animation(mut self, animation: bool) -> Self {
+        self.animation = animation;
+        self
+    }
+
+    /// Builder pattern for keeping ICC profiles and EXIF data in the output
+    pub fn with_preserve_metadata(mut self, preserve_metadata: bool) -> Self {
+        self.preserve_metadata = preserve_metadata;
+        self
+    }
+
+    /// Builder pattern for writing outputs without ICC, EXIF or XMP metadata
+    pub fn with_strip_metadata(mut self, strip_metadata: bool) -> Self {
+        self.strip_metadata = strip_metadata;
+        self
+    }
+
+    /// Builder pattern for applying the EXIF orientation to the pixels
+    pub fn with_auto_orient(mut self, auto_orient: bool) -> Self {
+        self.auto_orient = auto_orient;
+        self
+    }
+
+    /// Builder pattern for setting the SSIM that lossy encoding should reach
+    pub fn with_target_ssim(mut self, target_ssim: Option<f64>) -> Self {
+        self.target_ssim = target_ssim;
+        self
+    }
+
+    /// Builder pattern for setting the SSIM a lossy result needs to win in smallest mode
+    pub fn with_smallest_min_ssim(mut self, smallest_min_ssim: Option<f64>) -> Self {
+        self.smallest_min_ssim = smallest_min_ssim;
+        self
+    }
+
+    /// Builder pattern for capping output writes at `write_limit` MB/s
+    pub fn with_write_limit_mb(mut self, write_limit: Option<u64>) -> Self {
+        self.write_throttle = write_limit.map(|mb| Arc::new(WriteThrottle::new(mb * 1024 * 1024)));
+        self
+    }
+
+    /// Builder pattern for setting the quality (0-100)
+    pub fn with_quality(mut self, quality: u8) -> Self {
+        self.quality = quality as f32;
+        self
+    }
+
+    /// Builder pattern for setting the compression mode
+    pub fn with_mode(mut self, mode: CompressionMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn convert_to_webp(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+    ) -> Result<ConversionOutcome> {
         let original_size = std::fs::metadata(input_path)?.len();
 
-        // Dry run mode: only analyze without converting
-        if self.dry_run {
+        // Dry run mode: only analyze without converting; the output size stays unknown (0)
+        if self.dry_run && !self.dry_run_accurate {
             self.analyze_conversion(input_path, output_path)?;
-            return Ok((original_size, (original_size as f64 * 0.6) as u64));
+            return Ok(ConversionOutcome {
+                original_size,
+                ..Default::default()
+            });
+        }
+
+        let (outputs, quality_search) = self.encode_outputs(input_path, output_path)?;
+        let encoded_size: u64 = outputs.iter().map(|output| output.data.len() as u64).sum();
+
+        // Already well-compressed sources (optimized PNGs, tiny images) can grow as WebP
+        // A copied source would still carry the metadata that stripping promises to remove,
+        // except a WebP source, whose metadata chunks are dropped from the copy
+        let source_is_webp = input_format(input_path, self.detect_by_content) == OUTPUT_FORMAT;
+        if self.keep_smaller
+            && (!self.strip_metadata || source_is_webp)
+            && encoded_size > original_size
+        {
+            return self.keep_original(input_path, output_path, original_size, encoded_size);
+        }
+
+        // Accurate dry run: the encoded size is known, nothing is written
+        if self.dry_run {
+            let compressed_size = encoded_size;
+            log::info!(
+                "[DRY RUN] {} -> {} ({} -> {})",
+                input_path.display(),
+                output_path.display(),
+                humansize::format_size(original_size, humansize::DECIMAL),
+                humansize::format_size(compressed_size, humansize::DECIMAL)
+            );
+            return Ok(ConversionOutcome {
+                original_size,
+                compressed_size,
+                quality_search,
+                ..Default::default()
+            });
+        }
+
+        let mut compressed_size = 0;
+        let mut output_dimensions = Vec::with_capacity(outputs.len());
+        for EncodedOutput {
+            path,
+            data,
+            expected_dimensions,
+        } in &outputs
+        {
+            output_dimensions.push(*expected_dimensions);
+            self.save_webp_data_fast(data, path)?;
+            compressed_size += std::fs::metadata(path)?.len();
+            if self.preserve_timestamps {
+                copy_file_times(input_path, path);
+            }
+        }
+
+        Ok(ConversionOutcome {
+            original_size,
+            compressed_size,
+            quality_search,
+            output_paths: outputs.into_iter().map(|output| output.path).collect(),
+            output_dimensions,
+            ..Default::default()
+        })
+    }
+
+    /// Copy the source next to where its WebP would go, keeping the source extension
+    fn keep_original(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+        original_size: u64,
+        encoded_size: u64,
+    ) -> Result<ConversionOutcome> {
+        let kept_path = output_path.with_extension(input_path.extension().unwrap_or_default());
+        log::debug!(
+            "Keeping {} as WebP would be larger ({} -> {})",
+            input_path.display(),
+            humansize::format_size(original_size, humansize::DECIMAL),
+            humansize::format_size(encoded_size, humansize::DECIMAL)
+        );
+
+        let mut outcome = ConversionOutcome {
+            original_size,
+            compressed_size: original_size,
+            kept_original: true,
+            ..Default::default()
+        };
+        if self.dry_run {
+            return Ok(outcome);
+        }
+
+        // Converting in place would copy the source onto itself
+        let is_source =
+            kept_path.exists() && kept_path.canonicalize().ok() == input_path.canonicalize().ok();
+        if !is_source {
+            if self.strip_metadata {
+                let stripped = metadata::strip_metadata(&std::fs::read(input_path)?)?;
+                outcome.compressed_size = stripped.len() as u64;
+                self.write_stripped(input_path, &kept_path, &stripped)?;
+            } else {
+                self.copy_source(input_path, &kept_path, original_size)?;
+            }
+        }
+        outcome.output_paths.push(kept_path);
+        Ok(outcome)
+    }
+
+    /// Copy the source to `output_path` unchanged instead of converting it
+    ///
+    /// Used for files below the passthrough size, so the output tree stays complete while
+    /// only larger files are converted. Nothing is written in dry runs or when
+    /// `output_path` is the source itself (in-place output).
+    pub fn pass_through(&self, input_path: &Path, output_path: &Path) -> Result<ConversionOutcome> {
+        let original_size = std::fs::metadata(input_path)?.len();
+        let mut outcome = ConversionOutcome {
+            original_size,
+            compressed_size: original_size,
+            passed_through: true,
+            ..Default::default()
+        };
+        if self.dry_run {
+            log::info!(
+                "[DRY RUN] {} -> {} (copied unchanged)",
+                input_path.display(),
+                output_path.display()
+            );
+            return Ok(outcome);
+        }
+
+        let is_source = output_path.exists()
+            && output_path.canonicalize().ok() == input_path.canonicalize().ok();
+        if !is_source {
+            self.copy_source(input_path, output_path, original_size)?;
+        }
+        outcome.output_paths.push(output_path.to_path_buf());
+        Ok(outcome)
+    }
+
+    /// Copy `input_path` to `target` within the write limit, keeping its timestamps if set
+    fn copy_source(&self, input_path: &Path, target: &Path, size: u64) -> Result<()> {
+        if let Some(throttle) = &self.write_throttle {
+            throttle.acquire(size);
+        }
+        std::fs::copy(input_path, target)
+            .with_context(|| format!("Failed to copy original to: {}", target.display()))?;
+        if self.preserve_timestamps {
+            copy_file_times(input_path, target);
+        }
+        Ok(())
+    }
+
+    /// Write a WebP source copied without its metadata chunks, like `copy_source`
+    fn write_stripped(&self, input_path: &Path, target: &Path, data: &[u8]) -> Result<()> {
+        if let Some(throttle) = &self.write_throttle {
+            throttle.acquire(data.len() as u64);
+        }
+        crate::utils::write_atomically(target, data)
+            .with_context(|| format!("Failed to copy original to: {}", target.display()))?;
+        if self.preserve_timestamps {
+            copy_file_times(input_path, target);
+        }
+        Ok(())
+    }
+
+    /// Encode an input file in memory, returning every output file with its WebP data
+    ///
+    /// Most inputs produce a single file at `output_path`; ICO files with the `All` size
+    /// selection produce one per resolution.
+    fn encode_outputs(&self, input_path: &Path, output_path: &Path) -> Result<EncodedOutputs> {
+        let format = input_format(input_path, self.detect_by_content);
+
+        // Animated inputs are encoded frame by frame; single-frame files take the fast path
+        if self.animation
+            && let Some(frames) = self.decode_animation(input_path, &format)?
+        {
+            log::debug!(
+                "Encoding {} frames of {} as animated WebP",
+                frames.len(),
+                input_path.display()
+            );
+            let frames = self.resize_frames(frames)?;
+            let webp_data = self.encode_animation(&frames)?;
+            let output = EncodedOutput {
+                path: output_path.to_path_buf(),
+                data: webp_data.to_vec(),
+                expected_dimensions: frames[0].buffer().dimensions(),
+            };
+            return Ok((vec![output], None));
+        }
+
+        // Every resolution of an icon becomes its own output file
+        if self.ico_size == IcoSizeSelection::All && format == "ico" {
+            let mut outputs = Vec::new();
+            for image in ico::decode_all(input_path)? {
+                let (width, height) = image.dimensions();
+                let image = self.validate_and_resize_image(&image)?.unwrap_or(image);
+                let (webp_data, _) = self.encode_image(&image, "ico")?;
+                outputs.push(EncodedOutput {
+                    path: sized_output_path(output_path, width, height),
+                    data: webp_data.to_vec(),
+                    expected_dimensions: self.fitted_dimensions(width, height),
+                });
+            }
+            return Ok((outputs, None));
+        }
+
+        // Every page of a multi-page TIFF (e.g. a scanned document) becomes its own file
+        if is_tiff_format(&format) {
+            let page_count = match tiff_pages::page_count(input_path) {
+                Ok(page_count) => page_count,
+                Err(e) => {
+                    log::debug!("Could not count TIFF pages: {e:#}");
+                    1
+                }
+            };
+            if page_count > 1 && self.tiff_pages == TiffPageSelection::All {
+                log::info!(
+                    "Converting {} pages of {}",
+                    page_count,
+                    input_path.display()
+                );
+                let mut outputs = Vec::new();
+                for (index, page) in tiff_pages::decode_pages(input_path)?
+                    .into_iter()
+                    .enumerate()
+                {
+                    let (width, height) = page.dimensions();
+                    let page = self.validate_and_resize_image(&page)?.unwrap_or(page);
+                    let (webp_data, _) = self.encode_image(&page, "tiff")?;
+                    outputs.push(EncodedOutput {
+                        path: page_output_path(output_path, index + 1),
+                        data: webp_data.to_vec(),
+                        expected_dimensions: self.fitted_dimensions(width, height),
+                    });
+                }
+                return Ok((outputs, None));
+            }
+            if page_count > 1 {
+                log::info!(
+                    "Converting only the first of {} pages of {}",
+                    page_count,
+                    input_path.display()
+                );
+            }
         }
 
         // Performance: Read image with optimized buffer size
-        let img = image::open(input_path)
-            .with_context(|| format!("Failed to read image: {}", input_path.display()))?;
+        let (img, metadata) = if self.preserve_metadata
+            && !self.strip_metadata
+            && !is_heif_format(&format)
+            && format != "ico"
+        {
+            metadata::open_with_metadata(input_path, self.auto_orient)?
+        } else {
+            (
+                self.open_image(input_path, &format)?,
+                ImageMetadata::default(),
+            )
+        };
 
+        let (width, height) = img.dimensions();
         // Validate and potentially resize image to fit WebP constraints
         let processed_img = match self.validate_and_resize_image(&img)? {
             Some(resized) => resized,
             None => img, // Use original image without cloning
         };
 
+        let (webp_data, quality_search) = self.encode_image(&processed_img, &format)?;
+
+        let webp_data = if metadata.is_empty() {
+            webp_data.to_vec()
+        } else {
+            metadata::embed_metadata(&webp_data, &metadata)?
+        };
+
+        let output = EncodedOutput {
+            path: output_path.to_path_buf(),
+            data: webp_data,
+            expected_dimensions: self.fitted_dimensions(width, height),
+        };
+        Ok((vec![output], quality_search))
+    }
+
+    /// Encode an image file held in memory to WebP without touching the filesystem
+    ///
+    /// The format is detected from the data. Mode selection and downscaling work as for
+    /// files; animations are encoded as their first frame and metadata is not kept.
+    pub fn convert_bytes(&self, input: &[u8]) -> Result<Vec<u8>> {
+        let format = image::guess_format(input).context("Unrecognized image data")?;
+        let img = if self.auto_orient {
+            metadata::load_oriented_from_memory(input, format)?
+        } else {
+            image::load_from_memory_with_format(input, format)
+                .context("Failed to decode image data")?
+        };
+        let extension = format.extensions_str().first().copied().unwrap_or_default();
+        self.encode_in_memory(&img, extension)
+    }
+
+    /// Encode a decoded image to WebP without touching the filesystem
+    ///
+    /// Without a source format, auto mode decides by image content alone.
+    pub fn convert_image(&self, img: &DynamicImage) -> Result<Vec<u8>> {
+        self.encode_in_memory(img, "")
+    }
+
+    fn encode_in_memory(&self, img: &DynamicImage, extension: &str) -> Result<Vec<u8>> {
+        let resized = self.validate_and_resize_image(img)?;
+        let (webp_data, _) = self.encode_image(resized.as_ref().unwrap_or(img), extension)?;
+        Ok(webp_data.to_vec())
+    }
+
+    /// Encode an image with the configured compression mode
+    ///
+    /// `extension` is the lowercase source extension, used by auto mode as a hint.
+    fn encode_image(
+        &self,
+        img: &DynamicImage,
+        extension: &str,
+    ) -> Result<(WebPMemory, Option<QualitySearchResult>)> {
+        let flattened = self
+            .background
+            .filter(|_| img.color().has_alpha())
+            .map(|background| DynamicImage::ImageRgb8(flatten_alpha(&img.to_rgba8(), background)));
+        let img = flattened.as_ref().unwrap_or(img);
+
         // Choose conversion strategy based on mode
-        match self.mode {
-            CompressionMode::Lossless => self.convert_lossless_fast(&processed_img, output_path),
-            CompressionMode::Lossy => self.convert_lossy_fast(&processed_img, output_path),
+        Ok(match self.mode {
+            CompressionMode::Lossless => (self.encode_lossless_fast(img)?, None),
+            CompressionMode::Lossy => self.encode_lossy_fast(img)?,
+            CompressionMode::NearLossless => (self.encode_near_lossless(img)?, None),
+            CompressionMode::Smallest => self.encode_smallest(img)?,
             CompressionMode::Auto => {
-                self.convert_auto_fast(&processed_img, output_path, input_path)
+                // Smart strategy selection: automatically choose compression mode based on image characteristics
+                if self.should_use_lossless_fast(img, extension) {
+                    (self.encode_lossless_fast(img)?, None)
+                } else {
+                    self.encode_lossy_fast(img)?
+                }
             }
-        }?;
+        })
+    }
 
-        let compressed_size = std::fs::metadata(output_path)?.len();
-        Ok((original_size, compressed_size))
+    /// Decode a still image of the given format, dispatching HEIC/HEIF files to libheif
+    ///
+    /// ICO files yield the resolution picked by the ICO size selection; with `All`
+    /// (handled separately when converting) the largest one is used. Other formats are
+    /// decoded by what their data looks like.
+    fn open_image(&self, input_path: &Path, format: &str) -> Result<DynamicImage> {
+        if is_heif_format(format) {
+            return heif::open_primary(input_path);
+        }
+        if format == "ico" {
+            let mut images = ico::decode_all(input_path)?.into_iter();
+            let image = if self.ico_size == IcoSizeSelection::Smallest {
+                images.next_back()
+            } else {
+                images.next()
+            };
+            return image.context("ICO file contains no images");
+        }
+        if self.auto_orient {
+            return metadata::open_oriented(input_path);
+        }
+        image::ImageReader::open(input_path)
+            .and_then(|reader| reader.with_guessed_format())
+            .map_err(image::ImageError::IoError)
+            .and_then(|reader| reader.decode())
+            .with_context(|| format!("Failed to read image: {}", input_path.display()))
     }
 
     /// Analyze conversion without actually performing it (dry run mode)
     fn analyze_conversion(&self, input_path: &Path, output_path: &Path) -> Result<()> {
         // Read image to analyze but don't convert
-        let img = image::open(input_path)
-            .with_context(|| format!("Failed to read image: {}", input_path.display()))?;
+        let format = input_format(input_path, self.detect_by_content);
+        let img = self.open_image(input_path, &format)?;
 
         let (width, height) = img.dimensions();
         let compression_mode = if matches!(self.mode, CompressionMode::Auto) {
-            if self.should_use_lossless_fast(&img, input_path) {
+            if self.should_use_lossless_fast(&img, &format) {
                 "lossless"
             } else {
                 "lossy"
@@ -73,6 +764,8 @@ impl ImageConverter {
             match self.mode {
                 CompressionMode::Lossless => "lossless",
                 CompressionMode::Lossy => "lossy",
+                CompressionMode::NearLossless => "near-lossless",
+                CompressionMode::Smallest => "smallest of lossless and lossy",
                 CompressionMode::Auto => unreachable!(),
             }
         };
@@ -90,57 +783,327 @@ impl ImageConverter {
         Ok(())
     }
 
-    fn convert_lossless_fast(&self, img: &DynamicImage, output_path: &Path) -> Result<()> {
-        let encoder = Encoder::from_image(img)
-            .map_err(|e| anyhow::anyhow!("Failed to create encoder: {}", e))?;
+    fn encode_lossless_fast(&self, img: &DynamicImage) -> Result<WebPMemory> {
+        let config = self.encoder_config(&CompressionMode::Lossless)?;
+        self.encode_with_config(img, &config)
+    }
+
+    /// Encode losslessly and lossy in memory and keep whichever is smaller
+    ///
+    /// A smaller lossy result is only kept when its SSIM against the source reaches
+    /// `smallest_min_ssim`, if one is set; otherwise the lossless result wins.
+    fn encode_smallest(
+        &self,
+        img: &DynamicImage,
+    ) -> Result<(WebPMemory, Option<QualitySearchResult>)> {
+        let lossless = self.encode_lossless_fast(img)?;
+        let (lossy, quality_search) = self.encode_lossy_fast(img)?;
+        if lossy.len() >= lossless.len() {
+            log::debug!(
+                "Smallest mode: lossless wins ({} vs {} bytes lossy)",
+                lossless.len(),
+                lossy.len()
+            );
+            return Ok((lossless, None));
+        }
+
+        if let Some(min_ssim) = self.smallest_min_ssim {
+            let decoded = image::load_from_memory_with_format(&lossy, image::ImageFormat::WebP)
+                .context("Failed to decode lossy WebP candidate for SSIM measurement")?;
+            let ssim = metrics::ssim(img, &decoded);
+            if ssim < min_ssim {
+                log::debug!(
+                    "Smallest mode: lossless wins, lossy SSIM {:.4} is below {:.4}",
+                    ssim,
+                    min_ssim
+                );
+                return Ok((lossless, None));
+            }
+        }
+
+        log::debug!(
+            "Smallest mode: lossy wins ({} vs {} bytes lossless)",
+            lossy.len(),
+            lossless.len()
+        );
+        Ok((lossy, quality_search))
+    }
+
+    /// Lossless encoding with near-lossless preprocessing, using quality as the level
+    fn encode_near_lossless(&self, img: &DynamicImage) -> Result<WebPMemory> {
+        let config = self.encoder_config(&CompressionMode::NearLossless)?;
+        self.encode_with_config(img, &config)
+    }
+
+    /// Advanced encoder settings for `mode`
+    ///
+    /// Auto and smallest mode map to lossless here; they are only used for animations,
+    /// which are typically palette-based graphics.
+    fn encoder_config(&self, mode: &CompressionMode) -> Result<WebPConfig> {
+        let mut config = WebPConfig::new()
+            .map_err(|_| anyhow::anyhow!("Failed to initialize WebP encoder configuration"))?;
+        config.method = self.effort as i32;
+
+        match mode {
+            CompressionMode::Lossless | CompressionMode::Auto | CompressionMode::Smallest => {
+                config.lossless = 1
+            }
+            CompressionMode::Lossy => {
+                config.quality = self.quality;
+                config.alpha_quality = self.alpha_quality as i32;
+                if let Some(filter_strength) = self.filter_strength {
+                    config.filter_strength = filter_strength as i32;
+                }
+                if let Some(sharpness) = self.sharpness {
+                    config.filter_sharpness = sharpness as i32;
+                }
+            }
+            CompressionMode::NearLossless => {
+                config.lossless = 1;
+                config.near_lossless = self.quality.clamp(0.0, 100.0) as i32;
+            }
+        }
+
+        // In lossless encoding libwebp reads `quality` as compression effort, not fidelity
+        if config.lossless == 1 {
+            match self.lossless_level {
+                Some(level) => {
+                    let (method, effort) = LOSSLESS_PRESETS[level as usize];
+                    config.method = method;
+                    config.quality = effort;
+                }
+                None => config.quality = DEFAULT_LOSSLESS_EFFORT,
+            }
+        }
 
-        // Performance: Use faster encoding method with error handling
-        let webp_data = encoder.encode_lossless();
-        self.save_webp_data_fast(&webp_data, output_path)
+        Ok(config)
     }
 
-    fn convert_lossy_fast(&self, img: &DynamicImage, output_path: &Path) -> Result<()> {
-        let encoder = Encoder::from_image(img)
-            .map_err(|e| anyhow::anyhow!("Failed to create encoder: {}", e))?;
+    /// Decode every frame of an animated GIF or APNG, or every image of a HEIF sequence
+    ///
+    /// Returns `None` for other formats and for files with a single frame.
+    fn decode_animation(&self, input_path: &Path, format: &str) -> Result<Option<Vec<Frame>>> {
+        if is_heif_format(format) {
+            return Ok(heif_frames(heif::open_top_level_images(input_path)?));
+        }
 
-        // Performance: Use ultra-fast encoding with optimized quality
-        let quality = if self.ultra_fast && self.quality > 85.0 {
-            // For ultra-fast mode, cap quality to balance speed vs size
-            85.0
-        } else {
-            self.quality
+        let read_error = || format!("Failed to read image: {}", input_path.display());
+
+        let reader = BufReader::new(File::open(input_path).with_context(read_error)?);
+        let mut frames = match format {
+            "gif" => GifDecoder::new(reader)
+                .with_context(read_error)?
+                .into_frames(),
+            "png" => {
+                let decoder = PngDecoder::new(reader).with_context(read_error)?;
+                if !decoder.is_apng().with_context(read_error)? {
+                    return Ok(None);
+                }
+                decoder.apng().with_context(read_error)?.into_frames()
+            }
+            _ => return Ok(None),
+        };
+
+        // Stop after the first frame when there is no second one
+        let Some(first) = frames.next() else {
+            return Ok(None);
         };
+        let Some(second) = frames.next() else {
+            return Ok(None);
+        };
+
+        let mut decoded = vec![
+            first.with_context(read_error)?,
+            second.with_context(read_error)?,
+        ];
+        for frame in frames {
+            decoded.push(frame.with_context(read_error)?);
+        }
+        Ok(Some(decoded))
+    }
 
-        let webp_data = encoder.encode(quality);
-        self.save_webp_data_fast(&webp_data, output_path)
+    /// Scale every frame down to fit the dimension limit, keeping the frame delays
+    fn resize_frames(&self, frames: Vec<Frame>) -> Result<Vec<Frame>> {
+        frames
+            .into_iter()
+            .map(|frame| {
+                let delay = frame.delay();
+                let image = DynamicImage::ImageRgba8(frame.into_buffer());
+                Ok(match self.validate_and_resize_image(&image)? {
+                    Some(resized) => Frame::from_parts(resized.into_rgba8(), 0, 0, delay),
+                    None => Frame::from_parts(image.into_rgba8(), 0, 0, delay),
+                })
+            })
+            .collect()
     }
 
-    fn convert_auto_fast(
+    fn encode_animation(&self, frames: &[Frame]) -> Result<WebPMemory> {
+        let (width, height) = frames[0].buffer().dimensions();
+        let config = self.encoder_config(&self.mode)?;
+        let mut encoder = AnimEncoder::new(width, height, &config);
+
+        // Flattened frames stay RGBA (the animation encoder needs it), just fully opaque
+        let flattened: Option<Vec<RgbaImage>> = self.background.map(|background| {
+            frames
+                .iter()
+                .map(|frame| {
+                    DynamicImage::ImageRgb8(flatten_alpha(frame.buffer(), background)).into_rgba8()
+                })
+                .collect()
+        });
+
+        let mut timestamp_ms = 0i32;
+        for (index, frame) in frames.iter().enumerate() {
+            let buffer = flattened
+                .as_ref()
+                .map_or(frame.buffer(), |flattened| &flattened[index]);
+            encoder.add_frame(AnimFrame::from_rgba(
+                buffer.as_raw(),
+                width,
+                height,
+                timestamp_ms,
+            ));
+
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let delay_ms = numer.checked_div(denom).unwrap_or(0);
+            let delay_ms = if delay_ms <= MIN_FRAME_DELAY_MS {
+                DEFAULT_FRAME_DELAY_MS
+            } else {
+                delay_ms
+            };
+            timestamp_ms = timestamp_ms.saturating_add(delay_ms as i32);
+        }
+
+        encoder
+            .try_encode()
+            .map_err(|e| ErrorKind::Encode.error(format!("Animated WebP encoding failed: {e:?}")))
+    }
+
+    fn encode_lossy_fast(
         &self,
         img: &DynamicImage,
-        output_path: &Path,
-        input_path: &Path,
-    ) -> Result<()> {
-        // Smart strategy selection: automatically choose compression mode based on image characteristics
-        let should_use_lossless = self.should_use_lossless_fast(img, input_path);
+    ) -> Result<(WebPMemory, Option<QualitySearchResult>)> {
+        let img = without_opaque_alpha(img);
 
-        if should_use_lossless {
-            self.convert_lossless_fast(img, output_path)
-        } else {
-            self.convert_lossy_fast(img, output_path)
+        if let Some(target_ssim) = self.target_ssim {
+            let (webp_data, search) = self.encode_lossy_for_target_ssim(&img, target_ssim)?;
+            return Ok((webp_data, Some(search)));
         }
+
+        let config = self.encoder_config(&CompressionMode::Lossy)?;
+        let webp_data = self.encode_with_config(&img, &config)?;
+        Ok((webp_data, None))
     }
 
-    fn should_use_lossless_fast(&self, img: &DynamicImage, input_path: &Path) -> bool {
-        // Enhanced decision algorithm with content analysis
-        let extension = input_path
-            .extension()
-            .and_then(|ext| ext.to_str())
-            .map(|ext| ext.to_lowercase())
-            .unwrap_or_default();
+    /// Binary-search the lowest lossy quality whose output reaches `target_ssim`
+    ///
+    /// Every candidate is decoded again and compared with the source. If no quality
+    /// reaches the target within the attempt budget, the candidate closest to it is kept.
+    fn encode_lossy_for_target_ssim(
+        &self,
+        img: &DynamicImage,
+        target_ssim: f64,
+    ) -> Result<(WebPMemory, QualitySearchResult)> {
+        let mut low = 0u8;
+        let mut high = 100u8;
+        let mut attempts = 0;
+        // Lowest quality reaching the target, and the closest miss as a fallback
+        let mut passing: Option<(WebPMemory, QualitySearchResult)> = None;
+        let mut closest: Option<(WebPMemory, QualitySearchResult)> = None;
+        let mut config = self.encoder_config(&CompressionMode::Lossy)?;
+
+        while low <= high && attempts < MAX_SSIM_ATTEMPTS {
+            let quality = low + (high - low) / 2;
+            attempts += 1;
 
+            config.quality = quality as f32;
+            let webp_data = self.encode_with_config(img, &config)?;
+            let decoded = image::load_from_memory_with_format(&webp_data, image::ImageFormat::WebP)
+                .context("Failed to decode WebP candidate for SSIM measurement")?;
+            let ssim = metrics::ssim(img, &decoded);
+            let result = QualitySearchResult {
+                quality,
+                ssim,
+                attempts,
+            };
+
+            if ssim >= target_ssim {
+                passing = Some((webp_data, result));
+                if quality == 0 {
+                    break;
+                }
+                high = quality - 1;
+            } else {
+                let is_closer = closest.as_ref().is_none_or(|(_, best)| {
+                    (target_ssim - ssim).abs() < (target_ssim - best.ssim).abs()
+                });
+                if is_closer {
+                    closest = Some((webp_data, result));
+                }
+                low = quality + 1;
+            }
+        }
+
+        let (webp_data, mut result) = passing
+            .or(closest)
+            .context("SSIM search did not produce any candidate")?;
+        result.attempts = attempts;
+
+        log::debug!(
+            "Target SSIM {:.4}: picked quality {} (SSIM {:.4}) after {} attempts",
+            target_ssim,
+            result.quality,
+            result.ssim,
+            attempts
+        );
+
+        Ok((webp_data, result))
+    }
+
+    /// Encode `img` with advanced encoder settings
+    fn encode_with_config(&self, img: &DynamicImage, config: &WebPConfig) -> Result<WebPMemory> {
+        self.encode_with_fallback(img, |encoder| {
+            encoder
+                .encode_advanced(config)
+                .map_err(|e| ErrorKind::Encode.error(format!("WebP encoding failed: {e:?}")))
+        })
+    }
+
+    /// Run `encode` on a WebP encoder for `img`, normalizing the pixel layout if needed
+    ///
+    /// The encoder only accepts 8-bit RGB/RGBA buffers, so 16-bit, grayscale and other
+    /// layouts are rejected up front. In that case the image is converted to RGBA8 and
+    /// encoding is retried once before giving up.
+    fn encode_with_fallback<F>(&self, img: &DynamicImage, encode: F) -> Result<WebPMemory>
+    where
+        F: Fn(&Encoder) -> Result<WebPMemory>,
+    {
+        match Encoder::from_image(img) {
+            Ok(encoder) => encode(&encoder),
+            Err(first_error) => {
+                log::debug!(
+                    "WebP encoder rejected {:?} pixel layout ({}), retrying as RGBA8",
+                    img.color(),
+                    first_error
+                );
+
+                let normalized = DynamicImage::ImageRgba8(img.to_rgba8());
+                let encoder = Encoder::from_image(&normalized).map_err(|e| {
+                    ErrorKind::UnsupportedFormat.error(format!(
+                        "WebP encoder does not support {:?} images, even after conversion to RGBA8: {}",
+                        img.color(),
+                        e
+                    ))
+                })?;
+                encode(&encoder)
+            }
+        }
+    }
+
+    fn should_use_lossless_fast(&self, img: &DynamicImage, extension: &str) -> bool {
+        // Enhanced decision algorithm with content analysis
         // Quick decision based on file extension
-        match extension.as_str() {
+        match extension {
             "png" | "gif" => true, // Likely have transparency or few colors
             "jpg" | "jpeg" => {
                 // For JPEG, analyze image characteristics
@@ -170,84 +1133,383 @@ impl ImageConverter {
     }
 
     /// Analyze color complexity to determine optimal compression mode
+    ///
+    /// Samples about `auto_sample_size` pixels on an evenly spaced grid over the whole
+    /// image. Transparency or fewer than `auto_color_threshold` distinct colors (graphics,
+    /// logos, screenshots) select lossless; photographic content selects lossy.
     fn analyze_color_complexity(&self, img: &DynamicImage) -> bool {
-        // Sample pixels to estimate color complexity
         let (width, height) = img.dimensions();
-        let sample_size = 100.min(width * height / 4); // Sample up to 100 pixels
-        let step_x = (width / 10).max(1);
-        let step_y = (height / 10).max(1);
+        if width == 0 || height == 0 {
+            return true;
+        }
+        let columns = (f64::from(self.auto_sample_size).sqrt().ceil() as u32).clamp(1, width);
+        let rows = self.auto_sample_size.div_ceil(columns).clamp(1, height);
 
         let mut unique_colors = std::collections::HashSet::new();
-        let mut has_transparency = false;
-
-        // Sample pixels across the image
-        for y in (0..height).step_by(step_y as usize) {
-            for x in (0..width).step_by(step_x as usize) {
-                if unique_colors.len() > sample_size as usize {
-                    break;
-                }
-
-                let pixel = img.get_pixel(x, y);
-                let rgba = pixel.0;
-
-                // Check for transparency
-                if rgba.len() > 3 && rgba[3] < 255 {
-                    has_transparency = true;
+        for row in 0..rows {
+            // Sample the center of each grid cell so the edges aren't over-represented
+            let y = ((2 * row as u64 + 1) * height as u64 / (2 * rows as u64)) as u32;
+            for column in 0..columns {
+                let x = ((2 * column as u64 + 1) * width as u64 / (2 * columns as u64)) as u32;
+                let [red, green, blue, alpha] = img.get_pixel(x, y).0;
+                if alpha < 255 {
+                    return true;
                 }
-
-                // Store RGB values (ignore alpha for color counting)
-                unique_colors.insert((rgba[0], rgba[1], rgba[2]));
+                // Alpha is ignored for color counting
+                unique_colors.insert((red, green, blue));
             }
         }
 
-        // Decision logic:
-        // - Use lossless if transparency detected
-        // - Use lossless if low color count (graphics/logos)
-        // - Use lossy for photographic content (high color count)
-        has_transparency || unique_colors.len() < 64
+        unique_colors.len() < self.auto_color_threshold
     }
 
-    fn save_webp_data_fast(&self, webp_data: &WebPMemory, output_path: &Path) -> Result<()> {
-        // Performance: Use optimized file writing with correct dereferencing
-        std::fs::write(output_path, &**webp_data)
+    fn save_webp_data_fast(&self, webp_data: &[u8], output_path: &Path) -> Result<()> {
+        if let Some(throttle) = &self.write_throttle {
+            throttle.acquire(webp_data.len() as u64);
+        }
+
+        // Written under a temporary name, so a failed or cancelled run leaves no partial WebP
+        crate::utils::write_atomically(output_path, webp_data)
             .with_context(|| format!("Failed to save WebP file: {}", output_path.display()))?;
         Ok(())
     }
 
-    /// Validate and potentially resize image to fit WebP constraints
+    /// Longest side an output may have: the max dimension, capped at the WebP limit
+    fn dimension_limit(&self) -> u32 {
+        self.max_dimension
+            .map_or(MAX_WEBP_DIMENSION, |max| max.min(MAX_WEBP_DIMENSION))
+    }
+
+    /// Size of a `width` x `height` image scaled down to fit the dimension limit
+    fn fitted_dimensions(&self, width: u32, height: u32) -> (u32, u32) {
+        let scale_factor = (self.dimension_limit() as f64 / width.max(height) as f64).min(1.0);
+        (
+            ((width as f64 * scale_factor) as u32).max(1),
+            ((height as f64 * scale_factor) as u32).max(1),
+        )
+    }
+
+    /// Validate and potentially resize image to fit WebP constraints and the max dimension
     /// Returns None if no resizing is needed, Some(resized_image) if resizing was performed
+    /// Images are only ever scaled down, keeping their aspect ratio
     fn validate_and_resize_image(&self, img: &DynamicImage) -> Result<Option<DynamicImage>> {
         let (width, height) = img.dimensions();
 
-        // WebP maximum dimensions are 16383x16383
-        const MAX_WEBP_DIMENSION: u32 = 16383;
-
         if width == 0 || height == 0 {
-            return Err(anyhow::anyhow!(
-                "Invalid image dimensions: {}x{} (zero dimensions)",
-                width,
-                height
-            ));
+            return Err(ErrorKind::Decode.error(format!(
+                "Invalid image dimensions: {width}x{height} (zero dimensions)"
+            )));
         }
 
-        if width <= MAX_WEBP_DIMENSION && height <= MAX_WEBP_DIMENSION {
+        if width <= self.dimension_limit() && height <= self.dimension_limit() {
             // Image is within limits, no cloning needed
             return Ok(None);
         }
 
-        // Image is too large, resize it to fit within WebP limits
-        let scale_factor = (MAX_WEBP_DIMENSION as f64 / width.max(height) as f64).min(1.0);
-        let new_width = (width as f64 * scale_factor) as u32;
-        let new_height = (height as f64 * scale_factor) as u32;
+        // Image is too large, resize it to fit within the limit
+        let (new_width, new_height) = self.fitted_dimensions(width, height);
 
-        log::warn!(
-            "Resizing image from {width}x{height} to {new_width}x{new_height} to fit WebP limits"
-        );
+        if self.dimension_limit() < MAX_WEBP_DIMENSION {
+            log::debug!("Downscaling image from {width}x{height} to {new_width}x{new_height}");
+        } else {
+            log::warn!(
+                "Resizing image from {width}x{height} to {new_width}x{new_height} to fit WebP limits"
+            );
+        }
 
         Ok(Some(img.resize(
             new_width,
             new_height,
-            image::imageops::FilterType::Lanczos3,
+            self.resize_filter.filter_type(),
         )))
     }
 }
+
+/// Extension of `output_path`, kept for the extra outputs derived from it
+fn output_extension(output_path: &Path) -> String {
+    output_path
+        .extension()
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_else(|| OUTPUT_FORMAT.to_string())
+}
+
+/// Output path for one page of a multi-page input, e.g. `scan_p2.webp`
+fn page_output_path(output_path: &Path, page: usize) -> PathBuf {
+    let stem = output_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    output_path.with_file_name(format!("{stem}_p{page}.{}", output_extension(output_path)))
+}
+
+/// Set the modification and access times of `target` to those of `source`
+///
+/// Failures only cost the timestamps, so they are logged instead of failing the file.
+fn copy_file_times(source: &Path, target: &Path) {
+    let result = std::fs::metadata(source).and_then(|metadata| {
+        let mut times = std::fs::FileTimes::new().set_modified(metadata.modified()?);
+        // Not every filesystem records access times
+        if let Ok(accessed) = metadata.accessed() {
+            times = times.set_accessed(accessed);
+        }
+        std::fs::File::options()
+            .write(true)
+            .open(target)?
+            .set_times(times)
+    });
+    if let Err(e) = result {
+        log::warn!(
+            "Failed to copy timestamps of {} to {}: {}",
+            source.display(),
+            target.display(),
+            e
+        );
+    }
+}
+
+/// Whether `format` (a lowercase extension) is HEIC/HEIF
+fn is_heif_format(format: &str) -> bool {
+    matches!(format, "heic" | "heif")
+}
+
+/// Whether `format` (a lowercase extension) is TIFF
+fn is_tiff_format(format: &str) -> bool {
+    matches!(format, "tiff" | "tif")
+}
+
+/// Output path for one resolution of a multi-size input, e.g. `icon_32x32.webp`
+fn sized_output_path(output_path: &Path, width: u32, height: u32) -> PathBuf {
+    let stem = output_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    output_path.with_file_name(format!(
+        "{stem}_{width}x{height}.{}",
+        output_extension(output_path)
+    ))
+}
+
+/// Turn the top-level images of a HEIF file (burst or live photo) into animation frames
+///
+/// Returns `None` for a single image, or when the images differ in size and cannot share
+/// one canvas; the primary image is converted on its own in both cases.
+fn heif_frames(images: Vec<DynamicImage>) -> Option<Vec<Frame>> {
+    if images.len() < 2 {
+        return None;
+    }
+    let dimensions = images[0].dimensions();
+    if images.iter().any(|image| image.dimensions() != dimensions) {
+        return None;
+    }
+
+    let delay = Delay::from_numer_denom_ms(DEFAULT_FRAME_DELAY_MS, 1);
+    Some(
+        images
+            .into_iter()
+            .map(|image| Frame::from_parts(image.into_rgba8(), 0, 0, delay))
+            .collect(),
+    )
+}
+
+/// Composite an RGBA image onto a solid `background`, dropping the alpha channel
+fn flatten_alpha(img: &RgbaImage, background: Rgba<u8>) -> image::RgbImage {
+    image::RgbImage::from_fn(img.width(), img.height(), |x, y| {
+        let Rgba([red, green, blue, alpha]) = *img.get_pixel(x, y);
+        let blend = |color: u8, base: u8| {
+            let alpha = u32::from(alpha);
+            ((u32::from(color) * alpha + u32::from(base) * (255 - alpha) + 127) / 255) as u8
+        };
+        image::Rgb([
+            blend(red, background[0]),
+            blend(green, background[1]),
+            blend(blue, background[2]),
+        ])
+    })
+}
+
+/// Drop the alpha channel of images that are fully opaque, so no alpha plane is encoded
+fn without_opaque_alpha(img: &DynamicImage) -> Cow<'_, DynamicImage> {
+    let opaque = img.color().has_alpha() && img.pixels().all(|(_, _, pixel)| pixel.0[3] == u8::MAX);
+    if opaque {
+        Cow::Owned(DynamicImage::ImageRgb8(img.to_rgb8()))
+    } else {
+        Cow::Borrowed(img)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lossless_effort_defaults_to_level_six() {
+        let converter = ImageConverter::new_with_dry_run(70, &CompressionMode::Lossless, false);
+        let config = converter
+            .encoder_config(&CompressionMode::Lossless)
+            .unwrap();
+        assert_eq!(config.quality, DEFAULT_LOSSLESS_EFFORT);
+        assert_eq!(config.method, DEFAULT_EFFORT as i32);
+
+        let level_six = converter
+            .with_lossless_level(Some(6))
+            .encoder_config(&CompressionMode::Lossless)
+            .unwrap();
+        assert_eq!(
+            (level_six.method, level_six.quality),
+            (config.method, config.quality)
+        );
+    }
+
+    #[test]
+    fn unsupported_pixel_layouts_are_encoded_as_rgba8() {
+        let converter = ImageConverter::new_with_dry_run(80, &CompressionMode::Lossless, false);
+        let images = [
+            DynamicImage::ImageRgb16(image::ImageBuffer::from_pixel(
+                12,
+                8,
+                image::Rgb([65535, 0, 32768]),
+            )),
+            DynamicImage::ImageLumaA8(image::ImageBuffer::from_pixel(
+                12,
+                8,
+                image::LumaA([128, 200]),
+            )),
+        ];
+
+        for img in images {
+            assert!(Encoder::from_image(&img).is_err());
+            let webp_data = converter
+                .encode_with_fallback(&img, |encoder| Ok(encoder.encode_lossless()))
+                .unwrap();
+            let decoded = image::load_from_memory(&webp_data).unwrap();
+            assert_eq!(decoded.dimensions(), (12, 8));
+            assert_eq!(
+                decoded.to_rgba8().get_pixel(0, 0),
+                img.to_rgba8().get_pixel(0, 0)
+            );
+        }
+    }
+
+    #[test]
+    fn target_ssim_search_lands_near_the_target() {
+        let converter = ImageConverter::new_with_dry_run(80, &CompressionMode::Lossy, false);
+        let fixtures = [
+            // Smooth photographic gradient
+            RgbaImage::from_fn(96, 64, |x, y| {
+                Rgba([(x * 2) as u8, (y * 3) as u8, ((x + y) * 2) as u8, 255])
+            }),
+            // High-frequency texture
+            RgbaImage::from_fn(96, 64, |x, y| {
+                let noise = (x.wrapping_mul(73_856_093) ^ y.wrapping_mul(19_349_663)) % 251;
+                Rgba([noise as u8, (noise * 3 % 256) as u8, (x * 2) as u8, 255])
+            }),
+            // Flat graphic with hard edges
+            RgbaImage::from_fn(96, 64, |x, _| {
+                if x < 48 {
+                    Rgba([220, 30, 30, 255])
+                } else {
+                    Rgba([20, 20, 200, 255])
+                }
+            }),
+        ];
+
+        for (index, fixture) in fixtures.into_iter().enumerate() {
+            let img = DynamicImage::ImageRgba8(fixture);
+            for target in [0.90, 0.97] {
+                let (webp_data, result) = converter
+                    .encode_lossy_for_target_ssim(&img, target)
+                    .unwrap();
+                let decoded = image::load_from_memory(&webp_data).unwrap();
+                let ssim = metrics::ssim(&img, &decoded);
+                assert!(result.attempts <= MAX_SSIM_ATTEMPTS);
+                assert!((ssim - result.ssim).abs() < 1e-9);
+                assert!(ssim >= target, "fixture {index}: {ssim} < {target}");
+                assert!(
+                    result.quality == 0 || ssim < target + 0.05,
+                    "fixture {index}: {ssim} overshoots {target} at quality {}",
+                    result.quality
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn color_complexity_tells_photos_from_graphics() {
+        let converter = ImageConverter::new_with_dry_run(80, &CompressionMode::Auto, false);
+        let photo = |x: u32, y: u32| {
+            let grain = (x.wrapping_mul(31) ^ y.wrapping_mul(17)) % 23;
+            Rgba([
+                (x / 2 + grain) as u8,
+                (y / 2 + grain * 2) as u8,
+                ((x + y) / 4) as u8,
+                255,
+            ])
+        };
+        let graphic = |x: u32, y: u32| match (x / 100, y / 100) {
+            (0, _) => Rgba([255, 255, 255, 255]),
+            (1, 0) => Rgba([30, 120, 220, 255]),
+            (1, _) => Rgba([230, 60, 40, 255]),
+            _ => Rgba([20, 20, 20, 255]),
+        };
+        let analyze = |img: RgbaImage| converter.analyze_color_complexity(&img.into());
+
+        assert!(!analyze(RgbaImage::from_fn(400, 300, photo)));
+        assert!(analyze(RgbaImage::from_fn(400, 300, graphic)));
+
+        // Transparency keeps photographic content lossless
+        let mut translucent = RgbaImage::from_fn(400, 300, photo);
+        translucent.pixels_mut().for_each(|pixel| pixel.0[3] = 128);
+        assert!(analyze(translucent));
+
+        // The whole image is sampled, not just its top rows
+        let photo_below_banner =
+            RgbaImage::from_fn(
+                400,
+                300,
+                |x, y| if y < 60 { graphic(x, y) } else { photo(x, y) },
+            );
+        assert!(!analyze(photo_below_banner));
+    }
+
+    #[test]
+    fn max_dimension_applies_to_animations_and_icon_sizes() {
+        use image::ImageEncoder;
+        use image::codecs::{gif::GifEncoder, ico::IcoEncoder};
+
+        let dir = std::env::temp_dir().join(format!("webpify-resize-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let frame = |shade: u8| {
+            RgbaImage::from_fn(64, 32, |x, y| {
+                Rgba([(x * 4) as u8, (y * 8) as u8, shade, 255])
+            })
+        };
+
+        let gif_path = dir.join("anim.gif");
+        let mut encoder = GifEncoder::new(File::create(&gif_path).unwrap());
+        for shade in [0, 255] {
+            encoder.encode_frame(Frame::new(frame(shade))).unwrap();
+        }
+        drop(encoder);
+
+        let ico_path = dir.join("icon.ico");
+        IcoEncoder::new(File::create(&ico_path).unwrap())
+            .write_image(frame(128).as_raw(), 64, 32, image::ExtendedColorType::Rgba8)
+            .unwrap();
+
+        let converter = ImageConverter::new_with_dry_run(80, &CompressionMode::Lossless, false)
+            .with_animation(true)
+            .with_ico_size_selection(IcoSizeSelection::All)
+            .with_max_dimension(Some(16));
+        for input in [gif_path, ico_path] {
+            let (outputs, _) = converter
+                .encode_outputs(&input, &dir.join("out.webp"))
+                .unwrap();
+            assert_eq!(outputs.len(), 1);
+            assert_eq!(outputs[0].expected_dimensions, (16, 8));
+            let decoded = image::load_from_memory(&outputs[0].data).unwrap();
+            assert_eq!(decoded.dimensions(), (16, 8), "{}", input.display());
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,20 +1,111 @@
 use anyhow::{Context, Result};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use rayon::prelude::*;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::Ordering;
-use std::time::Instant;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 use walkdir::WalkDir;
 
 use crate::{
-    ConversionReport, ReplaceInputMode, config::ConversionOptions, converter::ImageConverter,
-    progress::ProgressReporter, stats::ConversionStats, utils::is_valid_image_file,
+    CollisionPolicy, ConversionReport, FormatOverrideUsage, JsonLinesWriter, RecycleFallback,
+    ReplaceInputMode, ReportFormat, SortOrder,
+    checkpoint::Checkpoint,
+    config::{ConversionOptions, OUTPUT_FORMAT, render_filename_template},
+    converter::{ConversionOutcome, ImageConverter},
+    heif,
+    manifest::{Manifest, hash_file},
+    output_path::{DefaultOutputPathResolver, OutputPathResolver},
+    progress::ProgressEvent,
+    progress::ProgressReporter,
+    run_log::RunLog,
+    stats::ConversionStats,
+    stats::{ErrorKind, FileResult, FileStatus, SkipReason},
+    utils::{
+        ImageValidationError, MemoryBudget, MemoryReservation, detect_image_format,
+        estimate_decoded_bytes, input_format, is_same_format, is_transient_error,
+        lower_current_priority, validate_image_file,
+    },
 };
 
+/// A set of input files that would all be written to the same output path
+#[derive(Debug, Clone)]
+pub struct OutputCollision {
+    pub output_path: PathBuf,
+    pub sources: Vec<PathBuf>,
+}
+
+/// Delay before the first retry of a transient failure; doubles with each attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(100);
+
+/// Number of scanned files between two `ScanProgress` events
+const SCAN_PROGRESS_INTERVAL: usize = 100;
+
+/// Paths buffered between the directory walker and the workers in streaming mode
+const STREAMING_QUEUE_CAPACITY: usize = 1024;
+
+/// Capacity of the event channel; workers wait when the consumer falls this far behind
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
 /// Core conversion engine that orchestrates the image conversion process
 pub struct WebpifyCore {
     options: ConversionOptions,
     stats: ConversionStats,
+    event_sink: Option<crossbeam_channel::Sender<ProgressEvent>>,
+    /// Receiver side handed out by `file_results`, fed as each file completes
+    file_result_sink: Option<crossbeam_channel::Sender<FileResult>>,
+    /// Per-file report lines written during the run for JSON Lines reports
+    report_stream: Option<JsonLinesWriter>,
+    /// Disambiguated output paths for inputs that collided with an earlier input
+    renamed_outputs: HashMap<PathBuf, PathBuf>,
+    /// Content hashes from earlier runs, when the manifest is enabled
+    manifest: Option<Manifest>,
+    /// Timestamped per-file results, when a log file is set
+    run_log: Option<RunLog>,
+    /// Files completed in this batch, so an interrupted run can be resumed
+    checkpoint: Option<Checkpoint>,
+    /// Limit on the memory of images decoded at once, when a memory cap is set
+    memory_budget: Option<Arc<MemoryBudget>>,
+    /// Outputs written in this run, checked again at the end when verification is enabled
+    pending_verification: Mutex<Vec<PendingVerification>>,
+    /// Set when the file cap left matching files out of this run
+    file_limit_reached: AtomicBool,
+    /// Directories and files the scan could not read, for the summary warning
+    inaccessible_paths: AtomicUsize,
+    /// Input roots with their output subdirectories, resolved on first use
+    input_roots: OnceLock<Vec<(PathBuf, PathBuf)>>,
+}
+
+/// Context of an error that persisted through every retry of a file
+#[derive(Debug)]
+struct RetriesExhausted {
+    retries: u32,
+}
+
+impl std::fmt::Display for RetriesExhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Giving up after {} retries", self.retries)
+    }
+}
+
+/// Drops the event sender of a core when it goes out of scope
+struct ClearEventSink<'a>(&'a mut WebpifyCore);
+
+impl Drop for ClearEventSink<'_> {
+    fn drop(&mut self) {
+        self.0.event_sink = None;
+    }
+}
+
+/// A converted source whose outputs are checked again at the end of the run
+struct PendingVerification {
+    source: PathBuf,
+    outcome: ConversionOutcome,
 }
 
 impl WebpifyCore {
@@ -23,6 +114,162 @@ impl WebpifyCore {
         Self {
             options,
             stats: ConversionStats::new(),
+            event_sink: None,
+            file_result_sink: None,
+            report_stream: None,
+            renamed_outputs: HashMap::new(),
+            manifest: None,
+            run_log: None,
+            checkpoint: None,
+            memory_budget: None,
+            pending_verification: Mutex::new(Vec::new()),
+            file_limit_reached: AtomicBool::new(false),
+            inaccessible_paths: AtomicUsize::new(0),
+            input_roots: OnceLock::new(),
+        }
+    }
+
+    /// Run the conversion on a background thread and stream its progress as typed events
+    ///
+    /// This is the preferred integration surface for front ends: one iterator carries
+    /// scanning progress, per-file results, errors and the final report, so a progress
+    /// bar, a GUI or a JSON-lines writer can all be driven from the same loop. The stream
+    /// ends with [`ProgressEvent::Finished`], or with an [`ProgressEvent::Error`] without a
+    /// path if the run fails. Statistics are shared with this core, so
+    /// [`get_stats`](Self::get_stats) reflects the run while it is being consumed.
+    ///
+    /// Dropping the iterator before the end cancels the run, through the control handle
+    /// of the options if one is set.
+    pub fn events(&mut self) -> impl Iterator<Item = ProgressEvent> + use<> {
+        let (sender, receiver) = crossbeam_channel::bounded(EVENT_CHANNEL_CAPACITY);
+        let control = self.options.control.clone().unwrap_or_default();
+        let mut core = WebpifyCore {
+            options: self.options.clone().with_control(control),
+            stats: self.stats.clone(),
+            event_sink: Some(sender),
+            file_result_sink: self.file_result_sink.take(),
+            report_stream: None,
+            renamed_outputs: HashMap::new(),
+            manifest: None,
+            run_log: None,
+            checkpoint: None,
+            memory_budget: None,
+            pending_verification: Mutex::new(Vec::new()),
+            file_limit_reached: AtomicBool::new(false),
+            inaccessible_paths: AtomicUsize::new(0),
+            input_roots: OnceLock::new(),
+        };
+
+        std::thread::spawn(move || {
+            let event = match core.run() {
+                Ok(report) => ProgressEvent::Finished {
+                    report: Box::new(report),
+                },
+                Err(e) => ProgressEvent::Error {
+                    path: None,
+                    message: format!("{e:#}"),
+                },
+            };
+            core.emit(event);
+        });
+
+        receiver.into_iter()
+    }
+
+    /// Run the conversion and pass every progress event to `callback`
+    ///
+    /// A lighter alternative to implementing [`ProgressReporter`]: the events are the same
+    /// as those of [`events`](Self::events), ending with [`ProgressEvent::Finished`] or a
+    /// run-level [`ProgressEvent::Error`]. The conversion runs on a helper thread while
+    /// `callback` is called on the calling thread, so it does not need to be `Send`.
+    pub fn run_with_callback(
+        &mut self,
+        mut callback: impl FnMut(ProgressEvent),
+    ) -> Result<ConversionReport> {
+        let (sender, receiver) = crossbeam_channel::bounded(EVENT_CHANNEL_CAPACITY);
+        self.event_sink = Some(sender);
+
+        std::thread::scope(|scope| {
+            let run = scope.spawn(|| {
+                // Dropping the sender ends the receiving loop below, even if the run panics
+                let core = ClearEventSink(self);
+                let result = core.0.run();
+                core.0.emit(match &result {
+                    Ok(report) => ProgressEvent::Finished {
+                        report: Box::new(report.clone()),
+                    },
+                    Err(e) => ProgressEvent::Error {
+                        path: None,
+                        message: format!("{e:#}"),
+                    },
+                });
+                result
+            });
+
+            for event in receiver {
+                callback(event);
+            }
+
+            run.join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        })
+    }
+
+    /// Run the conversion on tokio's blocking pool so the async runtime is not blocked
+    ///
+    /// The conversion itself is the same as [`run`](Self::run). Must be awaited within a
+    /// tokio runtime.
+    #[cfg(feature = "async")]
+    pub async fn run_async(mut self) -> Result<ConversionReport> {
+        tokio::task::spawn_blocking(move || self.run())
+            .await
+            .context("Conversion task failed")?
+    }
+
+    /// Like [`run_async`](Self::run_async), streaming progress events through the receiver
+    ///
+    /// The events are those of [`run_with_callback`](Self::run_with_callback). The
+    /// conversion starts right away, so this must be called within a tokio runtime; events
+    /// are dropped if the receiver is closed.
+    #[cfg(feature = "async")]
+    pub fn run_async_with_events(
+        mut self,
+    ) -> (
+        tokio::sync::mpsc::Receiver<ProgressEvent>,
+        impl Future<Output = Result<ConversionReport>>,
+    ) {
+        let (sender, receiver) = tokio::sync::mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        let task = tokio::task::spawn_blocking(move || {
+            self.run_with_callback(|event| {
+                let _ = sender.blocking_send(event);
+            })
+        });
+
+        let report = async move { task.await.context("Conversion task failed")? };
+        (receiver, report)
+    }
+
+    /// Stream the result of every file of the next run as it completes
+    ///
+    /// Meant for embedders that store results as they arrive (e.g. in a database) instead
+    /// of waiting for the final report. Each file handled by a worker, whether converted,
+    /// skipped or failed, yields one [`FileResult`] in completion order. The channel is
+    /// unbounded so a slow consumer never stalls the workers, and it closes when the run
+    /// ends. Without a call to this method no results are sent.
+    pub fn file_results(&mut self) -> crossbeam_channel::Receiver<FileResult> {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        self.file_result_sink = Some(sender);
+        receiver
+    }
+
+    /// Send an event to the `events` consumer, if there is one
+    fn emit(&self, event: ProgressEvent) {
+        // Nobody is listening any more, so the rest of the run would be wasted
+        if let Some(sink) = &self.event_sink
+            && sink.send(event).is_err()
+            && let Some(control) = &self.options.control
+        {
+            control.cancel();
         }
     }
 
@@ -32,69 +279,184 @@ impl WebpifyCore {
     }
 
     /// Run the conversion process with progress reporting
+    ///
+    /// With a configured thread count the run gets its own thread pool, so consecutive
+    /// runs in one process each use their own setting. Low-priority runs also get their
+    /// own pool, so on Linux and Windows, where priorities are per thread, lowering the
+    /// workers' priority does not outlive the run. Other Unix systems (macOS included)
+    /// lower the whole process until it exits. Otherwise rayon's global pool is used.
     pub fn run_with_progress(
         &mut self,
         progress_reporter: Option<Box<dyn ProgressReporter>>,
+    ) -> Result<ConversionReport> {
+        let result = if self.options.threads.is_some() || self.options.low_priority {
+            // Zero threads lets rayon pick its default count
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(self.options.threads.unwrap_or(0))
+                .build()
+                .context("Failed to create thread pool")
+                .and_then(|pool| pool.install(|| self.run_in_current_pool(progress_reporter)))
+        } else {
+            self.run_in_current_pool(progress_reporter)
+        };
+        // Dropping the sender closes the `file_results` receiver
+        self.file_result_sink = None;
+        result
+    }
+
+    /// Run the conversion on the rayon pool of the calling context
+    fn run_in_current_pool(
+        &mut self,
+        progress_reporter: Option<Box<dyn ProgressReporter>>,
     ) -> Result<ConversionReport> {
         let start_time = Instant::now();
         let start_time_utc = Utc::now();
 
-        // Setup thread pool (only if not already initialized)
-        if let Some(threads) = self.options.threads {
-            // Check if global pool is already initialized by trying to build a new one
-            if rayon::ThreadPoolBuilder::new()
-                .num_threads(threads)
-                .build_global()
-                .is_err()
-            {
-                // Thread pool already exists, just log a warning
-                log::debug!("Thread pool already initialized, using existing configuration");
-            }
+        if self.options.low_priority {
+            self.lower_priority();
         }
 
         // Create output directory
         let output_dir = self.options.get_output_dir();
         std::fs::create_dir_all(&output_dir).context("Failed to create output directory")?;
 
+        self.manifest = self.options.manifest.then(|| Manifest::load(&output_dir));
+
+        // A dry run completes nothing, so it neither resumes nor leaves a checkpoint
+        self.checkpoint = (!self.options.dry_run)
+            .then(|| Checkpoint::open(&self.options, &output_dir, self.options.resume));
+
+        // JSON Lines reports are written while converting so partial results survive
+        self.report_stream = None;
+        if self.options.generate_report && self.options.report_format == ReportFormat::JsonLines {
+            let report_path = crate::report_path(
+                &output_dir,
+                &ReportFormat::JsonLines,
+                self.options.report_path.as_deref(),
+                self.options.report_timestamp.then_some(start_time_utc),
+            );
+            self.report_stream = Some(JsonLinesWriter::create(&report_path)?);
+        }
+
+        self.memory_budget = self.options.max_memory_mb.map(|max_memory_mb| {
+            Arc::new(MemoryBudget::new(max_memory_mb.saturating_mul(1024 * 1024)))
+        });
+
+        self.pending_verification = Mutex::new(Vec::new());
+        self.file_limit_reached.store(false, Ordering::Relaxed);
+        self.inaccessible_paths.store(0, Ordering::Relaxed);
+
+        self.run_log = match &self.options.log_file {
+            Some(path) => Some(RunLog::open(path)?),
+            None => None,
+        };
+        self.write_log(
+            "INFO",
+            &format!(
+                "Starting conversion of {} into {}",
+                self.options.input_dir.display(),
+                output_dir.display()
+            ),
+        );
+
         // Start timing
         self.stats.start_timer();
 
-        // Scan input files
-        let files = if self.options.prescan {
-            self.scan_input_files()?
+        let progress_reporter = progress_reporter.as_deref();
+
+        // Prescan collects every input first for an accurate total and ETA; otherwise
+        // conversion starts while the directory tree is still being walked. A filename
+        // template or a custom output path resolver always scans first so colliding names
+        // are rejected before any write, and so does in-place output, whose new files must
+        // not show up in the walk.
+        let total_files = if self.options.prescan
+            || self.options.filename_template.is_some()
+            || self.options.output_path_resolver.is_some()
+            || self.options.in_place
+        {
+            let (files, sizes) = self.scan_input_files()?;
+
+            if files.is_empty() {
+                return Ok(self.create_empty_report(start_time_utc, start_time, output_dir));
+            }
+
+            // Inputs that would overwrite each other are renamed or rejected up front
+            self.renamed_outputs = self.resolve_output_collisions(&files, &output_dir)?;
+
+            if !self.options.dry_run {
+                self.check_free_space(&files, &sizes, &output_dir)?;
+            }
+
+            // Report progress
+            if let Some(reporter) = progress_reporter {
+                reporter.set_total_files(files.len());
+                reporter.start_conversion();
+            }
+            self.emit(ProgressEvent::Started {
+                total: Some(files.len()),
+            });
+
+            // Execute conversion
+            self.convert_images(&files, &sizes, &output_dir, progress_reporter);
+            files.len()
         } else {
-            self.scan_files_streaming()?
+            if let Some(reporter) = progress_reporter {
+                reporter.start_conversion();
+            }
+            self.emit(ProgressEvent::Started { total: None });
+            let found = self.convert_streaming(&output_dir, progress_reporter)?;
+            if found == 0 {
+                return Ok(self.create_empty_report(start_time_utc, start_time, output_dir));
+            }
+            found
         };
 
-        if files.is_empty() {
-            return Ok(self.create_empty_report(start_time_utc, start_time, output_dir));
+        if self.options.verify {
+            self.verify_outputs();
         }
 
-        // Report progress
-        if let Some(reporter) = &progress_reporter {
-            reporter.set_total_files(files.len());
+        if let Some(reporter) = progress_reporter {
+            reporter.finish_conversion();
         }
 
-        // Execute conversion
-        self.convert_images(&files, &output_dir, progress_reporter)?;
+        self.finish_checkpoint();
+
+        // Keep the hashes of this run for the next one; a dry run converts nothing
+        if let Some(manifest) = &self.manifest
+            && !self.options.dry_run
+            && let Err(e) = manifest.save()
+        {
+            log::warn!("Failed to save manifest: {e:#}");
+        }
 
         let duration = start_time.elapsed();
         let end_time_utc = Utc::now();
+        // Quick dry runs only analyze the images, so no output size is known
+        let compressed_size_unknown = self.options.dry_run && !self.options.dry_run_accurate;
 
         // Create final report
-        Ok(ConversionReport {
+        let report = ConversionReport {
             start_time: start_time_utc,
             end_time: end_time_utc,
             duration,
             input_dir: self.options.input_dir.clone(),
             output_dir,
-            total_files: files.len() as u64,
+            total_files: total_files as u64,
             processed_files: self.stats.processed_count.load(Ordering::Relaxed),
             failed_files: self.stats.error_count.load(Ordering::Relaxed),
             skipped_files: self.stats.skipped_count.load(Ordering::Relaxed),
             original_size: self.stats.original_size.load(Ordering::Relaxed),
             compressed_size: self.stats.compressed_size.load(Ordering::Relaxed),
-            compression_ratio: self.stats.get_compression_ratio(),
+            space_saved_ratio: if compressed_size_unknown {
+                0.0
+            } else {
+                self.stats.get_space_saved_ratio()
+            },
+            size_ratio: if compressed_size_unknown {
+                0.0
+            } else {
+                self.stats.get_size_ratio()
+            },
             files_per_second: self.stats.processed_count.load(Ordering::Relaxed) as f64
                 / duration.as_secs_f64(),
             bytes_per_second: (self.stats.compressed_size.load(Ordering::Relaxed) as f64
@@ -102,202 +464,1629 @@ impl WebpifyCore {
             thread_count: rayon::current_num_threads(),
             quality: self.options.quality,
             mode: format!("{:?}", self.options.mode),
+            format_overrides: self.format_override_usage(),
             format_stats: self.stats.get_format_stats(),
             errors: self.stats.get_errors(),
-        })
-    }
+            quality_searches: self.stats.get_quality_searches(),
+            cancelled: self.is_cancelled(),
+            dry_run: self.options.dry_run,
+            compressed_size_unknown,
+            renamed_outputs: self.renamed_outputs.len() as u64,
+            kept_originals: self.stats.kept_original_count.load(Ordering::Relaxed),
+            webp_reencoded: self.stats.webp_reencoded_count.load(Ordering::Relaxed),
+            webp_kept: self.stats.webp_kept_count.load(Ordering::Relaxed),
+            passed_through: self.stats.passed_through_count.load(Ordering::Relaxed),
+            error_counts: self.stats.get_error_counts(),
+            skip_reasons: self.stats.get_skip_reasons(),
+            file_limit: self.file_limit(),
+            output_files: self.stats.get_output_files(),
+            files: self.stats.get_file_results(),
+            json_lines_stream: self.json_lines_stream(),
+        };
 
-    /// Scan input files with progress updates
-    fn scan_input_files(&self) -> Result<Vec<PathBuf>> {
-        let mut files = Vec::new();
+        self.write_log(
+            "INFO",
+            &format!(
+                "Finished: {} processed, {} skipped, {} failed in {:.1}s",
+                report.processed_files,
+                report.skipped_files,
+                report.failed_files,
+                duration.as_secs_f64()
+            ),
+        );
 
-        for entry in WalkDir::new(&self.options.input_dir)
-            .follow_links(false)
-            .into_iter()
-        {
-            let entry = entry.context("Failed to read directory entry")?;
-            let path = entry.path();
+        Ok(report)
+    }
 
-            if !path.is_file() {
-                continue;
+    /// Compare the estimated output size of the batch with the free space on the output volume
+    fn check_free_space(&self, files: &[PathBuf], sizes: &[u64], output_dir: &Path) -> Result<()> {
+        let available = match fs2::available_space(output_dir) {
+            Ok(available) => available,
+            Err(e) => {
+                log::debug!("Could not read free space of {}: {e}", output_dir.display());
+                return Ok(());
             }
+        };
 
-            if !is_valid_image_file(path) {
-                continue;
-            }
+        // Re-encoded WebP inputs are estimated with the mode they will really get
+        let format_overrides = self.options.effective_format_overrides();
+        let estimated: u64 = files
+            .iter()
+            .zip(sizes)
+            .map(|(path, &size)| {
+                let (mode, quality) = path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .and_then(|ext| format_overrides.get(&ext.to_lowercase()))
+                    .map_or(
+                        (&self.options.mode, self.options.quality),
+                        |(mode, quality)| (mode, *quality),
+                    );
+                (size as f64 * mode.estimated_size_factor(quality)) as u64
+            })
+            .sum();
+        if estimated <= available {
+            return Ok(());
+        }
 
-            // Check file extension
-            if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
-                let ext_lower = extension.to_lowercase();
-                if !self.options.formats.contains(&ext_lower) {
-                    continue;
-                }
+        let message = format!(
+            "Not enough free space in {}: the output needs about {} (estimated), {} available",
+            output_dir.display(),
+            humansize::format_size(estimated, humansize::DECIMAL),
+            humansize::format_size(available, humansize::DECIMAL)
+        );
+        if self.options.space_check {
+            anyhow::bail!("{message} (disable the space check to convert anyway)");
+        }
+        log::warn!("{message}");
+        Ok(())
+    }
 
-                // Skip WebP files unless re-encoding is enabled
-                if ext_lower == "webp" && !self.options.reencode_webp {
-                    continue;
-                }
-            }
+    /// The file cap, when it left matching files out of this run
+    fn file_limit(&self) -> Option<u64> {
+        self.options
+            .max_files
+            .filter(|_| self.file_limit_reached.load(Ordering::Relaxed))
+            .map(|max_files| max_files as u64)
+    }
 
-            // Check file size constraints
-            if let Ok(metadata) = std::fs::metadata(path) {
-                let file_size = metadata.len();
+    /// Mode and quality of every format override, with the number of files of that format
+    fn format_override_usage(&self) -> HashMap<String, FormatOverrideUsage> {
+        let format_stats = self.stats.get_format_stats();
+        self.options
+            .effective_format_overrides()
+            .iter()
+            .map(|(extension, (mode, quality))| {
+                let usage = FormatOverrideUsage {
+                    mode: format!("{mode:?}"),
+                    quality: *quality,
+                    files: format_stats.get(extension).copied().unwrap_or(0),
+                };
+                (extension.clone(), usage)
+            })
+            .collect()
+    }
+
+    /// Remember the outputs of `outcome` for the verification pass
+    ///
+    /// Replacing the source waits until its outputs have been verified.
+    fn queue_verification(&self, input_path: &Path, outcome: &ConversionOutcome) {
+        if let Ok(mut pending) = self.pending_verification.lock() {
+            pending.push(PendingVerification {
+                source: input_path.to_path_buf(),
+                outcome: outcome.clone(),
+            });
+        }
+    }
 
-                if file_size < self.options.min_size * 1024 {
+    /// Decode every output written in this run again and record those that don't match,
+    /// then replace the sources whose outputs all passed
+    ///
+    /// Failures count as failed files, on top of the conversion that wrote them, and keep
+    /// their source.
+    fn verify_outputs(&self) {
+        let pending = match self.pending_verification.lock() {
+            Ok(mut pending) => std::mem::take(&mut *pending),
+            Err(_) => return,
+        };
+        if pending.is_empty() {
+            return;
+        }
+        // Kept originals are copies of the source and have no expected dimensions
+        let output_count: usize = pending
+            .iter()
+            .map(|entry| entry.outcome.output_dimensions.len())
+            .sum();
+
+        log::info!("Verifying {output_count} outputs");
+        let failed = AtomicUsize::new(0);
+        pending.par_iter().for_each(|entry| {
+            let mut verified = true;
+            for (output, &dimensions) in entry
+                .outcome
+                .output_paths
+                .iter()
+                .zip(&entry.outcome.output_dimensions)
+            {
+                let _reservation = self
+                    .memory_budget
+                    .as_ref()
+                    .map(|budget| budget.acquire(estimate_decoded_bytes(output)));
+                let Some(reason) = verification_failure(output, dimensions) else {
                     continue;
-                }
+                };
 
-                if let Some(max_size) = self.options.max_size {
-                    if file_size > max_size * 1024 * 1024 {
-                        continue;
-                    }
+                verified = false;
+                failed.fetch_add(1, Ordering::Relaxed);
+                let message = format!("Verification of {} failed: {}", output.display(), reason);
+                log::error!("{message}");
+                self.stats.record_error(
+                    entry.source.display().to_string(),
+                    ErrorKind::Corrupt,
+                    message.clone(),
+                    0,
+                );
+                self.emit(ProgressEvent::Error {
+                    path: Some(entry.source.clone()),
+                    message,
+                });
+            }
+
+            if !verified {
+                if self.options.replace_input != ReplaceInputMode::Off {
+                    self.stats.record_warning(
+                        entry.source.display().to_string(),
+                        "Source kept instead of being replaced: its output failed verification"
+                            .to_string(),
+                    );
                 }
+                return;
+            }
+            if let Err(e) = self.handle_input_replacement(&entry.source, &entry.outcome) {
+                log::warn!(
+                    "Failed to handle input replacement for {}: {}",
+                    entry.source.display(),
+                    e
+                );
             }
+        });
 
-            files.push(path.to_path_buf());
+        match failed.into_inner() {
+            0 => log::info!("All {output_count} outputs verified"),
+            failed => log::warn!("{failed} of {output_count} outputs failed verification"),
         }
+    }
 
-        Ok(files)
+    /// Keep the checkpoint of a cancelled run for `resume`, otherwise the batch is done
+    fn finish_checkpoint(&self) {
+        let Some(checkpoint) = &self.checkpoint else {
+            return;
+        };
+
+        let result = if self.is_cancelled() {
+            checkpoint.save()
+        } else {
+            checkpoint.remove()
+        };
+        if let Err(e) = result {
+            log::warn!("{e:#}");
+        }
     }
 
-    /// Streaming file scan (alternative implementation)
-    fn scan_files_streaming(&self) -> Result<Vec<PathBuf>> {
-        // For now, use the same implementation as scan_input_files
-        // This could be optimized for very large directories
-        self.scan_input_files()
+    /// Whether the run was cancelled through its control handle
+    fn is_cancelled(&self) -> bool {
+        self.options
+            .control
+            .as_ref()
+            .is_some_and(|control| control.is_cancelled())
     }
 
-    /// Convert images with parallel processing
-    fn convert_images(
-        &self,
-        files: &[PathBuf],
-        output_dir: &Path,
-        progress_reporter: Option<Box<dyn ProgressReporter>>,
-    ) -> Result<()> {
-        let converter = ImageConverter::new_with_dry_run(
-            self.options.quality,
-            &self.options.mode,
-            self.options.dry_run,
-        );
+    /// Lower the priority of the calling thread and every worker in the thread pool
+    ///
+    /// Only called inside the run's own pool, whose threads exit when the run ends; where
+    /// the priority belongs to the whole process it stays lowered after the run.
+    fn lower_priority(&self) {
+        if let Err(e) = lower_current_priority() {
+            log::warn!("Failed to lower process priority: {e}");
+            return;
+        }
 
-        // Process files in parallel
-        files.par_iter().for_each(|input_path| {
-            let result = self.process_single_file(&converter, input_path, output_dir);
-
-            match result {
-                Ok((original_size, compressed_size)) => {
-                    self.stats.record_success(original_size, compressed_size);
-
-                    // Handle input file replacement
-                    if !self.options.dry_run {
-                        if let Err(e) = self.handle_input_replacement(input_path) {
-                            log::warn!(
-                                "Failed to handle input replacement for {}: {}",
-                                input_path.display(),
-                                e
-                            );
-                        }
-                    }
+        // Workers of an already running pool do not inherit the new priority
+        let failures = rayon::broadcast(|_| lower_current_priority().is_err())
+            .into_iter()
+            .filter(|failed| *failed)
+            .count();
+        if failures > 0 {
+            log::warn!("Failed to lower priority of {failures} worker threads");
+        } else {
+            log::debug!("Running conversions at reduced priority");
+        }
+    }
+
+    /// Scan input files with progress updates, returning their paths and sizes in bytes
+    ///
+    /// The directory walk is sequential, but the per-file checks (header, metadata and
+    /// dimensions) run in parallel since their syscalls dominate on network drives. The
+    /// files keep the walk order, so runs over the same tree are reproducible.
+    fn scan_input_files(&self) -> Result<(Vec<PathBuf>, Vec<u64>)> {
+        let mut candidates = Vec::new();
+        self.walk_candidates(|path| {
+            candidates.push(path);
+            ControlFlow::Continue(())
+        })?;
+
+        let found = AtomicUsize::new(0);
+        let files: Vec<(PathBuf, u64)> = candidates
+            .into_par_iter()
+            .filter_map(|path| {
+                if self.is_cancelled() {
+                    return None;
                 }
-                Err(e) => {
-                    self.stats
-                        .record_error(input_path.display().to_string(), format!("{e:#}"));
-                    log::error!("Failed to convert {}: {:#}", input_path.display(), e);
+                let size = self.check_candidate(&path)?;
+                let found = found.fetch_add(1, Ordering::Relaxed) + 1;
+                if found.is_multiple_of(SCAN_PROGRESS_INTERVAL) {
+                    self.emit(ProgressEvent::ScanProgress { files_found: found });
                 }
-            }
+                Some((path, size))
+            })
+            .collect();
 
-            // Report progress
-            if let Some(reporter) = &progress_reporter {
-                reporter.update_progress(
-                    self.stats.processed_count.load(Ordering::Relaxed) as usize,
-                    self.stats.error_count.load(Ordering::Relaxed) as usize,
-                );
-            }
+        self.warn_inaccessible_paths();
+        self.emit(ProgressEvent::ScanProgress {
+            files_found: files.len(),
         });
 
-        Ok(())
+        let mut files = files;
+        match self.options.sort_order {
+            SortOrder::None => {}
+            SortOrder::PathAlphabetical => files.sort_by(|(a, _), (b, _)| a.cmp(b)),
+            SortOrder::SizeAscending => files.sort_by(|(a_path, a_size), (b_path, b_size)| {
+                a_size.cmp(b_size).then_with(|| a_path.cmp(b_path))
+            }),
+            SortOrder::SizeDescending => files.sort_by(|(a_path, a_size), (b_path, b_size)| {
+                b_size.cmp(a_size).then_with(|| a_path.cmp(b_path))
+            }),
+        }
+
+        if let Some(max_files) = self
+            .options
+            .max_files
+            .filter(|&max_files| files.len() > max_files)
+        {
+            files.truncate(max_files);
+            self.file_limit_reached.store(true, Ordering::Relaxed);
+        }
+
+        Ok(files.into_iter().unzip())
     }
 
-    /// Process a single file conversion
-    fn process_single_file(
-        &self,
-        converter: &ImageConverter,
-        input_path: &Path,
-        output_dir: &Path,
-    ) -> Result<(u64, u64)> {
-        let output_path = self.calculate_output_path(input_path, output_dir)?;
+    /// Walk the input directory and pass every file that should be converted to `visit`
+    /// along with its size in bytes (0 if unreadable)
+    ///
+    /// Returns the number of files found. Files past the file cap are left out.
+    fn walk_input_files(&self, mut visit: impl FnMut(PathBuf, u64)) -> Result<usize> {
+        let mut found = 0;
+        self.walk_candidates(|path| {
+            let Some(size) = self.check_candidate(&path) else {
+                return ControlFlow::Continue(());
+            };
+            if self
+                .options
+                .max_files
+                .is_some_and(|max_files| found >= max_files)
+            {
+                // A matching file was left out; nothing after the cap is converted, so the
+                // rest of the tree isn't walked
+                self.file_limit_reached.store(true, Ordering::Relaxed);
+                return ControlFlow::Break(());
+            }
+            visit(path, size);
+            found += 1;
+            if found.is_multiple_of(SCAN_PROGRESS_INTERVAL) {
+                self.emit(ProgressEvent::ScanProgress { files_found: found });
+            }
+            ControlFlow::Continue(())
+        })?;
+
+        self.warn_inaccessible_paths();
+        self.emit(ProgressEvent::ScanProgress { files_found: found });
+
+        Ok(found)
+    }
 
-        // Check if output file already exists
-        if output_path.exists() && !self.options.overwrite {
-            self.stats.record_skip();
-            return Ok((0, 0)); // Skip without error
+    /// Walk the input directories and pass every file whose path passes the glob and
+    /// format filters to `visit`
+    ///
+    /// The checks that read the file itself are left to
+    /// [`check_candidate`](Self::check_candidate). The walk ends early when `visit`
+    /// returns [`ControlFlow::Break`].
+    fn walk_candidates(&self, mut visit: impl FnMut(PathBuf) -> ControlFlow<()>) -> Result<()> {
+        let formats = self.options.effective_formats();
+
+        if self
+            .options
+            .formats
+            .iter()
+            .any(|format| self.options.excludes_format(format))
+        {
+            log::info!(
+                "Excluding .{OUTPUT_FORMAT} inputs because the output format is WebP \
+                 (enable re-encoding of WebP files to convert them)"
+            );
         }
 
-        // Create output directory if needed
-        if let Some(parent) = output_path.parent() {
-            std::fs::create_dir_all(parent)
-                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        let include = build_glob_set(&self.options.include_globs)?;
+        let exclude = build_glob_set(&self.options.exclude_globs)?;
+
+        // Include/exclude patterns match the path below the input directory
+        let accepts = |root: &Path, path: &Path| {
+            let relative_path = path.strip_prefix(root).unwrap_or(path);
+            let excluded = include
+                .as_ref()
+                .is_some_and(|include| !include.is_match(relative_path))
+                || exclude
+                    .as_ref()
+                    .is_some_and(|exclude| exclude.is_match(relative_path));
+            if excluded {
+                self.stats.record_filtered(SkipReason::GlobExcluded);
+                return false;
+            }
+
+            // Check file extension; with detection by content the header decides later
+            if self.options.detect_by_content {
+                return true;
+            }
+            let Some(extension) = path.extension().and_then(|ext| ext.to_str()) else {
+                return true;
+            };
+            let extension = extension.to_lowercase();
+            if formats.contains(&extension) {
+                return true;
+            }
+            self.stats
+                .record_filtered(if self.options.excludes_format(&extension) {
+                    SkipReason::WebpNoReencode
+                } else {
+                    SkipReason::FormatFiltered
+                });
+            false
+        };
+
+        // A file list replaces the directory walk
+        if !self.options.input_files.is_empty() {
+            let base_dir = self.options.base_dir.as_deref().unwrap_or(Path::new(""));
+            // Outputs mirror the paths below the base directory, so a file outside of it
+            // would be written outside the output directory
+            let outside: Vec<String> = self
+                .options
+                .input_files
+                .iter()
+                .filter(|path| base_dir.as_os_str().is_empty() || !path.starts_with(base_dir))
+                .map(|path| path.display().to_string())
+                .collect();
+            if !outside.is_empty() {
+                anyhow::bail!(
+                    "Listed input files are not under the base directory {} (set the base \
+                     directory to one containing them):\n{}",
+                    base_dir.display(),
+                    outside.join("\n")
+                );
+            }
+
+            for path in &self.options.input_files {
+                if self.is_cancelled() {
+                    break;
+                }
+                if !path.is_file() {
+                    log::error!("Listed input file not found: {}", path.display());
+                    self.stats.record_error(
+                        path.display().to_string(),
+                        ErrorKind::Io,
+                        "Listed input file not found".to_string(),
+                        0,
+                    );
+                    continue;
+                }
+                if accepts(base_dir, path) && visit(path.clone()).is_break() {
+                    break;
+                }
+            }
+            return Ok(());
         }
 
-        // Record format statistics
-        if let Some(extension) = input_path.extension().and_then(|ext| ext.to_str()) {
-            self.stats.record_format(&extension.to_lowercase());
+        // Canonical paths of the directories walked so far, so a directory reachable
+        // through several symlinks is only scanned once
+        let visited_dirs = &RefCell::new(HashSet::new());
+        let follow_symlinks = self.options.follow_symlinks;
+        // WalkDir counts the input directory itself as depth 0
+        let max_depth = self.options.max_depth.map_or(usize::MAX, |depth| depth + 1);
+
+        let walker = self.input_roots().iter().flat_map(move |(root, _)| {
+            WalkDir::new(root)
+                .follow_links(follow_symlinks)
+                .max_depth(max_depth)
+                .into_iter()
+                .filter_entry(move |entry| {
+                    if !follow_symlinks || !entry.file_type().is_dir() {
+                        return true;
+                    }
+                    let Ok(canonical) = entry.path().canonicalize() else {
+                        return true;
+                    };
+                    if visited_dirs.borrow_mut().insert(canonical) {
+                        return true;
+                    }
+                    if entry.path_is_symlink() {
+                        self.record_skipped_symlink(entry.path());
+                    }
+                    false
+                })
+                .map(move |entry| (root, entry))
+        });
+
+        // Nested input directories reach the same files twice; the innermost root owns
+        // them in `calculate_output_path` whichever walk finds them first
+        let several_roots = self.input_roots().len() > 1;
+        let mut seen_files = HashSet::new();
+
+        // Unreadable directories are common on shared drives and only cost their contents
+        for (root, entry) in walker {
+            if self.is_cancelled() {
+                break;
+            }
+
+            // A symlink pointing back at one of its ancestors is reported as an error
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) if e.loop_ancestor().is_some() => {
+                    if let Some(path) = e.path() {
+                        self.record_skipped_symlink(path);
+                    }
+                    continue;
+                }
+                Err(e)
+                    if e.io_error().map(std::io::Error::kind)
+                        == Some(std::io::ErrorKind::PermissionDenied) =>
+                {
+                    self.record_inaccessible(e.path().unwrap_or(root.as_path()));
+                    continue;
+                }
+                Err(e) => return Err(e).context("Failed to read directory entry"),
+            };
+            let path = entry.path();
+
+            if !path.is_file() {
+                continue;
+            }
+            if several_roots
+                && !seen_files.insert(path.canonicalize().unwrap_or_else(|_| path.to_path_buf()))
+            {
+                continue;
+            }
+
+            if accepts(root, path) && visit(path.to_path_buf()).is_break() {
+                break;
+            }
         }
 
-        // Perform conversion
-        converter.convert_to_webp(input_path, &output_path)
+        Ok(())
     }
 
-    /// Calculate the output path for a given input file
-    fn calculate_output_path(&self, input_path: &Path, output_dir: &Path) -> Result<PathBuf> {
-        let relative_path = input_path
-            .strip_prefix(&self.options.input_dir)
-            .with_context(|| {
+    /// Record a path the scan was denied access to
+    fn record_inaccessible(&self, path: &Path) {
+        log::warn!("Skipping {}: permission denied", path.display());
+        self.stats.record_warning(
+            path.display().to_string(),
+            "Skipped during scan: permission denied".to_string(),
+        );
+        self.inaccessible_paths.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Summarize the paths the scan could not read, if there were any
+    fn warn_inaccessible_paths(&self) {
+        let inaccessible = self.inaccessible_paths.load(Ordering::Relaxed);
+        if inaccessible > 0 {
+            log::warn!("{inaccessible} paths could not be read and were skipped");
+            self.stats.record_warning(
+                self.options.input_dir.display().to_string(),
                 format!(
-                    "Input path {} is not under input directory {}",
-                    input_path.display(),
-                    self.options.input_dir.display()
-                )
-            })?;
+                    "{inaccessible} inaccessible paths skipped during scan (permission denied)"
+                ),
+            );
+        }
+    }
 
-        let output_path = if self.options.preserve_structure {
-            output_dir.join(relative_path)
+    /// Check a candidate's header, size, modification time and dimensions
+    ///
+    /// Returns the file size in bytes (0 if unreadable) if the file should be converted.
+    /// Empty files and unreadable headers are recorded as failures.
+    fn check_candidate(&self, path: &Path) -> Option<u64> {
+        let validation = if self.options.detect_by_content {
+            self.check_content_format(path)
         } else {
-            output_dir.join(input_path.file_name().context("Failed to get filename")?)
+            validate_image_file(path)
         };
+        match validation {
+            Ok(()) => {}
+            // Empty and truncated files are failures rather than silently left out
+            Err(ImageValidationError::FileTooSmall) => {
+                log::error!("File is empty or truncated: {}", path.display());
+                self.stats.record_error(
+                    path.display().to_string(),
+                    ErrorKind::Corrupt,
+                    "File is empty or truncated".to_string(),
+                    0,
+                );
+                return None;
+            }
+            // Unreadable files are counted like unreadable directories
+            Err(ImageValidationError::IoError(e))
+                if e.kind() == std::io::ErrorKind::PermissionDenied =>
+            {
+                self.record_inaccessible(path);
+                return None;
+            }
+            Err(_) => return None,
+        }
 
-        // Change extension to .webp
-        Ok(output_path.with_extension("webp"))
-    }
+        // Check file size constraints
+        let mut file_size = 0;
+        if let Ok(metadata) = std::fs::metadata(path) {
+            file_size = metadata.len();
 
-    /// Handle input file replacement after successful conversion
-    fn handle_input_replacement(&self, input_path: &Path) -> Result<()> {
-        match self.options.replace_input {
-            ReplaceInputMode::Off => Ok(()),
-            ReplaceInputMode::Recycle => {
-                trash::delete(input_path).with_context(|| {
-                    format!("Failed to move to recycle bin: {}", input_path.display())
-                })?;
-                Ok(())
+            if file_size < self.options.min_size * 1024 {
+                self.stats.record_filtered(SkipReason::BelowMinSize);
+                return None;
             }
-            ReplaceInputMode::Delete => {
-                std::fs::remove_file(input_path)
-                    .with_context(|| format!("Failed to delete file: {}", input_path.display()))?;
-                Ok(())
+
+            if let Some(max_size) = self.options.max_size
+                && file_size > max_size * 1024 * 1024
+            {
+                self.stats.record_filtered(SkipReason::AboveMaxSize);
+                return None;
+            }
+
+            // Check modification date; files without a readable one are kept
+            if self.options.modified_after.is_some() || self.options.modified_before.is_some() {
+                match metadata.modified() {
+                    Ok(modified) => {
+                        let modified = DateTime::<Utc>::from(modified);
+                        if self
+                            .options
+                            .modified_after
+                            .is_some_and(|after| modified < after)
+                            || self
+                                .options
+                                .modified_before
+                                .is_some_and(|before| modified >= before)
+                        {
+                            self.stats.record_filtered(SkipReason::OutsideDateRange);
+                            return None;
+                        }
+                    }
+                    Err(e) => log::warn!(
+                        "Including {} because its modification time cannot be read: {}",
+                        path.display(),
+                        e
+                    ),
+                }
+            }
+        }
+
+        // Check pixel dimensions, reading only the image header
+        if self.options.min_dimensions.is_some() || self.options.max_dimensions.is_some() {
+            let dimensions = if heif::is_heif_path(path) {
+                heif::image_dimensions(path)
+            } else {
+                image::image_dimensions(path).map_err(anyhow::Error::from)
+            };
+            match dimensions {
+                Ok((width, height)) => {
+                    let too_small =
+                        self.options
+                            .min_dimensions
+                            .is_some_and(|(min_width, min_height)| {
+                                width < min_width || height < min_height
+                            });
+                    let too_large =
+                        self.options
+                            .max_dimensions
+                            .is_some_and(|(max_width, max_height)| {
+                                width > max_width || height > max_height
+                            });
+                    if too_small || too_large {
+                        self.stats.record_filtered(SkipReason::OutsideDimensions);
+                        return None;
+                    }
+                }
+                Err(e) => {
+                    // Unreadable headers are failures, not silently filtered files
+                    log::error!(
+                        "Failed to read image dimensions of {}: {:#}",
+                        path.display(),
+                        e
+                    );
+                    self.stats.record_error(
+                        path.display().to_string(),
+                        ErrorKind::classify(&e),
+                        format!("Failed to read image dimensions: {e:#}"),
+                        0,
+                    );
+                    return None;
+                }
             }
         }
+
+        Some(file_size)
     }
 
-    /// Create an empty report for when no files are found
-    fn create_empty_report(
-        &self,
-        start_time_utc: chrono::DateTime<Utc>,
-        start_time: Instant,
+    /// Check the format named by the header of `path` against the selected formats
+    fn check_content_format(&self, path: &Path) -> Result<(), ImageValidationError> {
+        let format = match detect_image_format(path) {
+            Ok(format) => format,
+            // Files named like images still report being empty or damaged
+            Err(_) => {
+                return validate_image_file(path).and(Err(ImageValidationError::InvalidHeader));
+            }
+        };
+
+        let formats = self.options.effective_formats();
+        if !formats
+            .iter()
+            .any(|selected| is_same_format(selected, format))
+        {
+            self.stats
+                .record_filtered(if self.options.excludes_format(format) {
+                    SkipReason::WebpNoReencode
+                } else {
+                    SkipReason::FormatFiltered
+                });
+            return Err(ImageValidationError::InvalidExtension);
+        }
+
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        if !extension.is_some_and(|extension| is_same_format(extension, format)) {
+            log::debug!(
+                "Treating {} as {} based on its header",
+                path.display(),
+                format
+            );
+        }
+        Ok(())
+    }
+
+    /// Note a symlinked directory that was not walked because it would revisit a directory
+    fn record_skipped_symlink(&self, path: &Path) {
+        log::warn!(
+            "Skipping symlink {} because its target was already scanned (symlink cycle)",
+            path.display()
+        );
+        self.stats.record_warning(
+            path.display().to_string(),
+            "Skipped symlink cycle (target directory was already scanned)".to_string(),
+        );
+    }
+
+    /// Scan the input set and return the files a run would convert
+    ///
+    /// Applies the same format, size, dimension and glob filters and sort order as a
+    /// run, but decodes nothing and writes nothing, so it is much faster than a dry run.
+    pub fn list_candidates(&self) -> Result<Vec<PathBuf>> {
+        let (files, _) = self.scan_input_files()?;
+        Ok(files)
+    }
+
+    /// Scan the input set and report output paths shared by more than one input file
+    ///
+    /// Only flattened output (`preserve_structure = false`), a filename template, in-place
+    /// output or a custom output path resolver can collide, so otherwise the result is
+    /// always empty.
+    pub fn find_output_collisions(&self) -> Result<Vec<OutputCollision>> {
+        if self.options.preserve_structure
+            && self.options.filename_template.is_none()
+            && !self.options.in_place
+            && self.options.output_path_resolver.is_none()
+        {
+            return Ok(Vec::new());
+        }
+
+        let (files, _) = self.scan_input_files()?;
+        Ok(self.detect_output_collisions(&files, &self.options.get_output_dir()))
+    }
+
+    /// Apply the collision policy to inputs sharing an output path
+    ///
+    /// Returns the new output path of every renamed input. The first input of each group
+    /// keeps its name; the others get the first free numeric suffix (`logo_2.webp`).
+    /// Collisions caused by a filename template are always an error.
+    fn resolve_output_collisions(
+        &self,
+        files: &[PathBuf],
+        output_dir: &Path,
+    ) -> Result<HashMap<PathBuf, PathBuf>> {
+        let mut renamed = HashMap::new();
+        if self.options.preserve_structure
+            && self.options.filename_template.is_none()
+            && !self.options.in_place
+            && self.options.output_path_resolver.is_none()
+        {
+            return Ok(renamed);
+        }
+
+        let collisions = self.detect_output_collisions(files, output_dir);
+        if collisions.is_empty() {
+            return Ok(renamed);
+        }
+
+        let describe = |collision: &OutputCollision| {
+            let sources: Vec<String> = collision
+                .sources
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            format!(
+                "{} files map to {}: {}",
+                sources.len(),
+                collision.output_path.display(),
+                sources.join(", ")
+            )
+        };
+
+        if let Some(template) = &self.options.filename_template {
+            anyhow::bail!(
+                "Filename template '{}' maps several inputs to the same output; {}",
+                template,
+                describe(&collisions[0])
+            );
+        }
+        if self.options.collision_policy == CollisionPolicy::Error {
+            let details: Vec<String> = collisions.iter().map(describe).collect();
+            anyhow::bail!(
+                "Output name collisions with flattened structure (use the rename collision \
+                 policy or preserve the directory structure):\n{}",
+                details.join("\n")
+            );
+        }
+
+        // Suffixes must not clash with any other planned output either
+        let mut taken: HashSet<PathBuf> = files
+            .iter()
+            .enumerate()
+            .filter_map(|(index, file)| {
+                self.calculate_output_path(file, index + 1, output_dir).ok()
+            })
+            .collect();
+
+        for collision in &collisions {
+            log::warn!(
+                "Output name collision, later files get a numbered suffix: {}",
+                describe(collision)
+            );
+            let stem = collision
+                .output_path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            // Keep the extension of the colliding path, which a resolver may have chosen
+            let extension = collision
+                .output_path
+                .extension()
+                .map(|extension| extension.to_string_lossy().into_owned())
+                .unwrap_or_else(|| self.options.output_extension.clone());
+
+            let mut suffix = 2;
+            for source in &collision.sources[1..] {
+                let renamed_path = loop {
+                    let candidate = collision
+                        .output_path
+                        .with_file_name(format!("{stem}_{suffix}.{extension}"));
+                    suffix += 1;
+                    if taken.insert(candidate.clone()) {
+                        break candidate;
+                    }
+                };
+                log::debug!(
+                    "Writing {} to {} because its output name is already taken",
+                    source.display(),
+                    renamed_path.display()
+                );
+                renamed.insert(source.clone(), renamed_path);
+            }
+        }
+
+        Ok(renamed)
+    }
+
+    /// Group input files by output path and keep the groups with more than one source
+    fn detect_output_collisions(
+        &self,
+        files: &[PathBuf],
+        output_dir: &Path,
+    ) -> Vec<OutputCollision> {
+        let mut targets: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+
+        for (index, file) in files.iter().enumerate() {
+            if let Ok(output_path) = self.calculate_output_path(file, index + 1, output_dir) {
+                targets.entry(output_path).or_default().push(file.clone());
+            }
+        }
+
+        targets
+            .into_iter()
+            .filter(|(_, sources)| sources.len() > 1)
+            .map(|(output_path, sources)| OutputCollision {
+                output_path,
+                sources,
+            })
+            .collect()
+    }
+
+    /// Walk the input tree on a separate thread and convert files as they are found
+    ///
+    /// Paths flow through a bounded channel into the worker pool, so conversion starts
+    /// right away and memory stays flat however large the tree is. The progress total
+    /// grows as files are discovered. Returns the number of files found.
+    fn convert_streaming(
+        &self,
+        output_dir: &Path,
+        progress_reporter: Option<&dyn ProgressReporter>,
+    ) -> Result<usize> {
+        let converters = FormatConverters::new(&self.options);
+        let (sender, receiver) = crossbeam_channel::bounded(STREAMING_QUEUE_CAPACITY);
+        // Bytes of the files found so far; the total grows with the walk
+        let scanned_bytes = &AtomicU64::new(0);
+
+        std::thread::scope(|scope| {
+            let walker = scope.spawn(move || {
+                let mut found = 0;
+                // The channel closes when `sender` is dropped at the end of the walk
+                self.walk_input_files(|path, size| {
+                    found += 1;
+                    scanned_bytes.fetch_add(size, Ordering::Relaxed);
+                    if let Some(reporter) = progress_reporter {
+                        reporter.set_total_files(found);
+                    }
+                    // Workers only stop receiving once every file has been handled
+                    let _ = sender.send((found, path, size));
+                })
+            });
+
+            receiver
+                .into_iter()
+                .par_bridge()
+                .for_each(|(index, input_path, size)| {
+                    if self.convert_file(
+                        &converters,
+                        &input_path,
+                        index,
+                        size,
+                        output_dir,
+                        progress_reporter,
+                    ) {
+                        // The total is still growing, so there is no ETA yet
+                        if let Some(reporter) = progress_reporter {
+                            self.report_stats(
+                                reporter,
+                                None,
+                                scanned_bytes.load(Ordering::Relaxed),
+                            );
+                        }
+                    }
+                });
+
+            walker
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        })
+    }
+
+    /// Convert images with parallel processing
+    ///
+    /// `sizes` holds the size in bytes of each file in `files`.
+    fn convert_images(
+        &self,
+        files: &[PathBuf],
+        sizes: &[u64],
+        output_dir: &Path,
+        progress_reporter: Option<&dyn ProgressReporter>,
+    ) {
+        let converters = FormatConverters::new(&self.options);
+        let handled = AtomicUsize::new(0);
+        let total_bytes = sizes.iter().sum();
+
+        // Start the largest files first so a big image picked up last doesn't keep one core
+        // busy while the others idle; small files fill the gaps at the end. An explicit sort
+        // order is kept as the processing order.
+        let mut order: Vec<usize> = (0..files.len()).collect();
+        if self.options.largest_first && self.options.sort_order == SortOrder::None {
+            order.sort_by_key(|&index| std::cmp::Reverse(sizes[index]));
+        }
+
+        // Workers pull files in `order`; the index stays the scan position for `{index}`
+        order.into_iter().par_bridge().for_each(|index| {
+            let input_path = &files[index];
+            if !self.convert_file(
+                &converters,
+                input_path,
+                index + 1,
+                sizes[index],
+                output_dir,
+                progress_reporter,
+            ) {
+                return;
+            }
+
+            if let Some(reporter) = progress_reporter {
+                self.report_stats(reporter, Some(files.len()), total_bytes);
+            }
+
+            // Emit a milestone each time another tenth of the batch is done
+            let done = handled.fetch_add(1, Ordering::Relaxed) + 1;
+            let tenth = done * 10 / files.len();
+            if tenth > (done - 1) * 10 / files.len() {
+                self.emit(ProgressEvent::Milestone {
+                    percent: (tenth * 10) as u8,
+                    processed: done,
+                    total: files.len(),
+                });
+            }
+        });
+    }
+
+    /// Pass the live counters and, if the total is known, an ETA to the reporter
+    fn report_stats(
+        &self,
+        reporter: &dyn ProgressReporter,
+        total_files: Option<usize>,
+        total_bytes: u64,
+    ) {
+        reporter.update_stats(
+            self.stats.processed_count.load(Ordering::Relaxed) as usize,
+            self.stats.error_count.load(Ordering::Relaxed) as usize,
+            self.stats.original_size.load(Ordering::Relaxed),
+            self.stats.compressed_size.load(Ordering::Relaxed),
+            total_files.and_then(|total| self.stats.estimate_eta(total as u64)),
+        );
+        reporter.update_bytes(
+            self.stats.processed_bytes.load(Ordering::Relaxed),
+            total_bytes,
+        );
+    }
+
+    /// Convert one file and record the outcome
+    ///
+    /// `index` is the 1-based position of the file in the scan and `size` its size in
+    /// bytes. Returns false if the file was left untouched because the run was cancelled.
+    fn convert_file(
+        &self,
+        converters: &FormatConverters,
+        input_path: &Path,
+        index: usize,
+        size: u64,
+        output_dir: &Path,
+        progress_reporter: Option<&dyn ProgressReporter>,
+    ) -> bool {
+        // Park here while paused so no new file starts until resumed
+        if let Some(control) = &self.options.control {
+            control.wait_while_paused();
+        }
+
+        // Leave the remaining files untouched once the run is cancelled
+        if self.is_cancelled() {
+            return false;
+        }
+
+        self.emit(ProgressEvent::FileStart {
+            path: input_path.to_path_buf(),
+        });
+
+        // Index of this worker in the pool, for per-worker progress display
+        let thread_id = rayon::current_thread_index().unwrap_or(0);
+        if let Some(reporter) = progress_reporter {
+            reporter.file_started(thread_id, &input_path.display().to_string());
+        }
+        let result = self.process_single_file(converters, input_path, index, size, output_dir);
+        if let Some(reporter) = progress_reporter {
+            reporter.file_finished(thread_id);
+        }
+        self.stats
+            .processed_bytes
+            .fetch_add(size, Ordering::Relaxed);
+        self.write_report_line(input_path, &result);
+        self.write_log_line(input_path, &result);
+        if let Some(sink) = &self.file_result_sink {
+            // A dropped receiver only means nobody is listening any more
+            let _ = sink.send(file_result(input_path, &result));
+        }
+        if self.options.track_files {
+            self.stats
+                .record_file_result(file_result(input_path, &result));
+        }
+
+        match result {
+            Ok(outcome) => {
+                if let Some(checkpoint) = &self.checkpoint {
+                    checkpoint.record(input_path);
+                }
+                self.stats
+                    .record_success(outcome.original_size, outcome.compressed_size);
+                if outcome.kept_original {
+                    self.stats.record_kept_original();
+                }
+                if outcome.passed_through {
+                    self.stats.record_passed_through();
+                }
+                if !outcome.skipped && !outcome.passed_through && is_webp(input_path) {
+                    self.stats.record_webp_input(outcome.kept_original);
+                }
+                if let Some(search) = &outcome.quality_search {
+                    self.stats
+                        .record_quality_search(input_path.display().to_string(), search.clone());
+                }
+
+                if self.options.track_outputs {
+                    self.stats.record_outputs(&outcome.output_paths);
+                }
+                // Handle input file replacement; with verification it waits for the
+                // outputs to pass
+                if self.options.verify && !self.options.dry_run {
+                    self.queue_verification(input_path, &outcome);
+                } else if !self.options.dry_run
+                    && let Err(e) = self.handle_input_replacement(input_path, &outcome)
+                {
+                    log::warn!(
+                        "Failed to handle input replacement for {}: {}",
+                        input_path.display(),
+                        e
+                    );
+                }
+
+                if outcome.skipped {
+                    self.emit(ProgressEvent::Skip {
+                        path: input_path.to_path_buf(),
+                    });
+                } else {
+                    if let Some(reporter) = progress_reporter {
+                        reporter.report_success(
+                            &input_path.display().to_string(),
+                            outcome.original_size,
+                            outcome.compressed_size,
+                        );
+                    }
+                    self.emit(ProgressEvent::FileDone {
+                        path: input_path.to_path_buf(),
+                        result: outcome,
+                    });
+                }
+            }
+            Err(e) => {
+                self.record_failure(input_path, &e);
+                if let Some(reporter) = progress_reporter {
+                    reporter.report_error(&input_path.display().to_string(), &format!("{e:#}"));
+                }
+                self.emit(ProgressEvent::Error {
+                    path: Some(input_path.to_path_buf()),
+                    message: format!("{e:#}"),
+                });
+            }
+        }
+
+        // Report progress
+        if let Some(reporter) = progress_reporter {
+            reporter.update_progress(
+                self.stats.processed_count.load(Ordering::Relaxed) as usize,
+                self.stats.error_count.load(Ordering::Relaxed) as usize,
+            );
+        }
+
+        true
+    }
+
+    /// Append the result for one file to the JSON Lines report, if one is being written
+    fn write_report_line(&self, input_path: &Path, result: &Result<ConversionOutcome>) {
+        let Some(stream) = &self.report_stream else {
+            return;
+        };
+
+        let written = match result {
+            Ok(outcome) => stream.write_file(
+                input_path,
+                if outcome.skipped {
+                    "skipped"
+                } else {
+                    "converted"
+                },
+                outcome.original_size,
+                outcome.compressed_size,
+                None,
+            ),
+            Err(e) => stream.write_file(
+                input_path,
+                "failed",
+                0,
+                0,
+                Some((ErrorKind::classify(e), &format!("{e:#}"))),
+            ),
+        };
+
+        if let Err(e) = written {
+            log::warn!(
+                "Failed to write report line for {}: {:#}",
+                input_path.display(),
+                e
+            );
+        }
+    }
+
+    /// Append the result for one file to the log file, if one is set
+    fn write_log_line(&self, input_path: &Path, result: &Result<ConversionOutcome>) {
+        if self.run_log.is_none() {
+            return;
+        }
+
+        match result {
+            Ok(outcome) if outcome.skipped => {
+                self.write_log("INFO", &format!("Skipped {}", input_path.display()));
+            }
+            Ok(outcome) => self.write_log(
+                "INFO",
+                &format!(
+                    "Converted {} ({} -> {})",
+                    input_path.display(),
+                    humansize::format_size(outcome.original_size, humansize::DECIMAL),
+                    humansize::format_size(outcome.compressed_size, humansize::DECIMAL)
+                ),
+            ),
+            Err(e) => self.write_log(
+                "ERROR",
+                &format!(
+                    "Failed {} [{}]: {:#}",
+                    input_path.display(),
+                    ErrorKind::classify(e),
+                    e
+                ),
+            ),
+        }
+    }
+
+    /// Append a line to the log file, if one is set
+    fn write_log(&self, level: &str, message: &str) {
+        if let Some(run_log) = &self.run_log {
+            run_log.write(level, message);
+        }
+    }
+
+    /// Process a single file conversion
+    fn process_single_file(
+        &self,
+        converters: &FormatConverters,
+        input_path: &Path,
+        index: usize,
+        size: u64,
+        output_dir: &Path,
+    ) -> Result<ConversionOutcome> {
+        // Completed before an interrupted run stopped; resuming skips it
+        if self
+            .checkpoint
+            .as_ref()
+            .is_some_and(|checkpoint| checkpoint.is_completed(input_path))
+        {
+            self.stats.record_skip(SkipReason::AlreadyConverted);
+            return Ok(ConversionOutcome {
+                skipped: true,
+                ..Default::default()
+            });
+        }
+
+        let output_path = match self.renamed_outputs.get(input_path) {
+            Some(renamed_path) => renamed_path.clone(),
+            None => self.calculate_output_path(input_path, index, output_dir)?,
+        };
+        // Files below the passthrough size are copied under their own extension
+        let passthrough = self
+            .options
+            .passthrough_below
+            .is_some_and(|passthrough_below| {
+                std::fs::metadata(input_path)
+                    .is_ok_and(|metadata| metadata.len() < passthrough_below * 1024)
+            });
+        let output_path = if passthrough {
+            output_path.with_extension(input_path.extension().unwrap_or_default())
+        } else {
+            output_path
+        };
+        if is_same_file(&output_path, input_path) {
+            // In place, a file below the passthrough size is its own copy already
+            if passthrough {
+                return converters.default.pass_through(input_path, &output_path);
+            }
+            // e.g. a re-encoded WebP written in place; the source must survive a failed encode
+            return Err(ErrorKind::OutputExists.error(format!(
+                "Output would overwrite its source {} (use a filename template to rename it)",
+                input_path.display()
+            )));
+        }
+
+        // A manifest entry decides by content hash, so copies and syncs that only touch
+        // the modification time do not cause re-conversion
+        let hash = match &self.manifest {
+            Some(_) => Some(hash_file(input_path)?),
+            None => None,
+        };
+        let unchanged = self
+            .manifest
+            .as_ref()
+            .zip(hash.as_deref())
+            .and_then(|(manifest, hash)| manifest.is_unchanged(input_path, hash, &output_path));
+
+        // Check if output file already exists; in incremental mode only an output at least
+        // as new as its source counts, and stale outputs are converted again
+        let skip = match unchanged {
+            Some(unchanged) => unchanged && output_path.exists() && !self.options.overwrite,
+            None if self.options.incremental => is_up_to_date(input_path, &output_path),
+            None => output_path.exists() && !self.options.overwrite,
+        };
+        if skip {
+            // An output from before the manifest was used becomes the baseline, so later
+            // changes to the source are noticed
+            if let (Some(manifest), Some(hash), None) = (&self.manifest, &hash, unchanged)
+                && !self.options.dry_run
+            {
+                manifest.record(input_path, hash.clone(), output_path);
+            }
+            self.stats.record_skip(SkipReason::OutputExists);
+            // Skip without error
+            return Ok(ConversionOutcome {
+                skipped: true,
+                ..Default::default()
+            });
+        }
+
+        // Create output directory if needed
+        if let Some(parent) = output_path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+
+        // Record format statistics under the real format when detecting by content
+        let format = input_format(input_path, self.options.detect_by_content);
+        if !format.is_empty() {
+            self.stats.record_format(&format);
+        }
+
+        // Wait until the decoded image fits into the memory budget. The reservation is held
+        // until the file is done, or by the conversion thread when that may outlive a timeout.
+        let reserve = || {
+            self.memory_budget
+                .as_ref()
+                .map(|budget| budget.acquire(estimate_decoded_bytes(input_path)))
+        };
+        let timeout = self.options.per_file_timeout.filter(|_| !passthrough);
+        let _reservation = if timeout.is_none() { reserve() } else { None };
+
+        // Formats with an override get their own mode and quality, others may get the
+        // quality of their size on the quality ramp
+        let converter = converters.for_format(&format, self.options.ramp_quality(size));
+        let converter = &*converter;
+
+        // Perform conversion, retrying transient failures with exponential backoff
+        let outcome = self.retry_transient(input_path, || {
+            if passthrough {
+                converter.pass_through(input_path, &output_path)
+            } else {
+                match timeout {
+                    Some(timeout) => convert_with_timeout(
+                        converter,
+                        input_path,
+                        &output_path,
+                        timeout,
+                        reserve(),
+                    ),
+                    None => convert_catching_panics(converter, input_path, &output_path),
+                }
+            }
+        })?;
+        if let (Some(manifest), Some(hash)) = (&self.manifest, hash)
+            && !self.options.dry_run
+        {
+            manifest.record(input_path, hash, output_path);
+        }
+        Ok(outcome)
+    }
+
+    /// Count `input_path` as failed with `error`, along with the retries it took
+    fn record_failure(&self, input_path: &Path, error: &anyhow::Error) {
+        let retries = error
+            .downcast_ref::<RetriesExhausted>()
+            .map_or(0, |exhausted| exhausted.retries);
+        self.stats.record_error(
+            input_path.display().to_string(),
+            ErrorKind::classify(error),
+            format!("{error:#}"),
+            retries,
+        );
+        log::error!("Failed to convert {}: {:#}", input_path.display(), error);
+    }
+
+    /// Run `attempt` for `input_path`, retrying transient failures with exponential backoff
+    ///
+    /// Once the retries are used up the error carries a [`RetriesExhausted`] context, so
+    /// the number of retries can be recorded with the failure.
+    fn retry_transient<T>(
+        &self,
+        input_path: &Path,
+        mut attempt: impl FnMut() -> Result<T>,
+    ) -> Result<T> {
+        let mut retries = 0;
+        loop {
+            match attempt() {
+                Ok(value) => return Ok(value),
+                Err(e) if retries < self.options.max_retries && is_transient_error(&e) => {
+                    retries += 1;
+                    self.stats.record_retry();
+                    log::debug!(
+                        "Retrying {} ({}/{}) after transient error: {:#}",
+                        input_path.display(),
+                        retries,
+                        self.options.max_retries,
+                        e
+                    );
+                    std::thread::sleep(RETRY_BASE_DELAY * 2u32.saturating_pow(retries - 1));
+                }
+                Err(e) if retries > 0 => return Err(e.context(RetriesExhausted { retries })),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Calculate the output path for a given input file
+    ///
+    /// `index` is the 1-based position of the file in the scan, used by `{index}` in the
+    /// filename template. A custom output path resolver replaces all other settings.
+    fn calculate_output_path(
+        &self,
+        input_path: &Path,
+        index: usize,
+        output_dir: &Path,
+    ) -> Result<PathBuf> {
+        let resolver = self.options.output_path_resolver.as_deref();
+        if self.options.in_place && resolver.is_none() {
+            return match &self.options.filename_template {
+                Some(template) => Ok(input_path
+                    .with_file_name(render_filename_template(template, input_path, index)?)),
+                None => Ok(input_path.with_extension(&self.options.output_extension)),
+            };
+        }
+
+        let (root, subdir) = self.owning_root(input_path).with_context(|| {
+            format!(
+                "Input path {} is not under input directory {}",
+                input_path.display(),
+                self.options.input_dir.display()
+            )
+        })?;
+
+        if let Some(resolver) = resolver {
+            return Ok(resolver.resolve(input_path, root));
+        }
+
+        let output_path = DefaultOutputPathResolver::new(
+            output_dir.join(subdir),
+            self.options.preserve_structure,
+        )
+        .with_extension(self.options.output_extension.as_str())
+        .resolve(input_path, root);
+
+        match &self.options.filename_template {
+            Some(template) => {
+                Ok(output_path
+                    .with_file_name(render_filename_template(template, input_path, index)?))
+            }
+            None => Ok(output_path),
+        }
+    }
+
+    /// Input roots with their output subdirectories, resolved once per core
+    ///
+    /// Resolving them canonicalizes every input directory, which is too slow to repeat
+    /// for each file.
+    fn input_roots(&self) -> &[(PathBuf, PathBuf)] {
+        self.input_roots.get_or_init(|| {
+            self.options
+                .input_roots()
+                .into_iter()
+                .map(|(root, subdir)| (root.to_path_buf(), subdir))
+                .collect()
+        })
+    }
+
+    /// The input root `input_path` belongs to; with nested input directories the
+    /// innermost one owns the file
+    fn owning_root(&self, input_path: &Path) -> Option<(&Path, &Path)> {
+        self.input_roots()
+            .iter()
+            .filter(|(root, _)| input_path.starts_with(root))
+            .max_by_key(|(root, _)| root.components().count())
+            .map(|(root, subdir)| (root.as_path(), subdir.as_path()))
+    }
+
+    /// Handle input file replacement after successful conversion
+    ///
+    /// The source is only removed when this run wrote its output, the output size is
+    /// within the configured fraction of the source size and the output decodes again;
+    /// otherwise it is kept and a warning is recorded.
+    fn handle_input_replacement(
+        &self,
+        input_path: &Path,
+        outcome: &ConversionOutcome,
+    ) -> Result<()> {
+        if self.options.replace_input == ReplaceInputMode::Off || outcome.skipped {
+            return Ok(());
+        }
+        if let Some(reason) = self.replacement_blocker(input_path, outcome) {
+            log::warn!("Keeping {}: {}", input_path.display(), reason);
+            self.stats.record_warning(
+                input_path.display().to_string(),
+                format!("Source kept instead of being replaced: {reason}"),
+            );
+            return Ok(());
+        }
+
+        match self.options.replace_input {
+            ReplaceInputMode::Off => Ok(()),
+            ReplaceInputMode::Recycle => {
+                if let Err(e) = trash::delete(input_path) {
+                    self.handle_recycle_failure(input_path, e);
+                }
+                Ok(())
+            }
+            ReplaceInputMode::Delete => {
+                std::fs::remove_file(input_path)
+                    .with_context(|| format!("Failed to delete file: {}", input_path.display()))?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Apply the recycle fallback to a source the recycle bin refused
+    ///
+    /// Never deletes the source: it ends up in the quarantine directory or stays where it
+    /// is with a warning.
+    fn handle_recycle_failure(&self, input_path: &Path, error: trash::Error) {
+        let reason = match &self.options.recycle_fallback {
+            RecycleFallback::Keep => format!("moving it to the recycle bin failed: {error}"),
+            RecycleFallback::Quarantine(quarantine_dir) => {
+                match self.move_to_quarantine(input_path, quarantine_dir) {
+                    Ok(target) => {
+                        log::info!(
+                            "Moved {} to {} because moving it to the recycle bin failed: {}",
+                            input_path.display(),
+                            target.display(),
+                            error
+                        );
+                        return;
+                    }
+                    Err(e) => format!(
+                        "moving it to the recycle bin failed ({error}) and so did moving it \
+                         to the quarantine directory: {e:#}"
+                    ),
+                }
+            }
+        };
+
+        log::warn!("Keeping {}: {}", input_path.display(), reason);
+        self.stats.record_warning(
+            input_path.display().to_string(),
+            format!("Source kept instead of being replaced: {reason}"),
+        );
+    }
+
+    /// Move `input_path` below `quarantine_dir`, mirroring its path below the input
+    /// directory, and return where it went
+    fn move_to_quarantine(&self, input_path: &Path, quarantine_dir: &Path) -> Result<PathBuf> {
+        let relative_path = self
+            .owning_root(input_path)
+            .and_then(|(root, subdir)| {
+                input_path
+                    .strip_prefix(root)
+                    .ok()
+                    .map(|relative_path| subdir.join(relative_path))
+            })
+            .filter(|relative_path| relative_path.is_relative())
+            .unwrap_or_else(|| PathBuf::from(input_path.file_name().unwrap_or_default()));
+        let target = quarantine_dir.join(relative_path);
+
+        // An earlier quarantined file of the same name must survive
+        if target.exists() {
+            anyhow::bail!("{} already exists", target.display());
+        }
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+
+        if std::fs::rename(input_path, &target).is_err() {
+            // Across file systems the source is only removed once the copy is complete
+            if let Err(e) = std::fs::copy(input_path, &target) {
+                let _ = std::fs::remove_file(&target);
+                return Err(e).with_context(|| format!("Failed to copy to {}", target.display()));
+            }
+            std::fs::remove_file(input_path)
+                .with_context(|| format!("Failed to remove {}", input_path.display()))?;
+        }
+        Ok(target)
+    }
+
+    /// Why the outputs of `outcome` are not safe to replace their source with, if they aren't
+    fn replacement_blocker(
+        &self,
+        input_path: &Path,
+        outcome: &ConversionOutcome,
+    ) -> Option<String> {
+        if outcome.output_paths.is_empty() {
+            return Some("no output was written".to_string());
+        }
+        if outcome
+            .output_paths
+            .iter()
+            .any(|path| is_same_file(path, input_path))
+        {
+            return Some("the output was written over the source".to_string());
+        }
+
+        // Measure what is on disk rather than trusting the encoder's reported size
+        let mut output_size = 0;
+        for path in &outcome.output_paths {
+            match std::fs::metadata(path) {
+                Ok(metadata) => output_size += metadata.len(),
+                Err(e) => return Some(format!("output {} is unreadable: {e}", path.display())),
+            }
+        }
+
+        // An empty source has no meaningful ratio, but its outputs must still decode
+        if outcome.original_size > 0 {
+            let ratio = output_size as f64 / outcome.original_size as f64;
+            if ratio < self.options.replace_min_ratio || ratio > self.options.replace_max_ratio {
+                return Some(format!(
+                    "output is {:.1}% of the source size (allowed {:.1}% to {:.1}%)",
+                    ratio * 100.0,
+                    self.options.replace_min_ratio * 100.0,
+                    self.options.replace_max_ratio * 100.0
+                ));
+            }
+        }
+
+        // A fully decoded output proves the source is no longer needed
+        for path in &outcome.output_paths {
+            if let Err(e) = decode_output(path) {
+                return Some(format!(
+                    "output {} could not be decoded: {e}",
+                    path.display()
+                ));
+            }
+        }
+        None
+    }
+
+    /// Create an empty report for when no files are found
+    fn create_empty_report(
+        &self,
+        start_time_utc: chrono::DateTime<Utc>,
+        start_time: Instant,
         output_dir: PathBuf,
     ) -> ConversionReport {
         let duration = start_time.elapsed();
         let end_time_utc = Utc::now();
 
+        // Files rejected during the scan (e.g. empty ones) are still reported
+        let mut errors = self.stats.get_errors();
+        errors.push("No supported image files found in the specified directory".to_string());
+        self.finish_checkpoint();
+        self.write_log(
+            "WARN",
+            "No supported image files found in the specified directory",
+        );
+
         ConversionReport {
             start_time: start_time_utc,
             end_time: end_time_utc,
@@ -306,23 +2095,942 @@ impl WebpifyCore {
             output_dir,
             total_files: 0,
             processed_files: 0,
-            failed_files: 0,
+            failed_files: self.stats.error_count.load(Ordering::Relaxed),
             skipped_files: 0,
             original_size: 0,
             compressed_size: 0,
-            compression_ratio: 0.0,
+            space_saved_ratio: 0.0,
+            size_ratio: 0.0,
             files_per_second: 0.0,
             bytes_per_second: 0,
             thread_count: rayon::current_num_threads(),
             quality: self.options.quality,
             mode: format!("{:?}", self.options.mode),
             format_stats: std::collections::HashMap::new(),
-            errors: vec!["No supported image files found in the specified directory".to_string()],
+            format_overrides: std::collections::HashMap::new(),
+            errors,
+            quality_searches: std::collections::HashMap::new(),
+            cancelled: self.is_cancelled(),
+            dry_run: self.options.dry_run,
+            compressed_size_unknown: self.options.dry_run && !self.options.dry_run_accurate,
+            renamed_outputs: 0,
+            kept_originals: 0,
+            webp_reencoded: 0,
+            webp_kept: 0,
+            passed_through: 0,
+            error_counts: self.stats.get_error_counts(),
+            skip_reasons: self.stats.get_skip_reasons(),
+            file_limit: None,
+            output_files: Vec::new(),
+            files: Vec::new(),
+            json_lines_stream: self.json_lines_stream(),
         }
     }
 
+    /// Path of the JSON Lines report this run streams per-file lines to
+    fn json_lines_stream(&self) -> Option<PathBuf> {
+        self.report_stream
+            .as_ref()
+            .map(|stream| stream.path().to_path_buf())
+    }
+
     /// Get current conversion statistics
     pub fn get_stats(&self) -> &ConversionStats {
         &self.stats
     }
 }
+
+/// The converter for the global settings plus one per format override
+struct FormatConverters {
+    default: ImageConverter,
+    overrides: HashMap<String, ImageConverter>,
+}
+
+impl FormatConverters {
+    fn new(options: &ConversionOptions) -> Self {
+        let default = ImageConverter::from_options(options);
+        // Clones share the write throttle, so the bandwidth limit still covers all formats
+        let overrides = options
+            .effective_format_overrides()
+            .into_iter()
+            .map(|(extension, (mode, quality))| {
+                let mut converter = default.clone().with_mode(mode).with_quality(quality);
+                // A re-encoded WebP is only worth writing when it got smaller
+                if extension == OUTPUT_FORMAT {
+                    converter = converter.with_keep_smaller(true);
+                }
+                (extension, converter)
+            })
+            .collect();
+        Self { default, overrides }
+    }
+
+    /// The converter for inputs of `format` (a lowercase extension)
+    ///
+    /// Formats without an override use `ramp_quality` instead of the global quality.
+    fn for_format(&self, format: &str, ramp_quality: Option<u8>) -> Cow<'_, ImageConverter> {
+        if let Some(converter) = self.overrides.get(format) {
+            return Cow::Borrowed(converter);
+        }
+        match ramp_quality {
+            Some(quality) => Cow::Owned(self.default.clone().with_quality(quality)),
+            None => Cow::Borrowed(&self.default),
+        }
+    }
+}
+
+/// Convert one file on the current thread, turning a decoder panic into an error
+///
+/// A panic on malformed data fails only this file instead of the whole batch.
+fn convert_catching_panics(
+    converter: &ImageConverter,
+    input_path: &Path,
+    output_path: &Path,
+) -> Result<ConversionOutcome> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        converter.convert_to_webp(input_path, output_path)
+    }))
+    .unwrap_or_else(|_| {
+        Err(ErrorKind::Corrupt.error(format!(
+            "Decoder crashed on {} (the file is probably corrupt or truncated)",
+            input_path.display()
+        )))
+    })
+}
+
+/// Convert one file on its own thread, giving up once `timeout` has passed
+///
+/// Decoding and encoding can't be interrupted, so a timed-out conversion keeps running
+/// in the background until it finishes and may still write its output afterwards. The
+/// thread holds `reservation` until then, so the memory it uses stays counted.
+fn convert_with_timeout(
+    converter: &ImageConverter,
+    input_path: &Path,
+    output_path: &Path,
+    timeout: Duration,
+    reservation: Option<MemoryReservation>,
+) -> Result<ConversionOutcome> {
+    let (sender, receiver) = crossbeam_channel::bounded(1);
+    let converter = converter.clone();
+    let (input, output) = (input_path.to_path_buf(), output_path.to_path_buf());
+    std::thread::Builder::new()
+        .name("webpify-convert".to_string())
+        .spawn(move || {
+            let result = convert_catching_panics(&converter, &input, &output);
+            drop(reservation);
+            // Nobody is listening any more if the file already timed out
+            let _ = sender.send(result);
+        })
+        .context("Failed to start conversion thread")?;
+
+    receiver.recv_timeout(timeout).unwrap_or_else(|_| {
+        Err(ErrorKind::Timeout.error(format!(
+            "Timed out after {}s converting {}",
+            timeout.as_secs_f64(),
+            input_path.display()
+        )))
+    })
+}
+
+/// Decode a written output by its contents, since its extension may be a custom one
+fn decode_output(path: &Path) -> image::ImageResult<image::DynamicImage> {
+    image::ImageReader::open(path)?
+        .with_guessed_format()?
+        .decode()
+}
+
+/// Why `output` fails verification, if it does; `expected` is the size the source
+/// decoded to, fitted into the dimension limits
+fn verification_failure(output: &Path, expected: (u32, u32)) -> Option<String> {
+    match decode_output(output) {
+        Err(e) => Some(format!("the output could not be decoded: {e}")),
+        Ok(img) if (img.width(), img.height()) != expected => Some(format!(
+            "the output is {}x{} but the source is {}x{}",
+            img.width(),
+            img.height(),
+            expected.0,
+            expected.1
+        )),
+        Ok(_) => None,
+    }
+}
+
+/// Compile glob patterns into a single matcher, or `None` if there are no patterns
+fn build_glob_set(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder
+            .add(Glob::new(pattern).with_context(|| format!("Invalid glob pattern: {pattern}"))?);
+    }
+    Ok(Some(
+        builder.build().context("Failed to compile glob patterns")?,
+    ))
+}
+
+/// Whether `path` has the extension of the output format (WebP)
+fn is_webp(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(OUTPUT_FORMAT))
+}
+
+/// Whether `a` and `b` name the same file
+///
+/// Also catches paths differing only in case on case-insensitive file systems, e.g. an
+/// in-place `photo.WEBP` output for a `photo.webp` source.
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    a == b || (a.exists() && a.canonicalize().ok() == b.canonicalize().ok())
+}
+
+/// Per-file report entry for the result of converting `input_path`
+fn file_result(input_path: &Path, result: &Result<ConversionOutcome>) -> FileResult {
+    let (original_bytes, output_bytes, status, error, outputs) = match result {
+        Ok(outcome) => (
+            outcome.original_size,
+            outcome.compressed_size,
+            if outcome.skipped {
+                FileStatus::Skipped
+            } else if outcome.kept_original {
+                FileStatus::KeptOriginal
+            } else if outcome.passed_through {
+                FileStatus::PassedThrough
+            } else {
+                FileStatus::Converted
+            },
+            None,
+            outcome.output_paths.clone(),
+        ),
+        Err(e) => (0, 0, FileStatus::Failed, Some(format!("{e:#}")), Vec::new()),
+    };
+
+    FileResult {
+        path: input_path.to_path_buf(),
+        format: input_path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default(),
+        original_bytes,
+        output_bytes,
+        status,
+        error,
+        outputs,
+    }
+}
+
+/// Whether `output_path` exists and was modified no earlier than `input_path`
+fn is_up_to_date(input_path: &Path, output_path: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|metadata| metadata.modified());
+    match (modified(input_path), modified(output_path)) {
+        (Ok(input_modified), Ok(output_modified)) => output_modified >= input_modified,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CompressionMode, ConversionControl};
+
+    /// Fresh empty directory for one test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("webpify-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Write a small gradient PNG
+    fn write_png(path: &Path, width: u32, height: u32) {
+        image::RgbImage::from_fn(width, height, |x, y| {
+            image::Rgb([(x * 7) as u8, (y * 5) as u8, ((x + y) * 3) as u8])
+        })
+        .save(path)
+        .unwrap();
+    }
+
+    #[test]
+    fn failed_verification_keeps_the_source() {
+        let dir = temp_dir("verify-keeps-source");
+        let source = dir.join("photo.png");
+        write_png(&source, 32, 24);
+        let output = dir.join("photo.webp");
+        std::fs::write(&output, b"RIFF\0\0\0\0WEBPVP8 truncated").unwrap();
+
+        let options = ConversionOptions::new(dir.clone())
+            .with_verify(true)
+            .with_replace_input_mode(ReplaceInputMode::Delete);
+        let core = WebpifyCore::new(options);
+        core.queue_verification(
+            &source,
+            &ConversionOutcome {
+                original_size: 1000,
+                compressed_size: 500,
+                output_paths: vec![output],
+                output_dimensions: vec![(32, 24)],
+                ..Default::default()
+            },
+        );
+        core.verify_outputs();
+
+        assert!(source.exists());
+        assert_eq!(core.stats.error_count.load(Ordering::Relaxed), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn verified_outputs_replace_their_source_after_the_run() {
+        let dir = temp_dir("verify-then-replace");
+        write_png(&dir.join("photo.png"), 32, 24);
+
+        let options = ConversionOptions::new(dir.clone())
+            .with_output_dir(dir.join("out"))
+            .with_verify(true)
+            .with_replace_input_mode(ReplaceInputMode::Delete);
+        let report = WebpifyCore::new(options).run().unwrap();
+
+        assert_eq!(report.processed_files, 1);
+        assert_eq!(report.failed_files, 0);
+        assert!(dir.join("out/photo.webp").exists());
+        assert!(!dir.join("photo.png").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn transient_failures_record_their_retries() {
+        let options = ConversionOptions::new(PathBuf::from("photos")).with_max_retries(2);
+        let core = WebpifyCore::new(options);
+        let input_path = Path::new("photos/locked.png");
+
+        let mut attempts = 0;
+        let result: Result<()> = core.retry_transient(input_path, || {
+            attempts += 1;
+            Err(std::io::Error::from(std::io::ErrorKind::Interrupted).into())
+        });
+        let error = result.unwrap_err();
+        assert_eq!(attempts, 3);
+
+        core.record_failure(input_path, &error);
+        let records = core.stats.get_error_records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].retry_count, 2);
+        assert!(
+            records[0]
+                .error_message
+                .contains("Giving up after 2 retries")
+        );
+        assert_eq!(core.stats.retry_count.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn transient_failure_then_success_is_not_an_error() {
+        let options = ConversionOptions::new(PathBuf::from("photos")).with_max_retries(2);
+        let core = WebpifyCore::new(options);
+
+        let mut attempts = 0;
+        let result = core.retry_transient(Path::new("photos/busy.png"), || {
+            attempts += 1;
+            if attempts == 1 {
+                Err(std::io::Error::from(std::io::ErrorKind::WouldBlock).into())
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result.unwrap(), 2);
+        assert_eq!(core.stats.retry_count.load(Ordering::Relaxed), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn low_priority_runs_leave_the_global_pool_alone() {
+        let dir = temp_dir("low-priority-scoped");
+        write_png(&dir.join("photo.png"), 32, 24);
+
+        // SAFETY: getpriority only reads scheduler state for the calling thread
+        let nice = || rayon::broadcast(|_| unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) });
+        let before = nice();
+        let options = ConversionOptions::new(dir.clone())
+            .with_output_dir(dir.join("out"))
+            .with_low_priority(true);
+        let report = WebpifyCore::new(options).run().unwrap();
+
+        assert_eq!(report.processed_files, 1);
+        assert_eq!(nice(), before);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn flattened_outputs_report_colliding_names() {
+        let input_dir = PathBuf::from("photos");
+        let files = [
+            input_dir.join("2023/logo.png"),
+            input_dir.join("2024/logo.jpg"),
+            input_dir.join("2024/banner.png"),
+        ];
+        let output_dir = Path::new("out");
+
+        let options = ConversionOptions::new(input_dir.clone()).with_preserve_structure(false);
+        let collisions = WebpifyCore::new(options).detect_output_collisions(&files, output_dir);
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].output_path, output_dir.join("logo.webp"));
+        assert_eq!(collisions[0].sources, files[..2]);
+
+        let options = ConversionOptions::new(input_dir).with_preserve_structure(true);
+        let core = WebpifyCore::new(options);
+        assert!(core.detect_output_collisions(&files, output_dir).is_empty());
+    }
+
+    /// Collects log messages, since the tests share one process-wide logger
+    struct CapturedLog(Mutex<Vec<String>>);
+
+    impl log::Log for CapturedLog {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            if let Ok(mut messages) = self.0.lock() {
+                messages.push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static CAPTURED_LOG: CapturedLog = CapturedLog(Mutex::new(Vec::new()));
+
+    /// Route log messages to [`CAPTURED_LOG`]
+    fn capture_log() {
+        if log::set_logger(&CAPTURED_LOG).is_ok() {
+            log::set_max_level(log::LevelFilter::Info);
+        }
+    }
+
+    #[test]
+    fn webp_inputs_are_excluded_unless_reencoding() {
+        capture_log();
+        let dir = temp_dir("webp-excluded");
+        write_png(&dir.join("photo.png"), 32, 24);
+        write_png(&dir.join("logo.png"), 32, 24);
+        std::fs::rename(dir.join("logo.png"), dir.join("logo.webp")).unwrap();
+
+        let core = WebpifyCore::new(ConversionOptions::new(dir.clone()));
+        let mut candidates = Vec::new();
+        core.walk_candidates(|path| {
+            candidates.push(path);
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        assert_eq!(candidates, [dir.join("photo.png")]);
+        assert_eq!(
+            core.stats
+                .get_skip_reasons()
+                .get(&SkipReason::WebpNoReencode),
+            Some(&1)
+        );
+        assert!(
+            CAPTURED_LOG
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|message| message.starts_with("Excluding .webp inputs"))
+        );
+
+        let core = WebpifyCore::new(ConversionOptions::new(dir.clone()).with_reencode_webp(true));
+        let mut candidates = Vec::new();
+        core.walk_candidates(|path| {
+            candidates.push(path);
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        candidates.sort();
+        assert_eq!(candidates, [dir.join("logo.webp"), dir.join("photo.png")]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reencoded_webp_that_grows_keeps_the_source_in_an_explicit_mode() {
+        let dir = temp_dir("webp-grows");
+        // A pixel checkerboard is tiny as lossless WebP and much larger as lossy
+        let checkerboard = image::RgbImage::from_fn(128, 128, |x, y| {
+            image::Rgb([if (x + y) % 2 == 0 { 0 } else { 255 }; 3])
+        });
+        let source = dir.join("pattern.webp");
+        let lossless = webp::Encoder::from_rgb(&checkerboard, 128, 128).encode_lossless();
+        std::fs::write(&source, &*lossless).unwrap();
+
+        for strip_metadata in [false, true] {
+            let options = ConversionOptions::new(dir.clone())
+                .with_output_dir(dir.join("out"))
+                .with_min_size_kb(0)
+                .with_reencode_webp(true)
+                .with_mode(CompressionMode::Lossy)
+                .with_strip_metadata(strip_metadata);
+            let report = WebpifyCore::new(options).run().unwrap();
+
+            assert_eq!(report.webp_kept, 1);
+            assert_eq!(report.webp_reencoded, 0);
+            assert_eq!(
+                std::fs::read(dir.join("out/pattern.webp")).unwrap(),
+                &*lossless
+            );
+            let _ = std::fs::remove_dir_all(dir.join("out"));
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn paused_runs_convert_nothing_until_resumed() {
+        let dir = temp_dir("pause-resume");
+        write_png(&dir.join("photo.png"), 32, 24);
+
+        let control = ConversionControl::new();
+        control.pause();
+        let options = ConversionOptions::new(dir.clone())
+            .with_output_dir(dir.join("out"))
+            .with_control(control.clone());
+        let run = std::thread::spawn(move || WebpifyCore::new(options).run());
+
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(!run.is_finished());
+        assert!(!dir.join("out/photo.webp").exists());
+
+        control.resume();
+        let report = run.join().unwrap().unwrap();
+        assert_eq!(report.processed_files, 1);
+        assert!(dir.join("out/photo.webp").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn event_stream_reports_every_file_and_ends_with_the_report() {
+        let dir = temp_dir("event-stream");
+        for name in ["a.png", "b.png", "c.png"] {
+            write_png(&dir.join(name), 32, 24);
+        }
+        write_png(&dir.join("broken.png"), 96, 64);
+        let broken = std::fs::read(dir.join("broken.png")).unwrap();
+        std::fs::write(dir.join("broken.png"), &broken[..broken.len() / 2]).unwrap();
+        std::fs::create_dir_all(dir.join("out")).unwrap();
+        std::fs::write(dir.join("out/c.webp"), b"existing").unwrap();
+
+        let options = ConversionOptions::new(dir.clone()).with_output_dir(dir.join("out"));
+        let events: Vec<ProgressEvent> = WebpifyCore::new(options).events().collect();
+
+        assert!(matches!(
+            events[..2],
+            [
+                ProgressEvent::ScanProgress { files_found: 4 },
+                ProgressEvent::Started { total: Some(4) }
+            ]
+        ));
+        let Some(ProgressEvent::Finished { report }) = events.last() else {
+            panic!("the stream must end with the report: {:?}", events.last());
+        };
+        assert_eq!(
+            (
+                report.total_files,
+                report.failed_files,
+                report.skipped_files
+            ),
+            (4, 1, 1)
+        );
+
+        // Every file starts once, and its result follows its start
+        let mut started = HashSet::new();
+        let mut results = Vec::new();
+        for event in &events {
+            match event {
+                ProgressEvent::FileStart { path } => assert!(started.insert(path.clone())),
+                ProgressEvent::FileDone { path, .. } => results.push(("done", path)),
+                ProgressEvent::Skip { path } => results.push(("skip", path)),
+                ProgressEvent::Error {
+                    path: Some(path), ..
+                } => results.push(("error", path)),
+                _ => continue,
+            }
+            if let Some((_, path)) = results.last() {
+                assert!(started.contains(*path), "result before start: {path:?}");
+            }
+        }
+        results.sort();
+        assert_eq!(
+            results,
+            [
+                ("done", &dir.join("a.png")),
+                ("done", &dir.join("b.png")),
+                ("error", &dir.join("broken.png")),
+                ("skip", &dir.join("c.png")),
+            ]
+        );
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ProgressEvent::Milestone {
+                percent: 100,
+                processed: 4,
+                total: 4
+            }
+        )));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn corrupt_outputs_block_source_deletion() {
+        let dir = temp_dir("corrupt-output-keeps-source");
+        let source = dir.join("photo.png");
+        write_png(&source, 32, 24);
+        let output = dir.join("photo.webp");
+        // Within the allowed size ratio, so only decoding can catch it
+        std::fs::write(&output, b"RIFF\x40\0\0\0WEBPVP8L truncated bitstream").unwrap();
+
+        let options =
+            ConversionOptions::new(dir.clone()).with_replace_input_mode(ReplaceInputMode::Delete);
+        let core = WebpifyCore::new(options);
+        let outcome = ConversionOutcome {
+            original_size: std::fs::metadata(&source).unwrap().len(),
+            output_paths: vec![output.clone()],
+            ..Default::default()
+        };
+        let reason = core.replacement_blocker(&source, &outcome).unwrap();
+        assert!(reason.contains("could not be decoded"), "{reason}");
+
+        core.handle_input_replacement(&source, &outcome).unwrap();
+        assert!(source.exists());
+        let records = core.stats.get_error_records();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].kind.is_none());
+        assert!(
+            records[0]
+                .error_message
+                .starts_with("Source kept instead of being replaced")
+        );
+
+        // The same source goes once its output decodes
+        image::open(&source).unwrap().save(&output).unwrap();
+        core.handle_input_replacement(&source, &outcome).unwrap();
+        assert!(!source.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn largest_files_start_first_unless_sorted() {
+        let dir = temp_dir("largest-first");
+        write_png(&dir.join("small.png"), 32, 24);
+        write_png(&dir.join("large.png"), 256, 192);
+        write_png(&dir.join("medium.png"), 96, 64);
+
+        // A single worker starts files in exactly the processing order
+        let start_order = |options: ConversionOptions| -> Vec<PathBuf> {
+            WebpifyCore::new(options.with_threads(1).with_output_dir(dir.join("out")))
+                .events()
+                .filter_map(|event| match event {
+                    ProgressEvent::FileStart { path } => path.file_name().map(PathBuf::from),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let options = ConversionOptions::new(dir.clone()).with_overwrite(true);
+        assert_eq!(
+            start_order(options.clone()),
+            ["large.png", "medium.png", "small.png"].map(PathBuf::from)
+        );
+        assert_eq!(
+            start_order(options.with_sort_order(SortOrder::SizeAscending)),
+            ["small.png", "medium.png", "large.png"].map(PathBuf::from)
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Wall-clock benchmark of largest-first scheduling on a mixed batch
+    ///
+    /// Five files on two workers, with sleeps in the output path resolver standing in for
+    /// encoding time: 400 ms for the large file and 100 ms for each of four small ones.
+    /// Smallest first takes about 600 ms, since the large file runs alone at the end;
+    /// largest first takes about 400 ms, a third less. Ignored because it depends on
+    /// timing; run it with `cargo test largest_first -- --ignored`.
+    #[test]
+    #[ignore = "timing benchmark"]
+    fn largest_first_shortens_the_wall_clock_of_a_mixed_batch() {
+        /// Stands in for encoding time: conversions of the large file take four times as
+        /// long. Sleeping overlaps on any number of cores, unlike real encoding.
+        #[derive(Debug)]
+        struct SlowResolver {
+            output_dir: PathBuf,
+            seen: Mutex<HashSet<PathBuf>>,
+        }
+
+        impl crate::output_path::OutputPathResolver for SlowResolver {
+            fn resolve(&self, input: &Path, _input_root: &Path) -> PathBuf {
+                // The first call per file comes from the collision check during the scan
+                if !self.seen.lock().unwrap().insert(input.to_path_buf()) {
+                    let large = input.file_stem().is_some_and(|stem| stem == "large");
+                    std::thread::sleep(Duration::from_millis(if large { 400 } else { 100 }));
+                }
+                self.output_dir
+                    .join(input.file_name().unwrap())
+                    .with_extension("webp")
+            }
+        }
+
+        let dir = temp_dir("largest-first-timing");
+        write_png(&dir.join("large.png"), 256, 192);
+        for name in ["a", "b", "c", "d"] {
+            write_png(&dir.join(format!("{name}.png")), 32, 24);
+        }
+
+        // Two workers: the large file started last runs alone after the small ones
+        let wall_clock = |options: ConversionOptions, out: &str| -> Duration {
+            let started = Instant::now();
+            let report = WebpifyCore::new(options.with_threads(2).with_output_path_resolver(
+                SlowResolver {
+                    output_dir: dir.join(out),
+                    seen: Mutex::new(HashSet::new()),
+                },
+            ))
+            .run()
+            .unwrap();
+            assert_eq!(report.processed_files, 5);
+            started.elapsed()
+        };
+
+        let options = ConversionOptions::new(dir.clone());
+        let smallest_first = wall_clock(
+            options.clone().with_sort_order(SortOrder::SizeAscending),
+            "ascending",
+        );
+        let largest_first = wall_clock(options, "largest");
+
+        assert!(
+            largest_first + Duration::from_millis(100) < smallest_first,
+            "largest first took {largest_first:?}, smallest first {smallest_first:?}"
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn mixed_relative_and_absolute_input_lists_stay_in_the_output_dir() {
+        // Relative to the working directory, which cargo sets to the crate root
+        let dir =
+            PathBuf::from("target").join(format!("webpify-mixed-list-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("rel")).unwrap();
+        std::fs::create_dir_all(dir.join("absd")).unwrap();
+        write_png(&dir.join("rel/a.png"), 32, 24);
+        write_png(&dir.join("absd/b.png"), 32, 24);
+        let absolute_dir = std::path::absolute(&dir).unwrap();
+        let files = vec![dir.join("rel/a.png"), absolute_dir.join("absd/b.png")];
+        let out = absolute_dir.join("out");
+
+        let options = ConversionOptions::new(absolute_dir.clone())
+            .with_output_dir(out.clone())
+            .with_input_files(files.clone());
+        let report = WebpifyCore::new(options).run().unwrap();
+        assert_eq!(report.failed_files, 0);
+        assert!(out.join("rel/a.webp").exists());
+        assert!(out.join("absd/b.webp").exists());
+        assert!(!absolute_dir.join("absd/b.webp").exists());
+
+        // A file outside an explicit base directory fails the run before converting
+        std::fs::remove_dir_all(&out).unwrap();
+        let options = ConversionOptions::new(absolute_dir.clone())
+            .with_output_dir(out.clone())
+            .with_base_dir(dir.join("rel"))
+            .with_input_files(files);
+        let error = WebpifyCore::new(options).run().unwrap_err();
+        assert!(format!("{error:#}").contains("absd/b.png"), "{error:#}");
+        assert!(!out.join("a.webp").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn manifest_tracks_sources_skipped_for_an_existing_output() {
+        let dir = temp_dir("manifest-existing-output");
+        let source = dir.join("photo.png");
+        write_png(&source, 32, 24);
+        let output = dir.join("out/photo.webp");
+        std::fs::create_dir_all(dir.join("out")).unwrap();
+        std::fs::write(&output, b"from an earlier tool").unwrap();
+        let options = ConversionOptions::new(dir.clone())
+            .with_output_dir(dir.join("out"))
+            .with_manifest(true);
+
+        let report = WebpifyCore::new(options.clone()).run().unwrap();
+        assert_eq!(report.skipped_files, 1);
+        assert_eq!(std::fs::read(&output).unwrap(), b"from an earlier tool");
+
+        // The changed source no longer matches the hash recorded when it was skipped
+        write_png(&source, 48, 24);
+        let report = WebpifyCore::new(options).run().unwrap();
+        assert_eq!(report.skipped_files, 0);
+        assert_eq!(image::open(&output).unwrap().width(), 48);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn manifest_runs_with_overwrite_convert_unchanged_sources_again() {
+        let dir = temp_dir("manifest-overwrite");
+        write_png(&dir.join("photo.png"), 32, 24);
+        let output = dir.join("out/photo.webp");
+        let options = ConversionOptions::new(dir.clone())
+            .with_output_dir(dir.join("out"))
+            .with_manifest(true);
+
+        let report = WebpifyCore::new(options.clone()).run().unwrap();
+        assert_eq!(report.processed_files, 1);
+        std::fs::write(&output, b"stale output").unwrap();
+
+        // The manifest entry still matches, but overwrite asks for a fresh conversion
+        let report = WebpifyCore::new(options.with_overwrite(true))
+            .run()
+            .unwrap();
+        assert_eq!(report.skipped_files, 0);
+        assert_eq!(image::open(&output).unwrap().width(), 32);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn overlapping_input_dirs_convert_each_file_once() {
+        let dir = temp_dir("overlapping-roots");
+        std::fs::create_dir_all(dir.join("a/sub")).unwrap();
+        write_png(&dir.join("a/top.png"), 32, 24);
+        write_png(&dir.join("a/sub/nested.png"), 32, 24);
+
+        for input_dirs in [
+            vec![dir.join("a"), dir.join("a/sub")],
+            vec![dir.join("a"), dir.join("a")],
+        ] {
+            let out = dir.join("out");
+            let _ = std::fs::remove_dir_all(&out);
+            let options = ConversionOptions::new(PathBuf::new())
+                .with_input_dirs(input_dirs)
+                .with_output_dir(out.clone());
+            let report = WebpifyCore::new(options).run().unwrap();
+            assert_eq!(report.total_files, 2);
+            assert_eq!(report.processed_files, 2);
+            assert_eq!(report.failed_files, 0);
+        }
+
+        // A directory given twice gets a single output subdirectory
+        assert!(dir.join("out/a/top.webp").exists());
+        assert!(!dir.join("out/a_2").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_panicking_run_does_not_hang_the_callback_loop() {
+        #[derive(Debug)]
+        struct PanickingResolver;
+
+        impl crate::output_path::OutputPathResolver for PanickingResolver {
+            fn resolve(&self, _input: &Path, _input_root: &Path) -> PathBuf {
+                panic!("resolver failed");
+            }
+        }
+
+        let dir = temp_dir("callback-panic");
+        write_png(&dir.join("photo.png"), 32, 24);
+        let options = ConversionOptions::new(dir.clone())
+            .with_output_dir(dir.join("out"))
+            .with_output_path_resolver(PanickingResolver);
+
+        let (done, finished) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                WebpifyCore::new(options).run_with_callback(|_| {})
+            }));
+            let _ = done.send(result.is_err());
+        });
+
+        let panicked = finished
+            .recv_timeout(std::time::Duration::from_secs(30))
+            .expect("run_with_callback hung after the run panicked");
+        assert!(panicked);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn outputs_of_empty_sources_are_still_decoded_before_replacing() {
+        let dir = temp_dir("replace-empty-source");
+        let source = dir.join("empty.png");
+        std::fs::write(&source, b"").unwrap();
+        let output = dir.join("empty.webp");
+        std::fs::write(&output, b"RIFF\0\0\0\0WEBPVP8 truncated").unwrap();
+
+        let core = WebpifyCore::new(ConversionOptions::new(dir.clone()));
+        let blocker = core.replacement_blocker(
+            &source,
+            &ConversionOutcome {
+                original_size: 0,
+                output_paths: vec![output],
+                ..Default::default()
+            },
+        );
+
+        assert!(blocker.is_some_and(|reason| reason.contains("could not be decoded")));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn dropping_the_event_stream_cancels_the_run() {
+        let dir = temp_dir("events-dropped");
+        for index in 0..4 {
+            write_png(&dir.join(format!("photo{index}.png")), 32, 24);
+        }
+        let control = ConversionControl::new();
+        let options = ConversionOptions::new(dir.clone())
+            .with_output_dir(dir.join("out"))
+            .with_control(control.clone());
+
+        let mut events = WebpifyCore::new(options).events();
+        assert!(events.next().is_some());
+        drop(events);
+
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while !control.is_cancelled() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(control.is_cancelled());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn streaming_walks_stop_at_the_file_limit() {
+        let dir = temp_dir("walk-stops-at-limit");
+        for name in ["a", "b", "c", "d"] {
+            write_png(&dir.join(format!("{name}.png")), 32, 24);
+        }
+
+        let core = WebpifyCore::new(
+            ConversionOptions::new(dir.clone())
+                .with_max_files(2)
+                .unwrap(),
+        );
+        let mut visited = 0;
+        let found = core.walk_input_files(|_, _| visited += 1).unwrap();
+        assert_eq!((found, visited), (2, 2));
+        assert!(core.file_limit_reached.load(Ordering::Relaxed));
+
+        // Files past the limit that are filtered out don't count as left out
+        write_png(&dir.join("e.png"), 8, 8);
+        let options = ConversionOptions::new(dir.clone())
+            .with_output_dir(dir.join("out"))
+            .with_prescan(false)
+            .with_min_dimensions(32, 24);
+        let report = WebpifyCore::new(options.clone().with_max_files(4).unwrap())
+            .run()
+            .unwrap();
+        assert_eq!(report.processed_files, 4);
+        assert_eq!(report.file_limit, None);
+        let _ = std::fs::remove_dir_all(dir.join("out"));
+        let report = WebpifyCore::new(options.with_max_files(3).unwrap())
+            .run()
+            .unwrap();
+        assert_eq!(report.processed_files, 3);
+        assert_eq!(report.file_limit, Some(3));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -4,10 +4,15 @@ use eframe::egui;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
+use rayon::iter::{ParallelBridge, ParallelIterator};
+use webpify::config::{
+    CompressionConfig, DEFAULT_FORMATS, FilteringConfig, GeneralConfig, OUTPUT_FORMAT, OutputConfig,
+};
 use webpify::{
-    CompressionMode, ConversionOptions, ConversionReport, ProgressReporter, ReplaceInputMode,
-    ReportFormat, WebpifyCore,
+    CompressionMode, Config, ConversionControl, ConversionOptions, ConversionReport,
+    OutputCollision, ProgressReporter, ReplaceInputMode, ReportFormat, ReportOptions, WebpifyCore,
 };
 
 /// Icon definitions optimized for Windows 11 with semantic meaning
@@ -27,6 +32,7 @@ impl Icons {
     // Action icons - clear and intuitive
     const START: &'static str = "▶️"; // Play button for start
     const STOP: &'static str = "⏹️"; // Stop button
+    const PAUSE: &'static str = "⏸️"; // Pause button
     const CLEAR: &'static str = "🗑️"; // Trash for clear
 
     // Status icons - universally understood
@@ -60,6 +66,19 @@ enum StatusType {
     Info,
 }
 
+/// Number of files listed in the preview window (the largest ones)
+const PREVIEW_LIST_LIMIT: usize = 100;
+/// Most recent conversion log lines kept; older ones are dropped
+const CONVERSION_LOG_LIMIT: usize = 1000;
+
+/// Totals over every file found by the preview scan
+#[derive(Debug, Clone, Default)]
+struct PreviewSummary {
+    files: usize,
+    total_size: u64,
+    estimated_size: u64,
+}
+
 /// Information about a file to be converted (for preview)
 #[derive(Debug, Clone)]
 struct PreviewFileInfo {
@@ -74,23 +93,38 @@ pub struct WebpifyGuiApp {
     // UI State
     current_tab: Tab,
     is_converting: bool,
+    is_paused: bool,
+    is_stopping: bool,
+    conversion_control: Option<ConversionControl>,
     progress: f32,
     total_files: usize,
     processed_files: usize,
     failed_files: usize,
+    bytes_per_second: f64,
+    files_per_second: f64,
+    eta: Option<Duration>,
+    processed_bytes: u64,
+    total_bytes: u64,
+    // Weight the progress bar by file size instead of file count
+    byte_weighted_progress: bool,
 
     // Modal dialogs
     show_preview_window: bool,
     show_help_window: bool,
     preview_files: Vec<PreviewFileInfo>,
+    preview_summary: PreviewSummary,
+    preview_scan: Option<crossbeam_channel::Receiver<PreviewFileInfo>>,
 
     // Input/Output Configuration
     input_dir: String,
     output_dir: String,
     output_dir_auto: bool,
+    // Why the last drop onto the window was rejected
+    drop_status: Option<String>,
 
     // Basic Conversion Settings
     quality: u8,
+    effort: u8,
     mode: CompressionMode,
     threads: String,
     threads_auto: bool,
@@ -99,20 +133,27 @@ pub struct WebpifyGuiApp {
     formats: String,
     overwrite: bool,
     preserve_structure: bool,
+    in_place: bool,
     max_size: String,
     min_size: u64,
     prescan: bool,
     reencode_webp: bool,
+    preserve_metadata: bool,
+    preserve_timestamps: bool,
+    animation: bool,
 
     // Advanced Settings
     replace_input: ReplaceInputMode,
     dry_run: bool,
+    dry_run_accurate: bool,
+    low_priority: bool,
     verbose: bool,
     quiet: bool,
 
     // Report Settings
     generate_report: bool,
     report_format: ReportFormat,
+    report_timestamp: bool,
 
     // Configuration Management
     config_file: String,
@@ -120,6 +161,8 @@ pub struct WebpifyGuiApp {
 
     // Results
     last_report: Option<ConversionReport>,
+    // Most recently written report file
+    last_report_path: Option<PathBuf>,
     error_message: Option<String>,
     conversion_log: Vec<String>,
 
@@ -142,45 +185,65 @@ impl Default for WebpifyGuiApp {
             // UI State
             current_tab: Tab::Input,
             is_converting: false,
+            is_paused: false,
+            is_stopping: false,
+            conversion_control: None,
             progress: 0.0,
             total_files: 0,
             processed_files: 0,
             failed_files: 0,
+            bytes_per_second: 0.0,
+            files_per_second: 0.0,
+            eta: None,
+            processed_bytes: 0,
+            total_bytes: 0,
+            byte_weighted_progress: false,
 
             // Modal dialogs
             show_preview_window: false,
             show_help_window: false,
             preview_files: Vec::new(),
+            preview_summary: PreviewSummary::default(),
+            preview_scan: None,
 
             // Input/Output Configuration
             input_dir: String::new(),
             output_dir: String::new(),
             output_dir_auto: true,
+            drop_status: None,
 
             // Basic Conversion Settings
             quality: 80,
+            effort: webpify::converter::DEFAULT_EFFORT,
             mode: CompressionMode::Lossless,
             threads: num_cpus::get().to_string(),
             threads_auto: true,
 
             // File Processing Settings
-            formats: "jpg,jpeg,png,gif,bmp,tiff,webp".to_string(),
+            formats: DEFAULT_FORMATS.join(","),
             overwrite: false,
             preserve_structure: true,
+            in_place: false,
             max_size: String::new(),
             min_size: 1,
             prescan: true,
             reencode_webp: false,
+            preserve_metadata: false,
+            preserve_timestamps: false,
+            animation: true,
 
             // Advanced Settings
             replace_input: ReplaceInputMode::Off,
             dry_run: false,
+            dry_run_accurate: false,
+            low_priority: false,
             verbose: false,
             quiet: false,
 
             // Report Settings
             generate_report: false,
             report_format: ReportFormat::Json,
+            report_timestamp: false,
 
             // Configuration Management
             config_file: String::new(),
@@ -188,6 +251,7 @@ impl Default for WebpifyGuiApp {
 
             // Results
             last_report: None,
+            last_report_path: None,
             error_message: None,
             conversion_log: Vec::new(),
 
@@ -200,17 +264,27 @@ impl Default for WebpifyGuiApp {
 impl eframe::App for WebpifyGuiApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Update progress from background thread
-        if let Ok(reporter) = self.progress_reporter.lock() {
+        if let Ok(mut reporter) = self.progress_reporter.lock() {
             self.total_files = reporter.total_files;
             self.processed_files = reporter.processed_files;
             self.failed_files = reporter.failed_files;
-
-            if self.total_files > 0 {
+            self.bytes_per_second = reporter.bytes_per_second;
+            self.files_per_second = reporter.files_per_second;
+            self.eta = reporter.eta;
+            self.processed_bytes = reporter.processed_bytes;
+            self.total_bytes = reporter.total_bytes;
+
+            if self.byte_weighted_progress && self.total_bytes > 0 {
+                self.progress = self.processed_bytes as f32 / self.total_bytes as f32;
+            } else if self.total_files > 0 {
                 self.progress = self.processed_files as f32 / self.total_files as f32;
             }
 
             if reporter.finished {
                 self.is_converting = false;
+                self.is_paused = false;
+                self.is_stopping = false;
+                self.conversion_control = None;
                 if let Some(report) = &reporter.report {
                     self.last_report = Some(report.clone());
                     // Auto-switch to results tab when conversion finishes
@@ -218,17 +292,25 @@ impl eframe::App for WebpifyGuiApp {
                 }
                 if let Some(error) = &reporter.error {
                     self.error_message = Some(error.clone());
+                    self.current_tab = Tab::Results;
                 }
             }
 
-            // Collect conversion logs
-            for log in &reporter.logs {
-                if !self.conversion_log.contains(log) {
-                    self.conversion_log.push(log.clone());
-                }
+            if let Some(path) = reporter.report_path.take() {
+                self.last_report_path = Some(path);
             }
+
+            // Collect conversion logs
+            self.conversion_log.append(&mut reporter.logs);
+            let excess = self
+                .conversion_log
+                .len()
+                .saturating_sub(CONVERSION_LOG_LIMIT);
+            self.conversion_log.drain(..excess);
         }
 
+        self.handle_dropped_files(ctx);
+
         // Enhanced top panel with step indicator
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.vertical(|ui| {
@@ -236,7 +318,15 @@ impl eframe::App for WebpifyGuiApp {
                 ui.horizontal(|ui| {
                     ui.heading("Webpify - Batch WebP Converter");
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        if self.is_converting {
+                        if self.is_stopping {
+                            ui.spinner();
+                            ui.label("Stopping - finishing files in progress...");
+                        } else if self.is_converting && self.is_paused {
+                            ui.label(
+                                egui::RichText::new("⏸️ Paused - no new files will start")
+                                    .color(egui::Color32::ORANGE),
+                            );
+                        } else if self.is_converting {
                             ui.spinner();
                             ui.label("Converting...");
                         }
@@ -423,8 +513,35 @@ impl eframe::App for WebpifyGuiApp {
                     .secondary_button(ui, &Icons::with_text(Icons::STOP, "Stop"))
                     .on_hover_text("Stop the current conversion process");
 
-                if stop_btn.clicked() && self.is_converting {
-                    self.is_converting = false;
+                if stop_btn.clicked() && self.is_converting && !self.is_stopping {
+                    // Workers finish the files in progress and skip the rest; the run
+                    // reports back through the progress reporter once they are done
+                    if let Some(control) = &self.conversion_control {
+                        control.cancel();
+                    }
+                    self.is_paused = false;
+                    self.is_stopping = true;
+                }
+
+                let (pause_icon, pause_text, pause_hover) = if self.is_paused {
+                    (
+                        Icons::START,
+                        "Resume",
+                        "Continue converting the remaining files",
+                    )
+                } else {
+                    (
+                        Icons::PAUSE,
+                        "Pause",
+                        "Finish the files in progress and hold off on starting new ones",
+                    )
+                };
+                let pause_btn = self
+                    .secondary_button(ui, &Icons::with_text(pause_icon, pause_text))
+                    .on_hover_text(pause_hover);
+
+                if pause_btn.clicked() && self.is_converting && !self.is_stopping {
+                    self.set_paused(!self.is_paused);
                 }
 
                 let clear_btn = self
@@ -470,19 +587,106 @@ impl eframe::App for WebpifyGuiApp {
             });
         });
 
-        // Request repaint if converting
-        if self.is_converting {
+        self.poll_preview_scan();
+
+        // Request repaint if converting or scanning
+        if self.is_converting || self.preview_scan.is_some() {
             ctx.request_repaint();
         }
 
         // Show modal windows
         self.show_preview_modal(ctx);
         self.show_help_modal(ctx);
+
+        self.show_drop_hint(ctx);
     }
 }
 
 impl WebpifyGuiApp {
     /// Create a secondary action button
+    fn set_paused(&mut self, paused: bool) {
+        if let Some(control) = &self.conversion_control {
+            if paused {
+                control.pause();
+            } else {
+                control.resume();
+            }
+        }
+        self.is_paused = paused && self.conversion_control.is_some();
+    }
+
+    /// Use `path` as the input directory, deriving the output directory if it is automatic
+    fn set_input_dir(&mut self, path: PathBuf) {
+        self.input_dir = path.display().to_string();
+        self.drop_status = None;
+
+        // Auto-set output directory if enabled
+        if self.output_dir_auto {
+            let mut output_path = path;
+            output_path.push("webp_output");
+            self.output_dir = output_path.display().to_string();
+        }
+    }
+
+    /// Take a folder dropped onto the window as the input directory
+    ///
+    /// A dropped file selects the folder it is in. Only the first item of a multi-item
+    /// drop is used.
+    fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let dropped = ctx.input(|i| i.raw.dropped_files.first().map(|file| file.path.clone()));
+        let Some(path) = dropped else {
+            return;
+        };
+
+        if self.is_converting {
+            self.drop_status =
+                Some("Cannot change the input directory while converting".to_string());
+            return;
+        }
+        match path {
+            Some(path) if path.is_dir() => self.set_input_dir(path),
+            Some(path) if path.is_file() => match path.parent() {
+                Some(parent) => self.set_input_dir(parent.to_path_buf()),
+                None => self.drop_status = Some(format!("{} has no parent folder", path.display())),
+            },
+            Some(path) => {
+                self.drop_status = Some(format!(
+                    "{} is not a folder or file on this computer",
+                    path.display()
+                ));
+            }
+            None => {
+                self.drop_status = Some("Only folders and files can be dropped here".to_string());
+            }
+        }
+    }
+
+    /// Dim the window with a drop hint while files are dragged over it
+    fn show_drop_hint(&self, ctx: &egui::Context) {
+        if ctx.input(|i| i.raw.hovered_files.is_empty()) {
+            return;
+        }
+
+        let text = if self.is_converting {
+            "Cannot change the input directory while converting"
+        } else {
+            "📂 Drop a folder to use it as the input directory"
+        };
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            egui::Id::new("drop_hint"),
+        ));
+        let screen_rect = ctx.screen_rect();
+        painter.rect_filled(screen_rect, 0.0, egui::Color32::from_black_alpha(192));
+        painter.text(
+            screen_rect.center(),
+            egui::Align2::CENTER_CENTER,
+            text,
+            egui::FontId::proportional(20.0),
+            egui::Color32::WHITE,
+        );
+    }
+
     fn secondary_button(&self, ui: &mut egui::Ui, text: &str) -> egui::Response {
         ui.add_sized([100.0, 32.0], egui::Button::new(text))
     }
@@ -534,18 +738,20 @@ impl WebpifyGuiApp {
                                     .set_title("Select Input Directory")
                                     .pick_folder()
                                 {
-                                    self.input_dir = path.display().to_string();
-
-                                    // Auto-set output directory if enabled
-                                    if self.output_dir_auto {
-                                        let mut output_path = path;
-                                        output_path.push("webp_output");
-                                        self.output_dir = output_path.display().to_string();
-                                    }
+                                    self.set_input_dir(path);
                                 }
                             }
                         });
 
+                        if let Some(status) = &self.drop_status {
+                            ui.add_space(5.0);
+                            ui.label(
+                                egui::RichText::new(format!("⚠️ {status}"))
+                                    .color(egui::Color32::ORANGE)
+                                    .size(12.0),
+                            );
+                        }
+
                         if !self.input_dir.is_empty() {
                             ui.add_space(5.0);
                             let path = PathBuf::from(&self.input_dir);
@@ -593,7 +799,7 @@ impl WebpifyGuiApp {
                         ui.add(
                             egui::TextEdit::multiline(&mut self.formats)
                                 .desired_rows(2)
-                                .hint_text("jpg,jpeg,png,gif,bmp,tiff,webp"),
+                                .hint_text(DEFAULT_FORMATS.join(",")),
                         );
 
                         ui.add_space(8.0);
@@ -602,12 +808,29 @@ impl WebpifyGuiApp {
                                 self.formats = "jpg,jpeg,png".to_string();
                             }
                             if ui.small_button("🖼️ Common").clicked() {
-                                self.formats = "jpg,jpeg,png,gif,bmp,tiff".to_string();
+                                self.formats = DEFAULT_FORMATS
+                                    .iter()
+                                    .filter(|format| **format != OUTPUT_FORMAT)
+                                    .copied()
+                                    .collect::<Vec<_>>()
+                                    .join(",");
                             }
                             if ui.small_button("🌐 All").clicked() {
-                                self.formats = "jpg,jpeg,png,gif,bmp,tiff,webp".to_string();
+                                self.formats = "all".to_string();
                             }
                         });
+
+                        ui.add_space(5.0);
+                        let resolved = self.resolved_formats();
+                        ui.label(
+                            egui::RichText::new(if resolved.is_empty() {
+                                "Converts: no formats".to_string()
+                            } else {
+                                format!("Converts: {}", resolved.join(", "))
+                            })
+                            .small()
+                            .weak(),
+                        );
                     });
                 });
             });
@@ -795,6 +1018,11 @@ impl WebpifyGuiApp {
 
                         ui.add_space(10.0);
 
+                        ui.add(egui::Slider::new(&mut self.effort, 0..=6).text("Effort"))
+                            .on_hover_text("Higher effort is slower but produces smaller files");
+
+                        ui.add_space(10.0);
+
                         // Compression mode with visual feedback
                         ui.label("Compression Mode:");
                         egui::ComboBox::from_id_salt("compression_mode")
@@ -802,6 +1030,8 @@ impl WebpifyGuiApp {
                                 CompressionMode::Lossless => "🏆 Lossless (Perfect Quality)",
                                 CompressionMode::Lossy => "📦 Lossy (Smaller Size)",
                                 CompressionMode::Auto => "🤖 Auto (Smart Choice)",
+                                CompressionMode::NearLossless => "🎨 Near-Lossless (Graphics)",
+                                CompressionMode::Smallest => "📉 Smallest (Try Both)",
                             })
                             .show_ui(ui, |ui| {
                                 ui.selectable_value(
@@ -819,6 +1049,16 @@ impl WebpifyGuiApp {
                                     CompressionMode::Auto,
                                     "🤖 Auto (Smart Choice)",
                                 );
+                                ui.selectable_value(
+                                    &mut self.mode,
+                                    CompressionMode::NearLossless,
+                                    "🎨 Near-Lossless (Graphics)",
+                                );
+                                ui.selectable_value(
+                                    &mut self.mode,
+                                    CompressionMode::Smallest,
+                                    "📉 Smallest (Try Both)",
+                                );
                             });
 
                         // Mode explanation with better styling
@@ -827,6 +1067,12 @@ impl WebpifyGuiApp {
                             CompressionMode::Lossless => "Perfect quality, larger files",
                             CompressionMode::Lossy => "Good quality, smaller files",
                             CompressionMode::Auto => "Automatically chooses best mode per image",
+                            CompressionMode::NearLossless => {
+                                "Lossless with slight pixel tweaks; quality sets the level (100 = lossless)"
+                            }
+                            CompressionMode::Smallest => {
+                                "Encodes lossless and lossy, keeps the smaller file (about twice as slow)"
+                            }
                         };
                         ui.label(
                             egui::RichText::new(mode_desc)
@@ -854,7 +1100,20 @@ impl WebpifyGuiApp {
                             &mut self.preserve_structure,
                             "🗂️ Preserve directory structure",
                         );
+                        ui.checkbox(&mut self.in_place, "📍 Write WebP files next to the sources");
                         ui.checkbox(&mut self.reencode_webp, "🔄 Re-encode existing WebP files");
+                        ui.checkbox(
+                            &mut self.preserve_metadata,
+                            "🏷️ Keep color profiles and EXIF data",
+                        );
+                        ui.checkbox(
+                            &mut self.preserve_timestamps,
+                            "🕒 Keep the modification dates of the sources",
+                        );
+                        ui.checkbox(
+                            &mut self.animation,
+                            "🎞️ Keep animations (GIF/APNG/HEIC sequences to animated WebP)",
+                        );
 
                         ui.add_space(10.0);
 
@@ -1061,6 +1320,10 @@ impl WebpifyGuiApp {
             );
 
             if self.dry_run {
+                ui.checkbox(
+                    &mut self.dry_run_accurate,
+                    "Exact size estimates (encode in memory, slower)",
+                );
                 ui.colored_label(
                     egui::Color32::BLUE,
                     "ℹ️ Dry run mode: No files will be modified",
@@ -1070,6 +1333,22 @@ impl WebpifyGuiApp {
 
         ui.add_space(15.0);
 
+        // System Responsiveness
+        ui.group(|ui| {
+            ui.label("🐢 System Responsiveness");
+            ui.add_space(5.0);
+
+            ui.checkbox(
+                &mut self.low_priority,
+                "Low priority mode (keep other applications smooth while converting)",
+            )
+            .on_hover_text(
+                "On macOS the lower priority applies to the whole app and lasts until it is restarted",
+            );
+        });
+
+        ui.add_space(15.0);
+
         // Logging & Output
         ui.group(|ui| {
             ui.label("📝 Logging & Output");
@@ -1104,6 +1383,9 @@ impl WebpifyGuiApp {
                             ReportFormat::Json => "JSON",
                             ReportFormat::Csv => "CSV",
                             ReportFormat::Html => "HTML",
+                            ReportFormat::JsonLines => "JSON Lines",
+                            ReportFormat::Xml => "XML",
+                            ReportFormat::Text => "Text",
                         })
                         .show_ui(ui, |ui| {
                             ui.selectable_value(
@@ -1117,8 +1399,23 @@ impl WebpifyGuiApp {
                                 ReportFormat::Html,
                                 "HTML",
                             );
+                            ui.selectable_value(
+                                &mut self.report_format,
+                                ReportFormat::JsonLines,
+                                "JSON Lines",
+                            );
+                            ui.selectable_value(&mut self.report_format, ReportFormat::Xml, "XML");
+                            ui.selectable_value(
+                                &mut self.report_format,
+                                ReportFormat::Text,
+                                "Text",
+                            );
                         });
                 });
+                ui.checkbox(
+                    &mut self.report_timestamp,
+                    "Add the start time to the file name (keeps earlier reports)",
+                );
             }
         });
 
@@ -1153,6 +1450,24 @@ impl WebpifyGuiApp {
                         .hint_text("Configuration profile name"),
                 );
             });
+
+            ui.add_space(5.0);
+            ui.horizontal(|ui| {
+                if ui
+                    .button("📂 Load Config")
+                    .on_hover_text("Replace the settings with those in the config file")
+                    .clicked()
+                {
+                    self.load_config();
+                }
+                if ui
+                    .button("💾 Save Config")
+                    .on_hover_text("Save the current settings as a TOML config file")
+                    .clicked()
+                {
+                    self.save_config();
+                }
+            });
         });
     }
 
@@ -1173,10 +1488,24 @@ impl WebpifyGuiApp {
                     });
                 }
 
-                ui.add(egui::ProgressBar::new(self.progress).text(format!(
-                    "{}/{} files processed",
-                    self.processed_files, self.total_files
-                )));
+                let progress_text = if self.byte_weighted_progress && self.total_bytes > 0 {
+                    format!(
+                        "{} / {} processed",
+                        humansize::format_size(self.processed_bytes, humansize::DECIMAL),
+                        humansize::format_size(self.total_bytes, humansize::DECIMAL)
+                    )
+                } else {
+                    format!(
+                        "{}/{} files processed",
+                        self.processed_files, self.total_files
+                    )
+                };
+                ui.add(egui::ProgressBar::new(self.progress).text(progress_text));
+                ui.checkbox(
+                    &mut self.byte_weighted_progress,
+                    "Weight progress by file size",
+                )
+                .on_hover_text("Large files move the bar more than small ones");
 
                 ui.horizontal(|ui| {
                     ui.label(format!("✅ Processed: {}", self.processed_files));
@@ -1192,7 +1521,29 @@ impl WebpifyGuiApp {
                     if remaining > 0 {
                         ui.label(format!("⏳ Remaining: {}", remaining));
                     }
+                    // Unknown until the first file finishes, and while the scan is still
+                    // running without a prescan
+                    if self.is_converting {
+                        let eta = match self.eta {
+                            Some(eta) => webpify::format_duration(eta),
+                            None => "estimating…".to_string(),
+                        };
+                        ui.label(format!("⏱ ETA: {eta}"));
+                    }
                 });
+
+                if self.files_per_second > 0.0 {
+                    ui.horizontal(|ui| {
+                        ui.label(format!(
+                            "⚡ {}/s, {:.1} files/s",
+                            humansize::format_size(
+                                self.bytes_per_second as u64,
+                                humansize::DECIMAL
+                            ),
+                            self.files_per_second
+                        ));
+                    });
+                }
             });
 
             ui.add_space(15.0);
@@ -1203,8 +1554,10 @@ impl WebpifyGuiApp {
                     ui.label("📝 Conversion Log");
                     ui.add_space(5.0);
 
+                    // Follows new lines while scrolled to the bottom
                     egui::ScrollArea::vertical()
                         .max_height(200.0)
+                        .stick_to_bottom(true)
                         .show(ui, |ui| {
                             for log_entry in &self.conversion_log {
                                 ui.label(log_entry);
@@ -1235,6 +1588,18 @@ impl WebpifyGuiApp {
 
         // Results Summary
         if let Some(report) = &self.last_report {
+            if report.cancelled {
+                ui.colored_label(
+                    egui::Color32::ORANGE,
+                    "⏹️ Conversion was stopped - results cover only the files converted before stopping",
+                );
+                ui.add_space(10.0);
+            }
+            if report.dry_run {
+                ui.colored_label(egui::Color32::ORANGE, "🔍 Dry run - no files were written");
+                ui.add_space(10.0);
+            }
+
             // File Statistics
             ui.group(|ui| {
                 ui.label("📊 File Statistics");
@@ -1262,6 +1627,14 @@ impl WebpifyGuiApp {
                             ui.label(format!("{} files", report.skipped_files));
                             ui.end_row();
                         }
+
+                        let mut skip_reasons: Vec<_> = report.skip_reasons.iter().collect();
+                        skip_reasons.sort_by_key(|(reason, _)| reason.to_string());
+                        for (reason, count) in skip_reasons {
+                            ui.label(format!("   {reason}:"));
+                            ui.label(format!("{count} files"));
+                            ui.end_row();
+                        }
                     });
             });
 
@@ -1285,19 +1658,23 @@ impl WebpifyGuiApp {
                             ui.end_row();
 
                             ui.label("🗜️ Compressed size:");
+                            if report.compressed_size_unknown {
+                                ui.label("not measured (enable exact size estimates)");
+                                ui.end_row();
+                                return;
+                            }
                             ui.label(humansize::format_size(
                                 report.compressed_size,
                                 humansize::DECIMAL,
                             ));
                             ui.end_row();
 
-                            ui.label("💾 Space saved:");
-                            let savings =
-                                report.original_size.saturating_sub(report.compressed_size);
+                            ui.label("💾 Size change:");
+                            let difference = report.original_size.abs_diff(report.compressed_size);
                             ui.label(format!(
-                                "{} ({:.1}%)",
-                                humansize::format_size(savings, humansize::DECIMAL),
-                                report.compression_ratio * 100.0
+                                "{} ({})",
+                                humansize::format_size(difference, humansize::DECIMAL),
+                                report.size_change_summary()
                             ));
                             ui.end_row();
                         });
@@ -1332,30 +1709,50 @@ impl WebpifyGuiApp {
             ui.add_space(15.0);
 
             // Export Results
+            let mut export_format = None;
             ui.group(|ui| {
                 ui.label("📤 Export Results");
                 ui.add_space(5.0);
 
                 ui.horizontal(|ui| {
                     if ui.button("📄 Generate JSON Report").clicked() {
-                        if let Err(e) = webpify::generate_report(report, &ReportFormat::Json) {
-                            self.error_message = Some(format!("Failed to generate report: {}", e));
-                        }
+                        export_format = Some(ReportFormat::Json);
                     }
 
                     if ui.button("📊 Generate CSV Report").clicked() {
-                        if let Err(e) = webpify::generate_report(report, &ReportFormat::Csv) {
-                            self.error_message = Some(format!("Failed to generate report: {}", e));
-                        }
+                        export_format = Some(ReportFormat::Csv);
                     }
 
                     if ui.button("🌐 Generate HTML Report").clicked() {
-                        if let Err(e) = webpify::generate_report(report, &ReportFormat::Html) {
-                            self.error_message = Some(format!("Failed to generate report: {}", e));
-                        }
+                        export_format = Some(ReportFormat::Html);
+                    }
+
+                    if ui.button("🧾 Generate XML Report").clicked() {
+                        export_format = Some(ReportFormat::Xml);
+                    }
+
+                    if ui.button("📝 Generate Text Report").clicked() {
+                        export_format = Some(ReportFormat::Text);
                     }
                 });
+
+                if let Some(path) = &self.last_report_path {
+                    ui.add_space(5.0);
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Last report: {}", path.display()));
+                        if ui.button("📂 Open").clicked() {
+                            ui.ctx().open_url(egui::OpenUrl::new_tab(format!(
+                                "file://{}",
+                                path.display()
+                            )));
+                        }
+                    });
+                }
             });
+
+            if let Some(format) = export_format {
+                self.export_report(&format);
+            }
         } else {
             ui.centered_and_justified(|ui| {
                 ui.label("No results yet. Run a conversion to see detailed results here.");
@@ -1373,6 +1770,9 @@ impl WebpifyGuiApp {
         }
 
         self.preview_files.clear();
+        self.preview_summary = PreviewSummary::default();
+        // Dropping the receiver of a previous scan stops its thread
+        self.preview_scan = None;
         let input_path = PathBuf::from(&self.input_dir);
 
         // Validate input path exists
@@ -1387,155 +1787,115 @@ impl WebpifyGuiApp {
         }
 
         // Parse supported formats
-        let formats: Vec<String> = self
-            .formats
-            .split(',')
-            .map(|s| s.trim().to_lowercase())
-            .filter(|s| !s.is_empty())
-            .collect();
+        let formats = self.resolved_formats();
 
         if formats.is_empty() {
             self.error_message = Some("No file formats specified".to_string());
             return;
         }
 
-        // Recursively scan directory for supported files with error handling
-        match self.scan_directory_safe(&input_path, &formats) {
-            Ok(_) => {
-                // Sort by file size (largest first) for better overview
-                self.preview_files.sort_by(|a, b| b.size.cmp(&a.size));
+        // Scan in the background so the UI stays responsive; files show up as they are found
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        self.preview_scan = Some(receiver);
 
-                // Limit to first 100 files for performance
-                self.preview_files.truncate(100);
-
-                // If no files found, show helpful message
-                if self.preview_files.is_empty() {
-                    self.error_message = Some(format!(
-                        "No files with supported formats ({}) found in directory",
-                        self.formats
-                    ));
-                }
-            }
-            Err(e) => {
-                self.error_message = Some(format!("Error scanning directory: {}", e));
-            }
-        }
+        thread::spawn(move || {
+            walkdir::WalkDir::new(&input_path)
+                .follow_links(false)
+                .into_iter()
+                .filter_map(|entry| match entry {
+                    Ok(entry) => Some(entry),
+                    Err(e) => {
+                        log::warn!("Skipping unreadable entry during preview: {e}");
+                        None
+                    }
+                })
+                .filter(|entry| entry.file_type().is_file())
+                .par_bridge()
+                .filter_map(|entry| {
+                    let path = entry.into_path();
+                    let format = path.extension()?.to_str()?.to_lowercase();
+                    if !formats.contains(&format) || !webpify::is_valid_image_file(&path) {
+                        return None;
+                    }
+                    let size = std::fs::metadata(&path).ok()?.len();
+                    Some(PreviewFileInfo {
+                        path,
+                        size,
+                        format,
+                        estimated_output_size: None,
+                    })
+                })
+                // Sending fails once the preview is closed or restarted; stop then
+                .try_for_each(|file| sender.send(file).map_err(|_| ()))
+                .ok();
+        });
     }
 
-    fn scan_directory_safe(
-        &mut self,
-        dir: &PathBuf,
-        formats: &[String],
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        use std::fs;
-
-        // Safety check to prevent infinite recursion or too deep scanning
-        if self.preview_files.len() >= 100 {
-            return Ok(()); // Stop scanning if we already have enough files
-        }
-
-        let entries = match fs::read_dir(dir) {
-            Ok(entries) => entries,
-            Err(e) => {
-                // Instead of printing to stderr, return a proper error for GUI handling
-                return Err(format!("Cannot read directory {}: {}", dir.display(), e).into());
-            }
+    /// Collect files found by the background preview scan
+    fn poll_preview_scan(&mut self) {
+        let Some(receiver) = &self.preview_scan else {
+            return;
         };
 
-        for entry in entries {
-            if self.preview_files.len() >= 100 {
-                break; // Stop if we have enough files
-            }
-
-            let entry = match entry {
-                Ok(entry) => entry,
-                Err(e) => {
-                    eprintln!("Warning: Error reading directory entry: {}", e);
-                    continue;
-                }
-            };
-
-            let file_type = match entry.file_type() {
-                Ok(file_type) => file_type,
-                Err(e) => {
-                    eprintln!(
-                        "Warning: Cannot determine file type for {}: {}",
-                        entry.path().display(),
-                        e
-                    );
-                    continue;
-                }
-            };
-
-            if file_type.is_file() {
-                let path = entry.path();
-                if let Some(extension) = path.extension() {
-                    let ext = extension.to_string_lossy().to_lowercase();
-                    if formats.contains(&ext) {
-                        match fs::metadata(&path) {
-                            Ok(metadata) => {
-                                // Estimate output size based on compression mode and quality
-                                let estimated_size = self.estimate_webp_size(metadata.len());
-
-                                self.preview_files.push(PreviewFileInfo {
-                                    path: path.clone(),
-                                    size: metadata.len(),
-                                    format: ext,
-                                    estimated_output_size: Some(estimated_size),
-                                });
-                            }
-                            Err(e) => {
-                                // Log the error but continue processing other files
-                                eprintln!(
-                                    "Warning: Could not read metadata for {}: {}",
-                                    path.display(),
-                                    e
-                                );
-                            }
-                        }
-                    }
+        let mut finished = false;
+        let mut received = false;
+        loop {
+            match receiver.try_recv() {
+                Ok(file) => {
+                    let estimated_size = self.estimate_webp_size(file.size);
+                    self.preview_summary.files += 1;
+                    self.preview_summary.total_size += file.size;
+                    self.preview_summary.estimated_size += estimated_size;
+                    self.preview_files.push(PreviewFileInfo {
+                        estimated_output_size: Some(estimated_size),
+                        ..file
+                    });
+                    received = true;
                 }
-            } else if file_type.is_dir() && self.preserve_structure {
-                // Recursively scan subdirectories if preserve_structure is enabled
-                // Use a depth limit to prevent infinite recursion
-                if let Some(depth) = self.get_directory_depth(&entry.path(), dir) {
-                    if depth < 5 {
-                        // Reduced depth limit for safety
-                        if let Err(e) = self.scan_directory_safe(&entry.path(), formats) {
-                            eprintln!(
-                                "Warning: Error scanning subdirectory {}: {}",
-                                entry.path().display(),
-                                e
-                            );
-                            // Continue with other directories instead of failing completely
-                        }
-                    }
+                Err(crossbeam_channel::TryRecvError::Empty) => break,
+                Err(crossbeam_channel::TryRecvError::Disconnected) => {
+                    finished = true;
+                    break;
                 }
             }
         }
 
-        Ok(())
+        if received {
+            // Keep only the largest files for the list; the summary covers all of them
+            self.preview_files
+                .sort_by_key(|file| std::cmp::Reverse(file.size));
+            self.preview_files.truncate(PREVIEW_LIST_LIMIT);
+        }
+
+        if finished {
+            self.preview_scan = None;
+            if self.preview_summary.files == 0 {
+                self.error_message = Some(format!(
+                    "No files with supported formats ({}) found in directory",
+                    self.formats
+                ));
+            }
+        }
     }
 
-    fn get_directory_depth(&self, path: &PathBuf, base: &PathBuf) -> Option<usize> {
-        path.strip_prefix(base).ok().map(|p| p.components().count())
+    /// Formats from the format field, resolved like the core does (`all` expanded, WebP
+    /// only when re-encoding is enabled)
+    fn resolved_formats(&self) -> Vec<String> {
+        let formats: Vec<String> = self
+            .formats
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        ConversionOptions::default()
+            .with_supported_formats(formats)
+            .with_reencode_webp(self.reencode_webp)
+            .effective_formats()
     }
 
     fn estimate_webp_size(&self, original_size: u64) -> u64 {
         // Rough estimation based on compression mode and quality
-        let compression_factor = match self.mode {
-            CompressionMode::Lossless => 0.7, // Lossless typically saves 20-30%
-            CompressionMode::Lossy => {
-                // Lossy compression factor based on quality
-                match self.quality {
-                    90..=100 => 0.6,
-                    70..=89 => 0.4,
-                    50..=69 => 0.3,
-                    _ => 0.2,
-                }
-            }
-            CompressionMode::Auto => 0.5, // Conservative estimate for auto mode
-        };
+        let compression_factor = self.mode.estimated_size_factor(self.quality);
 
         (original_size as f64 * compression_factor) as u64
     }
@@ -1552,13 +1912,20 @@ impl WebpifyGuiApp {
             .resizable(true)
             .show(ctx, |ui| {
                 ui.horizontal(|ui| {
-                    ui.label(format!("📊 Found {} files to convert", self.preview_files.len()));
+                    if self.preview_scan.is_some() {
+                        ui.spinner();
+                        ui.label(format!("Scanning... {} files found", self.preview_summary.files));
+                    } else {
+                        ui.label(format!("📊 Found {} files to convert", self.preview_summary.files));
+                    }
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                         if ui.button("❌ Close").clicked() {
                             self.show_preview_window = false;
+                            self.preview_scan = None;
                         }
                         if !self.preview_files.is_empty() && ui.button("🚀 Start Conversion").clicked() {
                             self.show_preview_window = false;
+                            self.preview_scan = None;
                             self.start_conversion();
                         }
                     });
@@ -1577,6 +1944,9 @@ impl WebpifyGuiApp {
                 }
 
                 if self.preview_files.is_empty() {
+                    if self.preview_scan.is_some() {
+                        return;
+                    }
                     ui.centered_and_justified(|ui| {
                         if self.error_message.is_some() {
                             ui.label("Preview generation failed. Please check the error above and try again.");
@@ -1592,10 +1962,8 @@ impl WebpifyGuiApp {
                     ui.label("📈 Summary");
                     ui.add_space(5.0);
 
-                    let total_size: u64 = self.preview_files.iter().map(|f| f.size).sum();
-                    let estimated_output: u64 = self.preview_files.iter()
-                        .filter_map(|f| f.estimated_output_size)
-                        .sum();
+                    let total_size = self.preview_summary.total_size;
+                    let estimated_output = self.preview_summary.estimated_size;
                     let estimated_savings = total_size.saturating_sub(estimated_output);
                     let savings_percent = if total_size > 0 {
                         (estimated_savings as f64 / total_size as f64) * 100.0
@@ -1608,7 +1976,7 @@ impl WebpifyGuiApp {
                         .spacing([20.0, 5.0])
                         .show(ui, |ui| {
                             ui.label("🗂️ Total files:");
-                            ui.label(format!("{}", self.preview_files.len()));
+                            ui.label(format!("{}", self.preview_summary.files));
                             ui.end_row();
 
                             ui.label("📦 Total size:");
@@ -1693,10 +2061,13 @@ impl WebpifyGuiApp {
                         });
                 });
 
-                if self.preview_files.len() >= 100 {
+                if self.preview_summary.files > self.preview_files.len() {
                     ui.add_space(5.0);
-                    ui.colored_label(egui::Color32::ORANGE,
-                        "⚠️ Showing first 100 files only. Use filters to refine your selection.");
+                    ui.colored_label(egui::Color32::ORANGE, format!(
+                        "⚠️ Listing the {} largest of {} files. The summary covers all of them.",
+                        self.preview_files.len(),
+                        self.preview_summary.files
+                    ));
                 }
             });
     }
@@ -1814,6 +2185,7 @@ impl WebpifyGuiApp {
 
             ui.label("📂 Input Directory:");
             ui.label("  • Select folder containing images to convert");
+            ui.label("  • Or drop a folder (or a file in it) onto the window");
             ui.label("  • Supports nested subdirectories");
             ui.label("  • Only supported formats will be processed");
 
@@ -1881,6 +2253,11 @@ impl WebpifyGuiApp {
             ui.label("  • JSON: Machine-readable format");
             ui.label("  • CSV: Spreadsheet-compatible");
             ui.label("  • HTML: Human-readable with charts");
+
+            ui.add_space(5.0);
+            ui.label("⚙️ Config Files:");
+            ui.label("  • Save Config writes all settings as TOML");
+            ui.label("  • The same file works with the command line (--config)");
         });
     }
 
@@ -1900,6 +2277,7 @@ impl WebpifyGuiApp {
             ui.add_space(5.0);
             ui.label("⏹️ Controls:");
             ui.label("  • Stop button halts conversion safely");
+            ui.label("  • Pause holds off new files until you press Resume");
             ui.label("  • Conversion can be resumed by starting again");
             ui.label("  • Existing WebP files are skipped unless overwrite is enabled");
         });
@@ -1965,21 +2343,26 @@ impl WebpifyGuiApp {
             }
         };
 
-        // Clear previous results
-        self.clear_results();
-        self.is_converting = true;
-        self.current_tab = Tab::Progress; // Auto-switch to progress tab
-
         // Create conversion options with full configuration
         let mut options = ConversionOptions::new(input_path)
             .with_quality(self.quality)
+            .with_effort(self.effort)
             .with_mode(self.mode.clone())
             .with_dry_run(self.dry_run)
+            .with_dry_run_accurate(self.dry_run_accurate)
             .with_overwrite(self.overwrite)
             .with_preserve_structure(self.preserve_structure)
+            .with_in_place(self.in_place)
             .with_min_size_kb(self.min_size)
             .with_prescan(self.prescan)
             .with_reencode_webp(self.reencode_webp)
+            .with_preserve_metadata(self.preserve_metadata)
+            .with_preserve_timestamps(self.preserve_timestamps)
+            .with_animation(self.animation)
+            .with_low_priority(self.low_priority)
+            .with_generate_report(self.generate_report)
+            .with_report_format(self.report_format.clone())
+            .with_report_timestamp(self.report_timestamp)
             .with_replace_input_mode(self.replace_input.clone());
 
         // Set output directory
@@ -2014,33 +2397,84 @@ impl WebpifyGuiApp {
             options = options.with_supported_formats(formats);
         }
 
+        // Clear previous results
+        self.clear_results();
+        self.is_converting = true;
+        self.is_paused = false;
+        self.is_stopping = false;
+
+        let control = ConversionControl::new();
+        options = options.with_control(control.clone());
+        self.conversion_control = Some(control);
+        self.current_tab = Tab::Progress; // Auto-switch to progress tab
+
         // Start conversion in background thread
         let progress_reporter = Arc::clone(&self.progress_reporter);
         let generate_report = self.generate_report;
         let report_format = self.report_format.clone();
+        let report_options = ReportOptions {
+            timestamp: self.report_timestamp,
+            ..Default::default()
+        };
+
+        let check_collisions = !self.preserve_structure;
 
         thread::spawn(move || {
             let mut core = WebpifyCore::new(options);
 
+            // Refuse to flatten the output when several inputs would overwrite each other;
+            // the scan runs here so a large library doesn't freeze the window
+            if check_collisions {
+                let error = match core.find_output_collisions() {
+                    Ok(collisions) if !collisions.is_empty() => {
+                        Some(collision_message(&collisions))
+                    }
+                    Ok(_) => None,
+                    Err(e) => Some(format!("Failed to scan input files: {:#}", e)),
+                };
+                if let Some(error) = error {
+                    if let Ok(mut progress) = progress_reporter.lock() {
+                        progress.error = Some(error);
+                        progress.finished = true;
+                    }
+                    return;
+                }
+            }
+
             // Create progress reporter
             let reporter: Box<dyn ProgressReporter> = Box::new(ThreadSafeGuiProgressReporter {
                 inner: Arc::clone(&progress_reporter),
+                started: Instant::now(),
             });
 
             match core.run_with_progress(Some(reporter)) {
                 Ok(report) => {
                     // Generate report if requested
                     if generate_report {
-                        if let Err(e) = webpify::generate_report(&report, &report_format) {
-                            if let Ok(mut progress) = progress_reporter.lock() {
-                                progress.error = Some(format!(
-                                    "Conversion succeeded but failed to generate report: {}",
-                                    e
-                                ));
-                                progress.report = Some(report);
-                                progress.finished = true;
+                        match webpify::generate_report_with_options(
+                            &report,
+                            &report_format,
+                            &report_options,
+                        ) {
+                            Ok(path) => {
+                                if let Ok(mut progress) = progress_reporter.lock() {
+                                    progress
+                                        .logs
+                                        .push(format!("Report saved to: {}", path.display()));
+                                    progress.report_path = Some(path);
+                                }
+                            }
+                            Err(e) => {
+                                if let Ok(mut progress) = progress_reporter.lock() {
+                                    progress.error = Some(format!(
+                                        "Conversion succeeded but failed to generate report: {:#}",
+                                        e
+                                    ));
+                                    progress.report = Some(report);
+                                    progress.finished = true;
+                                }
+                                return;
                             }
-                            return;
                         }
                     }
 
@@ -2059,13 +2493,264 @@ impl WebpifyGuiApp {
         });
     }
 
+    fn export_report(&mut self, format: &ReportFormat) {
+        let Some(report) = &self.last_report else {
+            return;
+        };
+
+        let options = ReportOptions {
+            timestamp: self.report_timestamp,
+            ..Default::default()
+        };
+        match webpify::generate_report_with_options(report, format, &options) {
+            Ok(path) => {
+                self.conversion_log
+                    .push(format!("Report saved to: {}", path.display()));
+                self.last_report_path = Some(path);
+            }
+            Err(e) => self.error_message = Some(format!("Failed to generate report: {:#}", e)),
+        }
+    }
+
+    /// Load the config file named in the config file field, asking for one if it is empty
+    fn load_config(&mut self) {
+        if self.config_file.is_empty() {
+            let Some(path) = rfd::FileDialog::new()
+                .set_title("Load Configuration File")
+                .add_filter("TOML", &["toml"])
+                .pick_file()
+            else {
+                return;
+            };
+            self.config_file = path.display().to_string();
+        }
+
+        let path = PathBuf::from(&self.config_file);
+        match Config::from_file(&path).and_then(|config| self.apply_config(&config)) {
+            Ok(()) => self
+                .conversion_log
+                .push(format!("Config loaded from: {}", path.display())),
+            Err(e) => self.error_message = Some(format!("Failed to load config: {:#}", e)),
+        }
+    }
+
+    fn save_config(&mut self) {
+        let mut dialog = rfd::FileDialog::new()
+            .set_title("Save Configuration File")
+            .add_filter("TOML", &["toml"])
+            .set_file_name("webpify.config.toml");
+        if let Some(dir) = PathBuf::from(&self.config_file).parent()
+            && dir.is_dir()
+        {
+            dialog = dialog.set_directory(dir);
+        }
+        let Some(path) = dialog.save_file() else {
+            return;
+        };
+
+        match self.to_config().save(&path) {
+            Ok(()) => {
+                self.config_file = path.display().to_string();
+                self.conversion_log
+                    .push(format!("Config saved to: {}", path.display()));
+            }
+            Err(e) => self.error_message = Some(format!("Failed to save config: {:#}", e)),
+        }
+    }
+
+    /// The current settings in config file form
+    fn to_config(&self) -> Config {
+        let non_empty = |value: &str| (!value.is_empty()).then(|| value.to_string());
+
+        Config {
+            general: Some(GeneralConfig {
+                input_dir: non_empty(&self.input_dir),
+                output_dir: if self.output_dir_auto {
+                    None
+                } else {
+                    non_empty(&self.output_dir)
+                },
+                preserve_structure: Some(self.preserve_structure),
+                in_place: Some(self.in_place),
+                overwrite: Some(self.overwrite),
+                threads: if self.threads_auto {
+                    None
+                } else {
+                    self.threads.parse().ok()
+                },
+                low_priority: Some(self.low_priority),
+                preserve_timestamps: Some(self.preserve_timestamps),
+                prescan: Some(self.prescan),
+                replace_input: Some(self.replace_input.to_string()),
+                reencode_webp: Some(self.reencode_webp),
+                dry_run: Some(self.dry_run),
+                dry_run_accurate: Some(self.dry_run_accurate),
+                ..Default::default()
+            }),
+            compression: Some(CompressionConfig {
+                quality: Some(self.quality),
+                mode: Some(self.mode.to_string()),
+                effort: Some(self.effort),
+                preserve_metadata: Some(self.preserve_metadata),
+                animation: Some(self.animation),
+                ..Default::default()
+            }),
+            filtering: Some(FilteringConfig {
+                formats: Some(
+                    self.formats
+                        .split(',')
+                        .map(|s| s.trim().to_lowercase())
+                        .filter(|s| !s.is_empty())
+                        .collect(),
+                ),
+                min_size: Some(self.min_size),
+                // 0 means unlimited
+                max_size: Some(self.max_size.parse().unwrap_or(0)),
+                ..Default::default()
+            }),
+            output: Some(OutputConfig {
+                verbose: Some(self.verbose),
+                quiet: Some(self.quiet),
+                generate_report: Some(self.generate_report),
+                report_format: Some(self.report_format.to_string()),
+                report_timestamp: Some(self.report_timestamp),
+                ..Default::default()
+            }),
+            profiles: None,
+        }
+    }
+
+    /// Take over every setting the config file contains and keep the rest
+    fn apply_config(&mut self, config: &Config) -> anyhow::Result<()> {
+        // Parse everything up front so a bad value leaves all settings unchanged
+        let general = config.general.as_ref();
+        let compression = config.compression.as_ref();
+        let filtering = config.filtering.as_ref();
+        let output = config.output.as_ref();
+        let replace_input = general
+            .and_then(|general| general.replace_input.as_deref())
+            .map(str::parse::<ReplaceInputMode>)
+            .transpose()?;
+        let mode = compression
+            .and_then(|compression| compression.mode.as_deref())
+            .map(str::parse::<CompressionMode>)
+            .transpose()?;
+        let report_format = output
+            .and_then(|output| output.report_format.as_deref())
+            .map(str::parse::<ReportFormat>)
+            .transpose()?;
+
+        if let Some(general) = general {
+            if let Some(input_dir) = &general.input_dir {
+                self.input_dir = input_dir.clone();
+            }
+            if let Some(output_dir) = &general.output_dir {
+                self.output_dir = output_dir.clone();
+                self.output_dir_auto = false;
+            }
+            if let Some(preserve_structure) = general.preserve_structure {
+                self.preserve_structure = preserve_structure;
+            }
+            if let Some(in_place) = general.in_place {
+                self.in_place = in_place;
+            }
+            if let Some(overwrite) = general.overwrite {
+                self.overwrite = overwrite;
+            }
+            if let Some(threads) = general.threads {
+                self.threads = threads.to_string();
+                self.threads_auto = false;
+            }
+            if let Some(low_priority) = general.low_priority {
+                self.low_priority = low_priority;
+            }
+            if let Some(preserve_timestamps) = general.preserve_timestamps {
+                self.preserve_timestamps = preserve_timestamps;
+            }
+            if let Some(prescan) = general.prescan {
+                self.prescan = prescan;
+            }
+            if let Some(replace_input) = replace_input {
+                self.replace_input = replace_input;
+            }
+            if let Some(reencode_webp) = general.reencode_webp {
+                self.reencode_webp = reencode_webp;
+            }
+            if let Some(dry_run) = general.dry_run {
+                self.dry_run = dry_run;
+            }
+            if let Some(dry_run_accurate) = general.dry_run_accurate {
+                self.dry_run_accurate = dry_run_accurate;
+            }
+        }
+
+        if let Some(compression) = compression {
+            if let Some(quality) = compression.quality {
+                self.quality = quality.min(100);
+            }
+            if let Some(mode) = mode {
+                self.mode = mode;
+            }
+            if let Some(effort) = compression.effort {
+                self.effort = effort.min(webpify::converter::MAX_EFFORT);
+            }
+            if let Some(preserve_metadata) = compression.preserve_metadata {
+                self.preserve_metadata = preserve_metadata;
+            }
+            if let Some(animation) = compression.animation {
+                self.animation = animation;
+            }
+        }
+
+        if let Some(filtering) = filtering {
+            if let Some(formats) = &filtering.formats {
+                self.formats = formats.join(",");
+            }
+            if let Some(min_size) = filtering.min_size {
+                self.min_size = min_size;
+            }
+            if let Some(max_size) = filtering.max_size {
+                self.max_size = match max_size {
+                    0 => String::new(),
+                    max_size => max_size.to_string(),
+                };
+            }
+        }
+
+        if let Some(output) = output {
+            if let Some(verbose) = output.verbose {
+                self.verbose = verbose;
+            }
+            if let Some(quiet) = output.quiet {
+                self.quiet = quiet;
+            }
+            if let Some(generate_report) = output.generate_report {
+                self.generate_report = generate_report;
+            }
+            if let Some(report_format) = report_format {
+                self.report_format = report_format;
+            }
+            if let Some(report_timestamp) = output.report_timestamp {
+                self.report_timestamp = report_timestamp;
+            }
+        }
+
+        Ok(())
+    }
+
     fn clear_results(&mut self) {
         self.last_report = None;
+        self.last_report_path = None;
         self.error_message = None;
         self.progress = 0.0;
         self.total_files = 0;
         self.processed_files = 0;
         self.failed_files = 0;
+        self.bytes_per_second = 0.0;
+        self.files_per_second = 0.0;
+        self.eta = None;
+        self.processed_bytes = 0;
+        self.total_bytes = 0;
         self.conversion_log.clear();
 
         if let Ok(mut reporter) = self.progress_reporter.lock() {
@@ -2074,13 +2759,45 @@ impl WebpifyGuiApp {
     }
 }
 
+/// Describe flattened output collisions, listing the sources of every shared output
+fn collision_message(collisions: &[OutputCollision]) -> String {
+    let details: Vec<String> = collisions
+        .iter()
+        .map(|collision| {
+            let sources: Vec<String> = collision
+                .sources
+                .iter()
+                .map(|path| format!("  • {}", path.display()))
+                .collect();
+            format!(
+                "{}:\n{}",
+                collision.output_path.display(),
+                sources.join("\n")
+            )
+        })
+        .collect();
+    format!(
+        "{} output files would be overwritten because 'Preserve directory structure' is off. \
+         Enable it or rename these files:\n{}",
+        collisions.len(),
+        details.join("\n")
+    )
+}
+
 /// Progress reporter that can be safely shared between threads
 struct GuiProgressReporter {
     total_files: usize,
     processed_files: usize,
     failed_files: usize,
+    bytes_per_second: f64,
+    files_per_second: f64,
+    eta: Option<Duration>,
+    processed_bytes: u64,
+    total_bytes: u64,
     finished: bool,
     report: Option<ConversionReport>,
+    // Report written after the run, picked up by the UI thread
+    report_path: Option<PathBuf>,
     error: Option<String>,
     logs: Vec<String>,
 }
@@ -2091,8 +2808,14 @@ impl GuiProgressReporter {
             total_files: 0,
             processed_files: 0,
             failed_files: 0,
+            bytes_per_second: 0.0,
+            files_per_second: 0.0,
+            eta: None,
+            processed_bytes: 0,
+            total_bytes: 0,
             finished: false,
             report: None,
+            report_path: None,
             error: None,
             logs: Vec::new(),
         }
@@ -2102,6 +2825,7 @@ impl GuiProgressReporter {
 /// Thread-safe wrapper for GUI progress reporter
 struct ThreadSafeGuiProgressReporter {
     inner: Arc<Mutex<GuiProgressReporter>>,
+    started: Instant,
 }
 
 impl ProgressReporter for ThreadSafeGuiProgressReporter {
@@ -2117,6 +2841,76 @@ impl ProgressReporter for ThreadSafeGuiProgressReporter {
             reporter.failed_files = failed;
         }
     }
+
+    fn update_stats(
+        &self,
+        processed: usize,
+        failed: usize,
+        bytes_in: u64,
+        _bytes_out: u64,
+        eta: Option<Duration>,
+    ) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        if elapsed <= 0.0 {
+            return;
+        }
+
+        if let Ok(mut reporter) = self.inner.lock() {
+            reporter.bytes_per_second = bytes_in as f64 / elapsed;
+            reporter.files_per_second = (processed + failed) as f64 / elapsed;
+            reporter.eta = eta;
+        }
+    }
+
+    fn update_bytes(&self, processed_bytes: u64, total_bytes: u64) {
+        if let Ok(mut reporter) = self.inner.lock() {
+            reporter.processed_bytes = processed_bytes;
+            reporter.total_bytes = total_bytes;
+        }
+    }
+
+    fn start_conversion(&self) {
+        if let Ok(mut reporter) = self.inner.lock() {
+            let line = match reporter.total_files {
+                0 => "▶ Conversion started".to_string(),
+                total => format!("▶ Converting {total} files"),
+            };
+            reporter.logs.push(line);
+        }
+    }
+
+    fn finish_conversion(&self) {
+        if let Ok(mut reporter) = self.inner.lock() {
+            let line = format!(
+                "■ Finished: {} processed, {} failed",
+                reporter.processed_files, reporter.failed_files
+            );
+            reporter.logs.push(line);
+        }
+    }
+
+    fn report_error(&self, file_path: &str, error: &str) {
+        if let Ok(mut reporter) = self.inner.lock() {
+            reporter.logs.push(format!("❌ {file_path}: {error}"));
+        }
+    }
+
+    fn report_success(&self, file_path: &str, original_size: u64, compressed_size: u64) {
+        let saved = if original_size > 0 {
+            (1.0 - compressed_size as f64 / original_size as f64) * 100.0
+        } else {
+            0.0
+        };
+
+        if let Ok(mut reporter) = self.inner.lock() {
+            reporter.logs.push(format!(
+                "✅ {} -> {} ({:.1}% saved)",
+                file_path,
+                humansize::format_size(compressed_size, humansize::DECIMAL),
+                saved
+            ));
+        }
+    }
 }
 
 fn main() -> Result<(), eframe::Error> {
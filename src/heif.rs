@@ -0,0 +1,112 @@
+//! HEIC/HEIF input decoding (requires the `heic` feature)
+
+#[cfg(feature = "heic")]
+use anyhow::Context;
+use anyhow::Result;
+use image::DynamicImage;
+use std::path::Path;
+
+/// Major brands of HEIF files, found right after the `ftyp` box type
+pub(crate) const HEIF_BRANDS: &[&[u8; 4]] = &[
+    b"heic", b"heix", b"hevc", b"hevx", b"heim", b"heis", b"hevm", b"hevs", b"mif1", b"msf1",
+];
+
+/// Whether the path has a HEIC/HEIF extension
+pub fn is_heif_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("heic") || ext.eq_ignore_ascii_case("heif"))
+}
+
+/// Decode the primary image of a HEIC/HEIF file
+///
+/// Rotation and mirroring stored in the file are applied by libheif.
+#[cfg(feature = "heic")]
+pub fn open_primary(path: &Path) -> Result<DynamicImage> {
+    let context = read_context(path)?;
+    let handle = context
+        .primary_image_handle()
+        .with_context(|| format!("Failed to read image: {}", path.display()))?;
+    decode_handle(path, &handle)
+}
+
+/// Width and height of the primary image, read without decoding pixels
+#[cfg(feature = "heic")]
+pub fn image_dimensions(path: &Path) -> Result<(u32, u32)> {
+    let context = read_context(path)?;
+    let handle = context
+        .primary_image_handle()
+        .with_context(|| format!("Failed to read image: {}", path.display()))?;
+    Ok((handle.width(), handle.height()))
+}
+
+/// Decode every top-level image of a HEIC/HEIF file (burst shots, image sequences)
+///
+/// The primary image is not necessarily first; images are returned in file order.
+#[cfg(feature = "heic")]
+pub fn open_top_level_images(path: &Path) -> Result<Vec<DynamicImage>> {
+    let context = read_context(path)?;
+    let mut ids = vec![0; context.number_of_top_level_images()];
+    let count = context.top_level_image_ids(&mut ids);
+    ids.truncate(count);
+
+    ids.into_iter()
+        .map(|id| {
+            let handle = context
+                .image_handle(id)
+                .with_context(|| format!("Failed to read image: {}", path.display()))?;
+            decode_handle(path, &handle)
+        })
+        .collect()
+}
+
+#[cfg(feature = "heic")]
+fn read_context(path: &Path) -> Result<libheif_rs::HeifContext<'static>> {
+    let path_str = path
+        .to_str()
+        .with_context(|| format!("Unsupported file name: {}", path.display()))?;
+    libheif_rs::HeifContext::read_from_file(path_str)
+        .with_context(|| format!("Failed to read image: {}", path.display()))
+}
+
+#[cfg(feature = "heic")]
+fn decode_handle(path: &Path, handle: &libheif_rs::ImageHandle) -> Result<DynamicImage> {
+    use libheif_rs::{ColorSpace, LibHeif, RgbChroma};
+
+    let image = LibHeif::new()
+        .decode(handle, ColorSpace::Rgb(RgbChroma::Rgba), None)
+        .with_context(|| format!("Failed to decode image: {}", path.display()))?;
+    let plane = image
+        .planes()
+        .interleaved
+        .with_context(|| format!("Decoder returned no pixel data: {}", path.display()))?;
+
+    // Rows may be padded beyond width * 4 bytes
+    let row_len = plane.width as usize * 4;
+    let mut pixels = Vec::with_capacity(row_len * plane.height as usize);
+    for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+        pixels.extend_from_slice(&row[..row_len]);
+    }
+
+    image::RgbaImage::from_raw(plane.width, plane.height, pixels)
+        .map(DynamicImage::ImageRgba8)
+        .with_context(|| format!("Invalid pixel data: {}", path.display()))
+}
+
+#[cfg(not(feature = "heic"))]
+pub fn open_primary(path: &Path) -> Result<DynamicImage> {
+    anyhow::bail!(
+        "HEIC/HEIF input requires webpify to be built with the `heic` feature: {}",
+        path.display()
+    )
+}
+
+#[cfg(not(feature = "heic"))]
+pub fn image_dimensions(path: &Path) -> Result<(u32, u32)> {
+    open_primary(path).map(|image| (image.width(), image.height()))
+}
+
+#[cfg(not(feature = "heic"))]
+pub fn open_top_level_images(path: &Path) -> Result<Vec<DynamicImage>> {
+    open_primary(path).map(|image| vec![image])
+}
@@ -0,0 +1,74 @@
+//! Decoding the individual resolutions stored in ICO files
+
+use anyhow::{Context, Result, bail};
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use std::path::Path;
+
+/// Size of the ICONDIR header and of each directory entry
+const ICO_HEADER_LEN: usize = 6;
+const ICO_ENTRY_LEN: usize = 16;
+
+/// Whether the path has an `.ico` extension
+pub fn is_ico_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ico"))
+}
+
+/// Decode every resolution stored in an ICO file, largest first
+///
+/// When several entries share a size (e.g. different color depths), only the deepest
+/// one is kept so each resolution appears once.
+pub fn decode_all(path: &Path) -> Result<Vec<DynamicImage>> {
+    let data =
+        std::fs::read(path).with_context(|| format!("Failed to read image: {}", path.display()))?;
+    if data.len() < ICO_HEADER_LEN || data[0..4] != [0, 0, 1, 0] {
+        bail!("Not an ICO file: {}", path.display());
+    }
+
+    let count = u16::from_le_bytes([data[4], data[5]]) as usize;
+    let mut entries = Vec::with_capacity(count);
+    for index in 0..count {
+        let start = ICO_HEADER_LEN + index * ICO_ENTRY_LEN;
+        let Some(entry) = data.get(start..start + ICO_ENTRY_LEN) else {
+            bail!("Truncated ICO directory: {}", path.display());
+        };
+        let bits_per_pixel = u16::from_le_bytes([entry[6], entry[7]]);
+
+        // Decode each entry on its own by wrapping it in a single-entry ICO
+        let image =
+            image::load_from_memory_with_format(&single_entry_ico(&data, entry)?, ImageFormat::Ico)
+                .with_context(|| {
+                    format!("Failed to decode ICO entry {index}: {}", path.display())
+                })?;
+        entries.push((bits_per_pixel, image));
+    }
+
+    let area = |image: &DynamicImage| {
+        let (width, height) = image.dimensions();
+        width as u64 * height as u64
+    };
+    entries.sort_by(|(bpp_a, a), (bpp_b, b)| area(b).cmp(&area(a)).then(bpp_b.cmp(bpp_a)));
+    entries.dedup_by(|(_, later), (_, kept)| later.dimensions() == kept.dimensions());
+
+    if entries.is_empty() {
+        bail!("ICO file contains no images: {}", path.display());
+    }
+    Ok(entries.into_iter().map(|(_, image)| image).collect())
+}
+
+/// Build an ICO file holding only the image referenced by `entry`
+fn single_entry_ico(data: &[u8], entry: &[u8]) -> Result<Vec<u8>> {
+    let size = u32::from_le_bytes([entry[8], entry[9], entry[10], entry[11]]) as usize;
+    let offset = u32::from_le_bytes([entry[12], entry[13], entry[14], entry[15]]) as usize;
+    let image_data = data
+        .get(offset..offset.saturating_add(size))
+        .context("ICO entry points outside the file")?;
+
+    let mut ico = Vec::with_capacity(ICO_HEADER_LEN + ICO_ENTRY_LEN + size);
+    ico.extend_from_slice(&[0, 0, 1, 0, 1, 0]);
+    ico.extend_from_slice(&entry[..12]);
+    ico.extend_from_slice(&((ICO_HEADER_LEN + ICO_ENTRY_LEN) as u32).to_le_bytes());
+    ico.extend_from_slice(image_data);
+    Ok(ico)
+}
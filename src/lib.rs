@@ -5,27 +5,46 @@
 //! This library provides the core functionality for converting images to WebP format,
 //! with support for parallel processing, different compression modes, and comprehensive
 //! progress tracking.
+//!
+//! Front ends should prefer [`WebpifyCore::events`], which runs a conversion in the
+//! background and yields every [`ProgressEvent`] from scanning to the final report.
+//! For quick integrations, [`WebpifyCore::run_with_callback`] passes the same events to a
+//! closure and returns the report.
 
+pub mod checkpoint;
 pub mod config;
+pub mod control;
 pub mod converter;
 pub mod core;
+pub mod heif;
+pub mod ico;
+pub mod manifest;
+pub mod metadata;
+pub mod metrics;
+pub mod output_path;
 pub mod progress;
+mod run_log;
 pub mod stats;
+pub mod tiff_pages;
 pub mod utils;
 
 // Re-export commonly used types
 pub use config::{Config, ConversionOptions, ProfileConfig};
-pub use converter::ImageConverter;
-pub use core::WebpifyCore;
-pub use progress::ProgressReporter;
-pub use stats::ConversionStats;
+pub use control::ConversionControl;
+pub use converter::{ConversionOutcome, ImageConverter, QualitySearchResult};
+pub use core::{OutputCollision, WebpifyCore};
+pub use output_path::{DefaultOutputPathResolver, OutputPathResolver};
+pub use progress::{FileProgressReporter, ProgressEvent, ProgressReporter};
+pub use stats::{CategorizedError, ConversionStats, ErrorKind, FileResult, FileStatus, SkipReason};
 pub use utils::{ImageValidationError, format_duration, is_valid_image_file, validate_image_file};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use std::time::Duration;
 
 /// Main conversion report structure
@@ -42,14 +61,80 @@ pub struct ConversionReport {
     pub skipped_files: u64,
     pub original_size: u64,
     pub compressed_size: u64,
-    pub compression_ratio: f64,
+    /// Fraction of the original size saved, `1 - compressed / original`; negative when the
+    /// output is larger than the input
+    #[serde(alias = "compression_ratio")]
+    pub space_saved_ratio: f64,
+    /// Output size as a fraction of the original size, `compressed / original`; above 1
+    /// when the output is larger than the input
+    #[serde(default)]
+    pub size_ratio: f64,
     pub files_per_second: f64,
     pub bytes_per_second: u64,
     pub thread_count: usize,
     pub quality: u8,
     pub mode: String,
     pub format_stats: HashMap<String, u64>,
+    /// Mode and quality used for input formats with an override
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub format_overrides: HashMap<String, FormatOverrideUsage>,
     pub errors: Vec<String>,
+    /// Quality picked per file when encoding towards a target SSIM
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub quality_searches: HashMap<String, converter::QualitySearchResult>,
+    /// True when the run was cancelled before every file was handled
+    #[serde(default)]
+    pub cancelled: bool,
+    /// True for dry runs, which write no output files
+    #[serde(default)]
+    pub dry_run: bool,
+    /// True when output sizes were not measured (dry runs without accurate sizes); the
+    /// compressed size and both size ratios are then reported as 0
+    #[serde(default)]
+    pub compressed_size_unknown: bool,
+    /// Outputs given a numeric suffix because another input mapped to the same path
+    #[serde(default)]
+    pub renamed_outputs: u64,
+    /// Files whose source was copied to the output because the WebP would be larger
+    #[serde(default)]
+    pub kept_originals: u64,
+    /// WebP inputs that were re-encoded
+    #[serde(default)]
+    pub webp_reencoded: u64,
+    /// WebP inputs kept as they were because re-encoding didn't make them smaller
+    #[serde(default)]
+    pub webp_kept: u64,
+    /// Files below the passthrough size, copied to the output without converting
+    #[serde(default)]
+    pub passed_through: u64,
+    /// Number of failed files per error category
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub error_counts: HashMap<ErrorKind, u64>,
+    /// Number of files not converted per reason, including files left out by the scan
+    /// filters (which are not part of `skipped_files`)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub skip_reasons: HashMap<SkipReason, u64>,
+    /// The file cap, set when it left matching files out of the run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_limit: Option<u64>,
+    /// Every output file written, when output tracking is enabled
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub output_files: Vec<PathBuf>,
+    /// Result of every file, when per-file results are enabled
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileResult>,
+    /// JSON Lines report the run streamed its per-file lines to, if any; its summary line
+    /// is appended there, while other JSON Lines reports start with an empty file
+    #[serde(skip)]
+    pub json_lines_stream: Option<PathBuf>,
+}
+
+/// Settings used for an input format with an override, and how many files had it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FormatOverrideUsage {
+    pub mode: String,
+    pub quality: u8,
+    pub files: u64,
 }
 
 /// Report output formats
@@ -58,6 +143,12 @@ pub enum ReportFormat {
     Json,
     Csv,
     Html,
+    /// One JSON object per file, appended while converting, followed by a summary line
+    JsonLines,
+    /// Summary, format stats and errors as XML (schema on `generate_xml_report`)
+    Xml,
+    /// The human-readable summary the CLI prints, with every error listed
+    Text,
 }
 
 /// Compression modes for WebP conversion
@@ -69,6 +160,123 @@ pub enum CompressionMode {
     Lossy,
     /// Auto mode (intelligently choose based on image characteristics)
     Auto,
+    /// Near-lossless compression (lossless encoding after slight pixel preprocessing)
+    ///
+    /// The quality setting is used as the near-lossless level: 100 is plain lossless and
+    /// lower values allow more preprocessing for smaller files (60 is a good start).
+    NearLossless,
+    /// Encode both losslessly and lossy in memory and keep the smaller result
+    ///
+    /// Takes about twice as long as a single mode. A minimum SSIM can keep lossy results
+    /// that lose too much quality from being picked.
+    Smallest,
+}
+
+/// Which resolutions of an ICO file to convert
+#[derive(Debug, Clone, PartialEq)]
+pub enum IcoSizeSelection {
+    /// Convert only the largest embedded image (default)
+    Largest,
+    /// Convert only the smallest embedded image
+    Smallest,
+    /// Write one WebP per embedded resolution, suffixed with its size (`icon_32x32.webp`)
+    All,
+}
+
+/// Which pages of a multi-page TIFF file to convert
+#[derive(Debug, Clone, PartialEq)]
+pub enum TiffPageSelection {
+    /// Convert only the first page (default)
+    First,
+    /// Write one WebP per page, suffixed with its 1-based number (`scan_p2.webp`)
+    All,
+}
+
+/// Resampling filter used when images are downscaled
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResizeFilter {
+    /// Nearest neighbor (fastest, keeps hard edges for pixel art)
+    Nearest,
+    /// Linear interpolation (fast, slightly soft)
+    Triangle,
+    /// Cubic interpolation (sharp, good for photos)
+    CatmullRom,
+    /// Gaussian blur (smooth, no ringing)
+    Gaussian,
+    /// Lanczos with window 3 (sharpest, slowest; default)
+    Lanczos3,
+}
+
+impl CompressionMode {
+    /// Rough output size as a fraction of the input size, for estimates before encoding
+    pub fn estimated_size_factor(&self, quality: u8) -> f64 {
+        match self {
+            CompressionMode::Lossless => 0.7, // Lossless typically saves 20-30%
+            // Lossy compression factor based on quality
+            CompressionMode::Lossy => match quality {
+                90..=100 => 0.6,
+                70..=89 => 0.4,
+                50..=69 => 0.3,
+                _ => 0.2,
+            },
+            CompressionMode::Auto => 0.5, // Conservative estimate for auto mode
+            CompressionMode::NearLossless => 0.55, // Between lossless and high-quality lossy
+            CompressionMode::Smallest => 0.4, // At most the smaller of lossless and lossy
+        }
+    }
+}
+
+impl ConversionReport {
+    /// Human-readable size change, e.g. "42.0% smaller" or "12.5% larger"
+    ///
+    /// Spells out growth instead of showing a negative saving.
+    pub fn size_change_summary(&self) -> String {
+        let percent = self.space_saved_ratio * 100.0;
+        if self.original_size == 0 || percent.abs() < 0.05 {
+            "no change in size".to_string()
+        } else if percent > 0.0 {
+            format!("{percent:.1}% smaller")
+        } else {
+            format!("{:.1}% larger", -percent)
+        }
+    }
+}
+
+impl ResizeFilter {
+    /// The matching `image` crate filter
+    pub fn filter_type(&self) -> image::imageops::FilterType {
+        use image::imageops::FilterType;
+
+        match self {
+            ResizeFilter::Nearest => FilterType::Nearest,
+            ResizeFilter::Triangle => FilterType::Triangle,
+            ResizeFilter::CatmullRom => FilterType::CatmullRom,
+            ResizeFilter::Gaussian => FilterType::Gaussian,
+            ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// What to do when several inputs map to the same flattened output path
+#[derive(Debug, Clone, PartialEq)]
+pub enum CollisionPolicy {
+    /// Keep the first name and suffix the others (`logo.webp`, `logo_2.webp`; default)
+    Rename,
+    /// Abort the run and list the conflicting inputs
+    Error,
+}
+
+/// Order in which the prescanned input files are converted and numbered
+#[derive(Debug, Clone, PartialEq)]
+pub enum SortOrder {
+    /// Keep the directory walk order, which depends on the platform and filesystem (default)
+    None,
+    /// Sort by full path, byte-wise
+    PathAlphabetical,
+    /// Smallest files first (ties sorted by path)
+    SizeAscending,
+    /// Largest files first (ties sorted by path)
+    SizeDescending,
 }
 
 /// How to handle input files after successful conversion
@@ -82,28 +290,410 @@ pub enum ReplaceInputMode {
     Delete,
 }
 
+/// What happens to a source that could not be moved to the recycle bin
+///
+/// Recycling fails e.g. on headless Linux without a trash directory or on network
+/// shares. The source is never deleted instead.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum RecycleFallback {
+    /// Keep the source and add a warning to the report (default)
+    #[default]
+    Keep,
+    /// Move the source into this directory, mirroring its path below the input directory
+    Quarantine(PathBuf),
+}
+
+impl std::str::FromStr for ReportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "json" => Ok(ReportFormat::Json),
+            "csv" => Ok(ReportFormat::Csv),
+            "html" => Ok(ReportFormat::Html),
+            "jsonl" | "json-lines" | "ndjson" => Ok(ReportFormat::JsonLines),
+            "xml" => Ok(ReportFormat::Xml),
+            "text" | "txt" => Ok(ReportFormat::Text),
+            _ => {
+                anyhow::bail!(
+                    "Unknown report format '{value}' (expected json, csv, html, jsonl, xml or text)"
+                )
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for CompressionMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "lossless" => Ok(CompressionMode::Lossless),
+            "lossy" => Ok(CompressionMode::Lossy),
+            "auto" => Ok(CompressionMode::Auto),
+            "near-lossless" | "near_lossless" => Ok(CompressionMode::NearLossless),
+            "smallest" => Ok(CompressionMode::Smallest),
+            _ => anyhow::bail!(
+                "Unknown compression mode '{value}' (expected lossless, lossy, auto, near-lossless or smallest)"
+            ),
+        }
+    }
+}
+
+impl std::str::FromStr for ResizeFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "nearest" => Ok(ResizeFilter::Nearest),
+            "triangle" => Ok(ResizeFilter::Triangle),
+            "catmull-rom" | "catmullrom" => Ok(ResizeFilter::CatmullRom),
+            "gaussian" => Ok(ResizeFilter::Gaussian),
+            "lanczos3" => Ok(ResizeFilter::Lanczos3),
+            _ => anyhow::bail!(
+                "Unknown resize filter '{value}' (expected nearest, triangle, catmull-rom, gaussian or lanczos3)"
+            ),
+        }
+    }
+}
+
+impl std::str::FromStr for IcoSizeSelection {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "largest" => Ok(IcoSizeSelection::Largest),
+            "smallest" => Ok(IcoSizeSelection::Smallest),
+            "all" => Ok(IcoSizeSelection::All),
+            _ => anyhow::bail!(
+                "Unknown ICO size selection '{value}' (expected largest, smallest or all)"
+            ),
+        }
+    }
+}
+
+impl std::str::FromStr for TiffPageSelection {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "first" => Ok(TiffPageSelection::First),
+            "all" => Ok(TiffPageSelection::All),
+            _ => anyhow::bail!("Unknown TIFF page selection '{value}' (expected first or all)"),
+        }
+    }
+}
+
+impl std::str::FromStr for CollisionPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "rename" => Ok(CollisionPolicy::Rename),
+            "error" => Ok(CollisionPolicy::Error),
+            _ => anyhow::bail!("Unknown collision policy '{value}' (expected rename or error)"),
+        }
+    }
+}
+
+impl std::str::FromStr for SortOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "none" => Ok(SortOrder::None),
+            "path" | "path-alphabetical" => Ok(SortOrder::PathAlphabetical),
+            "size-asc" | "size-ascending" => Ok(SortOrder::SizeAscending),
+            "size-desc" | "size-descending" => Ok(SortOrder::SizeDescending),
+            _ => anyhow::bail!(
+                "Unknown sort order '{value}' (expected none, path, size-asc or size-desc)"
+            ),
+        }
+    }
+}
+
+impl std::str::FromStr for ReplaceInputMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "off" => Ok(ReplaceInputMode::Off),
+            "recycle" => Ok(ReplaceInputMode::Recycle),
+            "delete" => Ok(ReplaceInputMode::Delete),
+            _ => anyhow::bail!(
+                "Unknown replace_input mode '{value}' (expected off, recycle or delete)"
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ReportFormat::Json => "json",
+            ReportFormat::Csv => "csv",
+            ReportFormat::Html => "html",
+            ReportFormat::JsonLines => "jsonl",
+            ReportFormat::Xml => "xml",
+            ReportFormat::Text => "text",
+        })
+    }
+}
+
+impl std::fmt::Display for CompressionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CompressionMode::Lossless => "lossless",
+            CompressionMode::Lossy => "lossy",
+            CompressionMode::Auto => "auto",
+            CompressionMode::NearLossless => "near-lossless",
+            CompressionMode::Smallest => "smallest",
+        })
+    }
+}
+
+impl std::fmt::Display for ReplaceInputMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ReplaceInputMode::Off => "off",
+            ReplaceInputMode::Recycle => "recycle",
+            ReplaceInputMode::Delete => "delete",
+        })
+    }
+}
+
+/// Additional settings controlling how reports are written
+#[derive(Debug, Clone, Default)]
+pub struct ReportOptions {
+    /// Write errors to a sibling `*.errors.json` file instead of inlining them (JSON only)
+    pub split_errors: bool,
+    /// Report file or directory; defaults to the report's output directory
+    pub output_path: Option<PathBuf>,
+    /// Insert the run's start time (UTC) into the file name, e.g.
+    /// `webpify_report_20240601_120000.json`, so later runs don't overwrite the report
+    pub timestamp: bool,
+}
+
 /// Generate a conversion report in the specified format
-pub fn generate_report(report: &ConversionReport, format: &ReportFormat) -> Result<()> {
+///
+/// For [`ReportFormat::JsonLines`] the summary line is appended to the per-file lines
+/// written during the run; without such lines the file is replaced by the summary.
+/// `output_path` may name the report file or an existing directory to place it in;
+/// when `None` the report is written to the conversion output directory.
+/// Other formats are written to a temporary file and renamed into place, so an
+/// interrupted write never leaves a truncated report behind.
+/// Returns the path of the written report.
+pub fn generate_report(
+    report: &ConversionReport,
+    format: &ReportFormat,
+    output_path: Option<&Path>,
+) -> Result<PathBuf> {
+    let options = ReportOptions {
+        output_path: output_path.map(Path::to_path_buf),
+        ..Default::default()
+    };
+    generate_report_with_options(report, format, &options)
+}
+
+/// Generate a conversion report in the specified format with additional report options
+pub fn generate_report_with_options(
+    report: &ConversionReport,
+    format: &ReportFormat,
+    options: &ReportOptions,
+) -> Result<PathBuf> {
+    let report_path = report_path(
+        &report.output_dir,
+        format,
+        options.output_path.as_deref(),
+        options.timestamp.then_some(report.start_time),
+    );
+
+    if let Some(parent) = report_path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create report directory: {}", parent.display()))?;
+    }
+
     match format {
-        ReportFormat::Json => generate_json_report(report),
-        ReportFormat::Csv => generate_csv_report(report),
-        ReportFormat::Html => generate_html_report(report),
+        ReportFormat::Json => generate_json_report(report, options, &report_path),
+        ReportFormat::Csv => generate_csv_report(report, &report_path)
+            .and_then(|()| generate_csv_files_report(report, &report_path)),
+        ReportFormat::Html => generate_html_report(report, &report_path),
+        ReportFormat::JsonLines => generate_json_lines_report(report, &report_path),
+        ReportFormat::Xml => generate_xml_report(report, &report_path),
+        ReportFormat::Text => generate_text_report(report, &report_path),
+    }
+    .with_context(|| format!("Failed to write report: {}", report_path.display()))?;
+
+    Ok(report_path)
+}
+
+/// Resolve where a report of `format` should be written
+///
+/// With a `timestamp`, it is inserted before the extension of the file name, also when
+/// `output_path` names the file.
+pub(crate) fn report_path(
+    output_dir: &Path,
+    format: &ReportFormat,
+    output_path: Option<&Path>,
+    timestamp: Option<DateTime<Utc>>,
+) -> PathBuf {
+    let file_name = match format {
+        ReportFormat::Json => "webpify_report.json",
+        ReportFormat::Csv => "webpify_report.csv",
+        ReportFormat::Html => "webpify_report.html",
+        ReportFormat::JsonLines => "webpify_report.jsonl",
+        ReportFormat::Xml => "webpify_report.xml",
+        ReportFormat::Text => "webpify_report.txt",
+    };
+
+    let path = match output_path {
+        Some(path) if path.is_dir() => path.join(file_name),
+        Some(path) => path.to_path_buf(),
+        None => output_dir.join(file_name),
+    };
+
+    let Some(timestamp) = timestamp else {
+        return path;
+    };
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut timestamped = format!("{}_{}", stem, timestamp.format("%Y%m%d_%H%M%S"));
+    if let Some(extension) = path.extension() {
+        timestamped.push('.');
+        timestamped.push_str(&extension.to_string_lossy());
+    }
+    path.with_file_name(timestamped)
+}
+
+/// A single line of a JSON Lines report
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JsonLinesRecord<'a> {
+    File {
+        path: &'a Path,
+        status: &'a str,
+        original_size: u64,
+        compressed_size: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error_kind: Option<ErrorKind>,
+    },
+    Summary(&'a ConversionReport),
+}
+
+/// Appends per-file lines to a JSON Lines report while the conversion runs
+///
+/// Every line is flushed as soon as it is written, so the results of an interrupted
+/// run are still on disk.
+pub(crate) struct JsonLinesWriter {
+    path: PathBuf,
+    file: Mutex<std::io::LineWriter<std::fs::File>>,
+}
+
+impl JsonLinesWriter {
+    /// Create (or truncate) the report file at `path`
+    pub(crate) fn create(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).with_context(|| {
+                format!("Failed to create report directory: {}", parent.display())
+            })?;
+        }
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Failed to create report: {}", path.display()))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(std::io::LineWriter::new(file)),
+        })
+    }
+
+    /// The report file the lines are written to
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append the result for one file; `error` is set when the file failed
+    pub(crate) fn write_file(
+        &self,
+        path: &Path,
+        status: &str,
+        original_size: u64,
+        compressed_size: u64,
+        error: Option<(ErrorKind, &str)>,
+    ) -> Result<()> {
+        let line = serde_json::to_string(&JsonLinesRecord::File {
+            path,
+            status,
+            original_size,
+            compressed_size,
+            error: error.map(|(_, message)| message),
+            error_kind: error.map(|(kind, _)| kind),
+        })?;
+
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Report writer lock poisoned"))?;
+        writeln!(file, "{line}")?;
+        Ok(())
     }
 }
 
-fn generate_json_report(report: &ConversionReport) -> Result<()> {
-    let json = serde_json::to_string_pretty(report)?;
-    let report_path = "webpify_report.json";
-    std::fs::write(report_path, json)?;
-    println!("Report saved to: {report_path}");
+fn generate_json_lines_report(report: &ConversionReport, report_path: &Path) -> Result<()> {
+    // Only the file this run streamed to holds its per-file lines; anything else at the
+    // path is left over from an earlier run
+    let streamed = report.json_lines_stream.as_deref() == Some(report_path);
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(streamed)
+        .truncate(!streamed)
+        .open(report_path)?;
+
+    writeln!(
+        file,
+        "{}",
+        serde_json::to_string(&JsonLinesRecord::Summary(report))?
+    )?;
     Ok(())
 }
 
-fn generate_csv_report(report: &ConversionReport) -> Result<()> {
-    use std::io::Write;
+fn generate_json_report(
+    report: &ConversionReport,
+    options: &ReportOptions,
+    report_path: &Path,
+) -> Result<()> {
+    let json = if options.split_errors {
+        let errors_path = report_path.with_extension("errors.json");
+        utils::write_atomically(
+            &errors_path,
+            serde_json::to_string_pretty(&report.errors)?.as_bytes(),
+        )?;
 
-    let report_path = "webpify_report.csv";
-    let mut file = std::fs::File::create(report_path)?;
+        // Keep the main report lean: replace the inline error list with a count and a link
+        let mut value = serde_json::to_value(report)?;
+        if let Some(object) = value.as_object_mut() {
+            object.remove("errors");
+            object.insert("error_count".to_string(), report.errors.len().into());
+            object.insert(
+                "errors_file".to_string(),
+                errors_path.display().to_string().into(),
+            );
+        }
+        serde_json::to_string_pretty(&value)?
+    } else {
+        serde_json::to_string_pretty(report)?
+    };
+
+    utils::write_atomically(report_path, json.as_bytes())?;
+    Ok(())
+}
+
+fn generate_csv_report(report: &ConversionReport, report_path: &Path) -> Result<()> {
+    let mut file = Vec::new();
 
     // Write CSV header
     writeln!(file, "metric,value")?;
@@ -124,24 +714,203 @@ fn generate_csv_report(report: &ConversionReport) -> Result<()> {
     writeln!(file, "processed_files,{}", report.processed_files)?;
     writeln!(file, "failed_files,{}", report.failed_files)?;
     writeln!(file, "skipped_files,{}", report.skipped_files)?;
+    writeln!(file, "renamed_outputs,{}", report.renamed_outputs)?;
+    writeln!(file, "kept_originals,{}", report.kept_originals)?;
+    writeln!(file, "webp_reencoded,{}", report.webp_reencoded)?;
+    writeln!(file, "webp_kept,{}", report.webp_kept)?;
+    writeln!(file, "passed_through,{}", report.passed_through)?;
+    let mut error_counts: Vec<_> = report.error_counts.iter().collect();
+    error_counts.sort_by_key(|(kind, _)| kind.to_string());
+    for (kind, count) in error_counts {
+        let key = serde_json::to_value(kind)?;
+        writeln!(
+            file,
+            "errors_{},{}",
+            key.as_str().unwrap_or_default(),
+            count
+        )?;
+    }
+    let mut skip_reasons: Vec<_> = report.skip_reasons.iter().collect();
+    skip_reasons.sort_by_key(|(reason, _)| reason.to_string());
+    for (reason, count) in skip_reasons {
+        let key = serde_json::to_value(reason)?;
+        writeln!(
+            file,
+            "skipped_{},{}",
+            key.as_str().unwrap_or_default(),
+            count
+        )?;
+    }
     writeln!(file, "original_size_bytes,{}", report.original_size)?;
     writeln!(file, "compressed_size_bytes,{}", report.compressed_size)?;
-    writeln!(file, "compression_ratio,{:.2}", report.compression_ratio)?;
+    writeln!(file, "space_saved_ratio,{:.4}", report.space_saved_ratio)?;
+    writeln!(file, "size_ratio,{:.4}", report.size_ratio)?;
     writeln!(file, "files_per_second,{:.2}", report.files_per_second)?;
     writeln!(file, "bytes_per_second,{}", report.bytes_per_second)?;
     writeln!(file, "thread_count,{}", report.thread_count)?;
     writeln!(file, "quality,{}", report.quality)?;
     writeln!(file, "mode,{}", report.mode)?;
+    let mut format_overrides: Vec<_> = report.format_overrides.iter().collect();
+    format_overrides.sort_by_key(|(extension, _)| extension.as_str());
+    for (extension, usage) in format_overrides {
+        writeln!(
+            file,
+            "override_{},{} q{} ({} files)",
+            extension, usage.mode, usage.quality, usage.files
+        )?;
+    }
+    writeln!(file, "cancelled,{}", report.cancelled)?;
+    if let Some(file_limit) = report.file_limit {
+        writeln!(file, "file_limit,{}", file_limit)?;
+    }
+    writeln!(file, "dry_run,{}", report.dry_run)?;
+    writeln!(
+        file,
+        "compressed_size_unknown,{}",
+        report.compressed_size_unknown
+    )?;
+
+    utils::write_atomically(report_path, &file)?;
+    Ok(())
+}
+
+/// Write per-file rows to a sibling `*.files.csv`, if per-file results were collected
+fn generate_csv_files_report(report: &ConversionReport, report_path: &Path) -> Result<()> {
+    if report.files.is_empty() {
+        return Ok(());
+    }
 
-    println!("Report saved to: {report_path}");
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record([
+        "path",
+        "format",
+        "original_bytes",
+        "output_bytes",
+        "ratio",
+        "status",
+        "error",
+    ])?;
+    for file in &report.files {
+        let ratio = if file.original_bytes == 0 || file.status == FileStatus::Failed {
+            String::new()
+        } else {
+            format!(
+                "{:.4}",
+                1.0 - file.output_bytes as f64 / file.original_bytes as f64
+            )
+        };
+        let status = serde_json::to_value(file.status)?;
+        writer.write_record([
+            file.path.display().to_string(),
+            file.format.clone(),
+            file.original_bytes.to_string(),
+            file.output_bytes.to_string(),
+            ratio,
+            status.as_str().unwrap_or_default().to_string(),
+            file.error.clone().unwrap_or_default(),
+        ])?;
+    }
+    let contents = writer.into_inner().map_err(|err| err.into_error())?;
+    utils::write_atomically(&report_path.with_extension("files.csv"), &contents)?;
     Ok(())
 }
 
-fn generate_html_report(report: &ConversionReport) -> Result<()> {
+fn generate_html_report(report: &ConversionReport, report_path: &Path) -> Result<()> {
+    use humansize::{DECIMAL, format_size};
+
+    // Input formats, most common first
+    let mut formats: Vec<_> = report.format_stats.iter().collect();
+    formats.sort_by(|(a_name, a_count), (b_name, b_count)| {
+        b_count.cmp(a_count).then(a_name.cmp(b_name))
+    });
+    let format_rows: String = formats
+        .iter()
+        .map(|(format, count)| {
+            let (mode, quality) = report
+                .format_overrides
+                .get(*format)
+                .map_or((&report.mode, report.quality), |usage| {
+                    (&usage.mode, usage.quality)
+                });
+            format!(
+                "\n            <tr><td>{}</td><td>{}</td><td>{} q{}</td></tr>",
+                escape_html(format),
+                count,
+                escape_html(mode),
+                quality
+            )
+        })
+        .collect();
+
+    let errors_section = if report.errors.is_empty() {
+        String::new()
+    } else {
+        let items: String = report
+            .errors
+            .iter()
+            .map(|error| format!("\n            <li>{}</li>", escape_html(error)))
+            .collect();
+        format!(
+            r#"
+    <h2>Errors ({})</h2>
+    <div class="errors">
+        <ul>{}
+        </ul>
+    </div>"#,
+            report.errors.len(),
+            items
+        )
+    };
+
+    let skip_reasons_section = if report.skip_reasons.is_empty() {
+        String::new()
+    } else {
+        let mut skip_reasons: Vec<_> = report.skip_reasons.iter().collect();
+        skip_reasons.sort_by(|(a_reason, a_count), (b_reason, b_count)| {
+            b_count
+                .cmp(a_count)
+                .then(a_reason.to_string().cmp(&b_reason.to_string()))
+        });
+        let rows: String = skip_reasons
+            .iter()
+            .map(|(reason, count)| {
+                format!("\n            <tr><td>{reason}</td><td>{count}</td></tr>")
+            })
+            .collect();
+        format!(
+            r#"
+    <h2>Files Not Converted</h2>
+    <table>
+        <thead>
+            <tr><th>Reason</th><th>Files</th></tr>
+        </thead>
+        <tbody>{rows}
+        </tbody>
+    </table>"#
+        )
+    };
+
+    let dry_run_notice = match (report.dry_run, report.compressed_size_unknown) {
+        (false, _) => "",
+        (true, false) => {
+            "\n    <p class=\"notice\"><strong>Dry run:</strong> no files were written; sizes come from encoding in memory.</p>"
+        }
+        (true, true) => {
+            "\n    <p class=\"notice\"><strong>Dry run:</strong> no files were written and output sizes were not measured.</p>"
+        }
+    };
+
+    let file_limit_notice = report.file_limit.map_or(String::new(), |file_limit| {
+        format!(
+            "\n    <p class=\"notice\"><strong>Limited run:</strong> only the first {file_limit} matching files were converted.</p>"
+        )
+    });
+
     let html = format!(
         r#"<!DOCTYPE html>
 <html>
 <head>
+    <meta charset="utf-8">
     <title>Webpify Conversion Report</title>
     <style>
         body {{ font-family: Arial, sans-serif; margin: 40px; }}
@@ -150,34 +919,537 @@ fn generate_html_report(report: &ConversionReport) -> Result<()> {
         .metric {{ margin: 10px 0; }}
         .success {{ color: #27ae60; }}
         .error {{ color: #e74c3c; }}
+        table {{ border-collapse: collapse; margin-top: 10px; }}
+        th, td {{ border: 1px solid #ddd; padding: 6px 12px; text-align: left; }}
+        .notice {{ background: #fff8e1; padding: 10px; border-radius: 5px; }}
+        .errors {{ max-height: 400px; overflow-y: auto; background: #fdf2f2; padding: 10px; border-radius: 5px; }}
     </style>
 </head>
 <body>
-    <h1 class="header">Webpify Conversion Report</h1>
+    <h1 class="header">Webpify Conversion Report</h1>{}{}
     <div class="stats">
+        <div class="metric"><strong>Input Directory:</strong> {}</div>
+        <div class="metric"><strong>Output Directory:</strong> {}</div>
         <div class="metric"><strong>Duration:</strong> {} seconds</div>
         <div class="metric"><strong>Files Processed:</strong> <span class="success">{}</span></div>
         <div class="metric"><strong>Files Failed:</strong> <span class="error">{}</span></div>
         <div class="metric"><strong>Files Skipped:</strong> {}</div>
-        <div class="metric"><strong>Compression Ratio:</strong> {:.2}%</div>
+        <div class="metric"><strong>Original Size:</strong> {}</div>
+        <div class="metric"><strong>Compressed Size:</strong> {}</div>
+        <div class="metric"><strong>Size Change:</strong> {}</div>
         <div class="metric"><strong>Processing Speed:</strong> {:.2} files/sec</div>
         <div class="metric"><strong>Quality:</strong> {}</div>
-        <div class="metric"><strong>Mode:</strong> {}</div>
+        <div class="metric"><strong>Mode:</strong> {}</div>{}
     </div>
+    <h2>Input Formats</h2>
+    <table>
+        <thead>
+            <tr><th>Format</th><th>Files</th><th>Settings</th></tr>
+        </thead>
+        <tbody>{}
+        </tbody>
+    </table>{}{}
 </body>
 </html>"#,
+        dry_run_notice,
+        file_limit_notice,
+        escape_html(&report.input_dir.display().to_string()),
+        escape_html(&report.output_dir.display().to_string()),
         report.duration.as_secs(),
         report.processed_files,
         report.failed_files,
         report.skipped_files,
-        report.compression_ratio * 100.0,
+        format_size(report.original_size, DECIMAL),
+        if report.compressed_size_unknown {
+            "not measured".to_string()
+        } else {
+            format_size(report.compressed_size, DECIMAL)
+        },
+        if report.compressed_size_unknown {
+            "not measured".to_string()
+        } else {
+            format!(
+                "{} ({:.2}% of the original size)",
+                report.size_change_summary(),
+                report.size_ratio * 100.0
+            )
+        },
         report.files_per_second,
         report.quality,
-        report.mode
+        escape_html(&report.mode),
+        if report.cancelled {
+            "\n        <div class=\"metric error\"><strong>Cancelled before all files were converted</strong></div>"
+        } else {
+            ""
+        },
+        format_rows,
+        skip_reasons_section,
+        errors_section
     );
 
-    let report_path = "webpify_report.html";
-    std::fs::write(report_path, html)?;
-    println!("Report saved to: {report_path}");
+    utils::write_atomically(report_path, html.as_bytes())?;
     Ok(())
 }
+
+/// Write the report as XML
+///
+/// The schema is stable; new elements are only ever added, never renamed or removed:
+///
+/// ```xml
+/// <?xml version="1.0" encoding="UTF-8"?>
+/// <webpifyReport version="1">
+///   <startTime>2024-01-01T12:00:00Z</startTime>   <!-- RFC 3339, also endTime -->
+///   <durationSeconds>12.5</durationSeconds>
+///   <inputDir>…</inputDir> <outputDir>…</outputDir>
+///   <totalFiles/> <processedFiles/> <failedFiles/> <skippedFiles/> <renamedOutputs/>
+///   <keptOriginals/> <webpReencoded/> <webpKept/> <passedThrough/>
+///   <originalSize/> <compressedSize/>
+///   <spaceSavedRatio/> <sizeRatio/>   <!-- 1 - compressed/original, compressed/original -->
+///   <filesPerSecond/> <bytesPerSecond/> <threadCount/> <quality/> <mode/>
+///   <cancelled/> <dryRun/> <compressedSizeUnknown/>   <!-- sizes in bytes, true/false -->
+///   <fileLimit/>   <!-- only when the file cap left matching files out -->
+///   <formatStats>
+///     <format name="png" files="12" mode="lossless" quality="100"/>
+///   </formatStats>
+///   <errorCounts>
+///     <errorCount kind="decode" files="2"/>
+///   </errorCounts>
+///   <skipReasons>   <!-- includes files left out by the scan filters -->
+///     <skipReason reason="below_min_size" files="5"/>
+///   </skipReasons>
+///   <errors count="2">
+///     <error>…</error>
+///   </errors>
+///   <files>   <!-- only when per-file results were collected -->
+///     <file path="…" format="png" originalBytes="1024" outputBytes="512" status="converted">
+///       <error>…</error>   <!-- failed files only -->
+///     </file>
+///   </files>
+/// </webpifyReport>
+/// ```
+fn generate_xml_report(report: &ConversionReport, report_path: &Path) -> Result<()> {
+    use quick_xml::Writer;
+    use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+
+    fn text_element<W: Write>(writer: &mut Writer<W>, name: &str, text: &str) -> Result<()> {
+        writer.write_event(Event::Start(BytesStart::new(name)))?;
+        writer.write_event(Event::Text(BytesText::new(&xml_text(text))))?;
+        writer.write_event(Event::End(BytesEnd::new(name)))?;
+        Ok(())
+    }
+
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    let mut root = BytesStart::new("webpifyReport");
+    root.push_attribute(("version", "1"));
+    writer.write_event(Event::Start(root))?;
+
+    let summary = [
+        ("startTime", report.start_time.to_rfc3339()),
+        ("endTime", report.end_time.to_rfc3339()),
+        ("durationSeconds", report.duration.as_secs_f64().to_string()),
+        ("inputDir", report.input_dir.display().to_string()),
+        ("outputDir", report.output_dir.display().to_string()),
+        ("totalFiles", report.total_files.to_string()),
+        ("processedFiles", report.processed_files.to_string()),
+        ("failedFiles", report.failed_files.to_string()),
+        ("skippedFiles", report.skipped_files.to_string()),
+        ("renamedOutputs", report.renamed_outputs.to_string()),
+        ("keptOriginals", report.kept_originals.to_string()),
+        ("webpReencoded", report.webp_reencoded.to_string()),
+        ("webpKept", report.webp_kept.to_string()),
+        ("passedThrough", report.passed_through.to_string()),
+        ("originalSize", report.original_size.to_string()),
+        ("compressedSize", report.compressed_size.to_string()),
+        (
+            "spaceSavedRatio",
+            format!("{:.4}", report.space_saved_ratio),
+        ),
+        ("sizeRatio", format!("{:.4}", report.size_ratio)),
+        ("filesPerSecond", format!("{:.2}", report.files_per_second)),
+        ("bytesPerSecond", report.bytes_per_second.to_string()),
+        ("threadCount", report.thread_count.to_string()),
+        ("quality", report.quality.to_string()),
+        ("mode", report.mode.clone()),
+        ("cancelled", report.cancelled.to_string()),
+        ("dryRun", report.dry_run.to_string()),
+        (
+            "compressedSizeUnknown",
+            report.compressed_size_unknown.to_string(),
+        ),
+    ];
+    for (name, value) in &summary {
+        text_element(&mut writer, name, value)?;
+    }
+    if let Some(file_limit) = report.file_limit {
+        text_element(&mut writer, "fileLimit", &file_limit.to_string())?;
+    }
+
+    // Sorted so reports of the same run compare equal
+    let mut formats: Vec<_> = report.format_stats.iter().collect();
+    formats.sort_by_key(|(format, _)| format.as_str());
+    writer.write_event(Event::Start(BytesStart::new("formatStats")))?;
+    for (format, count) in formats {
+        let (mode, quality) = report
+            .format_overrides
+            .get(format)
+            .map_or((&report.mode, report.quality), |usage| {
+                (&usage.mode, usage.quality)
+            });
+        let mut element = BytesStart::new("format");
+        element.push_attribute(("name", &*xml_text(format)));
+        element.push_attribute(("files", count.to_string().as_str()));
+        element.push_attribute(("mode", &*xml_text(mode)));
+        element.push_attribute(("quality", quality.to_string().as_str()));
+        writer.write_event(Event::Empty(element))?;
+    }
+    writer.write_event(Event::End(BytesEnd::new("formatStats")))?;
+
+    let mut error_counts: Vec<_> = report.error_counts.iter().collect();
+    error_counts.sort_by_key(|(kind, _)| kind.to_string());
+    writer.write_event(Event::Start(BytesStart::new("errorCounts")))?;
+    for (kind, count) in error_counts {
+        let key = serde_json::to_value(kind)?;
+        let mut element = BytesStart::new("errorCount");
+        element.push_attribute(("kind", key.as_str().unwrap_or_default()));
+        element.push_attribute(("files", count.to_string().as_str()));
+        writer.write_event(Event::Empty(element))?;
+    }
+    writer.write_event(Event::End(BytesEnd::new("errorCounts")))?;
+
+    let mut skip_reasons: Vec<_> = report.skip_reasons.iter().collect();
+    skip_reasons.sort_by_key(|(reason, _)| reason.to_string());
+    writer.write_event(Event::Start(BytesStart::new("skipReasons")))?;
+    for (reason, count) in skip_reasons {
+        let key = serde_json::to_value(reason)?;
+        let mut element = BytesStart::new("skipReason");
+        element.push_attribute(("reason", key.as_str().unwrap_or_default()));
+        element.push_attribute(("files", count.to_string().as_str()));
+        writer.write_event(Event::Empty(element))?;
+    }
+    writer.write_event(Event::End(BytesEnd::new("skipReasons")))?;
+
+    let mut errors = BytesStart::new("errors");
+    errors.push_attribute(("count", report.errors.len().to_string().as_str()));
+    writer.write_event(Event::Start(errors))?;
+    for error in &report.errors {
+        text_element(&mut writer, "error", error)?;
+    }
+    writer.write_event(Event::End(BytesEnd::new("errors")))?;
+
+    if !report.files.is_empty() {
+        writer.write_event(Event::Start(BytesStart::new("files")))?;
+        for file in &report.files {
+            let status = serde_json::to_value(file.status)?;
+            let mut element = BytesStart::new("file");
+            let path = file.path.display().to_string();
+            element.push_attribute(("path", &*xml_text(&path)));
+            element.push_attribute(("format", &*xml_text(&file.format)));
+            element.push_attribute(("originalBytes", file.original_bytes.to_string().as_str()));
+            element.push_attribute(("outputBytes", file.output_bytes.to_string().as_str()));
+            element.push_attribute(("status", status.as_str().unwrap_or_default()));
+            match &file.error {
+                Some(error) => {
+                    writer.write_event(Event::Start(element))?;
+                    text_element(&mut writer, "error", error)?;
+                    writer.write_event(Event::End(BytesEnd::new("file")))?;
+                }
+                None => writer.write_event(Event::Empty(element))?,
+            }
+        }
+        writer.write_event(Event::End(BytesEnd::new("files")))?;
+    }
+
+    writer.write_event(Event::End(BytesEnd::new("webpifyReport")))?;
+    utils::write_atomically(report_path, &writer.into_inner())?;
+    Ok(())
+}
+
+fn generate_text_report(report: &ConversionReport, report_path: &Path) -> Result<()> {
+    let header = format!(
+        "Webpify Conversion Report\nStarted: {}\nInput: {}\nOutput: {}\n\n",
+        report.start_time.format("%Y-%m-%d %H:%M:%S UTC"),
+        report.input_dir.display(),
+        report.output_dir.display()
+    );
+    let text = header + &format_summary(report, None);
+    utils::write_atomically(report_path, text.as_bytes())?;
+    Ok(())
+}
+
+/// Human-readable summary of a run, as printed by the CLI and saved by text reports
+///
+/// Lists at most `max_errors` errors (all when `None`); longer lists are replaced by
+/// their count.
+pub fn format_summary(report: &ConversionReport, max_errors: Option<usize>) -> String {
+    let mut summary = String::new();
+    write_summary(&mut summary, report, max_errors).expect("writing to a String cannot fail");
+    summary
+}
+
+fn write_summary(
+    out: &mut impl std::fmt::Write,
+    report: &ConversionReport,
+    max_errors: Option<usize>,
+) -> std::fmt::Result {
+    use humansize::{DECIMAL, format_size};
+
+    if report.dry_run {
+        writeln!(out, "🔍 Dry run completed - no files were written")?;
+    } else {
+        writeln!(out, "🎉 Conversion completed!")?;
+    }
+    if let Some(file_limit) = report.file_limit {
+        writeln!(
+            out,
+            "✂️ Limited to the first {file_limit} matching files (--max-files)"
+        )?;
+    }
+    writeln!(out, "📊 Results Summary:")?;
+    writeln!(out, "  ✅ Processed: {} files", report.processed_files)?;
+    if report.failed_files > 0 {
+        writeln!(out, "  ❌ Failed: {} files", report.failed_files)?;
+        let mut counts: Vec<String> = report
+            .error_counts
+            .iter()
+            .map(|(kind, count)| format!("{kind}: {count}"))
+            .collect();
+        counts.sort();
+        if !counts.is_empty() {
+            writeln!(out, "     ({})", counts.join(", "))?;
+        }
+    }
+    if report.skipped_files > 0 {
+        writeln!(out, "  ⏭️ Skipped: {} files", report.skipped_files)?;
+    }
+    if !report.skip_reasons.is_empty() {
+        let mut reasons: Vec<String> = report
+            .skip_reasons
+            .iter()
+            .map(|(reason, count)| format!("{reason}: {count}"))
+            .collect();
+        reasons.sort();
+        writeln!(out, "  🔎 Not converted: {}", reasons.join(", "))?;
+    }
+    if report.renamed_outputs > 0 {
+        writeln!(
+            out,
+            "  🔀 Renamed: {} files (output name collisions)",
+            report.renamed_outputs
+        )?;
+    }
+    if report.kept_originals > 0 {
+        writeln!(
+            out,
+            "  📎 Kept original: {} files (WebP would be larger)",
+            report.kept_originals
+        )?;
+    }
+    if report.passed_through > 0 {
+        writeln!(
+            out,
+            "  📄 Copied unchanged: {} files (below the passthrough size), {} converted",
+            report.passed_through,
+            report.processed_files.saturating_sub(report.passed_through)
+        )?;
+    }
+    if report.webp_reencoded + report.webp_kept > 0 {
+        writeln!(
+            out,
+            "  🔄 WebP inputs: {} re-encoded, {} kept (re-encode not smaller)",
+            report.webp_reencoded, report.webp_kept
+        )?;
+    }
+
+    if report.original_size > 0 {
+        writeln!(out, "\n💾 Space Analysis:")?;
+        writeln!(
+            out,
+            "  📦 Original size: {}",
+            format_size(report.original_size, DECIMAL)
+        )?;
+        if report.compressed_size_unknown {
+            writeln!(
+                out,
+                "  🗜️ Compressed size: not measured (use --dry-run-accurate)"
+            )?;
+        } else {
+            writeln!(
+                out,
+                "  🗜️ Compressed size: {}",
+                format_size(report.compressed_size, DECIMAL)
+            )?;
+            writeln!(out, "  💾 Size change: {}", report.size_change_summary())?;
+        }
+    }
+
+    writeln!(out, "\n⏱️ Performance:")?;
+    writeln!(out, "  🕐 Duration: {:.1}s", report.duration.as_secs_f64())?;
+    writeln!(out, "  🚀 Speed: {:.1} files/sec", report.files_per_second)?;
+    writeln!(out, "  🧵 Threads used: {}", report.thread_count)?;
+
+    if max_errors.is_some_and(|max_errors| report.errors.len() > max_errors) {
+        writeln!(
+            out,
+            "\n❌ {} errors occurred (use --report for full details)",
+            report.errors.len()
+        )?;
+    } else if !report.errors.is_empty() {
+        writeln!(out, "\n❌ Errors:")?;
+        for error in &report.errors {
+            writeln!(out, "  • {error}")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Replace characters XML 1.0 can't represent even escaped (control characters other
+/// than tab and newlines); quick-xml escapes `&`, `<`, `>` and quotes itself
+fn xml_text(text: &str) -> std::borrow::Cow<'_, str> {
+    let is_invalid = |c: char| c.is_control() && !matches!(c, '\t' | '\n' | '\r');
+    if text.chars().any(is_invalid) {
+        text.chars()
+            .map(|c| if is_invalid(c) { '\u{FFFD}' } else { c })
+            .collect::<String>()
+            .into()
+    } else {
+        text.into()
+    }
+}
+
+/// Escape text for use in HTML element content and attribute values
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh empty directory for one test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("webpify-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Report of converting one small PNG in `dir`, with `errors` recorded on it
+    fn sample_report(dir: &Path, errors: &[&str]) -> ConversionReport {
+        image::RgbImage::from_fn(32, 24, |x, y| {
+            image::Rgb([(x * 7) as u8, (y * 5) as u8, ((x + y) * 3) as u8])
+        })
+        .save(dir.join("photo.png"))
+        .unwrap();
+        let options = ConversionOptions::new(dir.to_path_buf())
+            .with_output_dir(dir.join("out"))
+            .with_track_files(true);
+        let mut report = WebpifyCore::new(options).run().unwrap();
+        report.errors = errors.iter().map(|error| error.to_string()).collect();
+        report
+    }
+
+    #[test]
+    fn split_errors_write_a_lean_report_and_an_errors_file() {
+        let dir = temp_dir("split-report-errors");
+        let errors = ["broken.png: truncated", "locked.jpg: permission denied"];
+        let report = sample_report(&dir, &errors);
+
+        let options = ReportOptions {
+            split_errors: true,
+            ..Default::default()
+        };
+        let report_path =
+            generate_report_with_options(&report, &ReportFormat::Json, &options).unwrap();
+
+        let main: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&report_path).unwrap()).unwrap();
+        assert!(main.get("errors").is_none());
+        assert_eq!(main["error_count"], 2);
+        assert_eq!(main["processed_files"], 1);
+
+        let errors_path = PathBuf::from(main["errors_file"].as_str().unwrap());
+        assert_eq!(errors_path, report_path.with_extension("errors.json"));
+        let split: Vec<String> =
+            serde_json::from_slice(&std::fs::read(&errors_path).unwrap()).unwrap();
+        assert_eq!(split, errors);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_failure_after_the_rename_leaves_a_complete_report() {
+        let dir = temp_dir("report-after-rename");
+        let report = sample_report(&dir, &["broken.png: truncated"]);
+        assert!(!report.files.is_empty());
+
+        // The summary is renamed into place before the per-file CSV fails to be written
+        let report_path = dir.join("out/webpify_report.csv");
+        std::fs::create_dir_all(report_path.with_extension("files.csv").join("inside")).unwrap();
+        assert!(generate_report(&report, &ReportFormat::Csv, None).is_err());
+
+        let mut reader = csv::Reader::from_path(&report_path).unwrap();
+        let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+        assert!(
+            rows.iter()
+                .any(|row| &row[0] == "processed_files" && &row[1] == "1")
+        );
+        assert!(rows.iter().any(|row| &row[0] == "compressed_size_unknown"));
+        let leftovers = std::fs::read_dir(dir.join("out"))
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().extension() == Some("tmp".as_ref()))
+            .count();
+        assert_eq!(leftovers, 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn json_lines_reports_hold_one_run_each() {
+        let dir = temp_dir("json-lines-runs");
+        image::RgbImage::from_fn(32, 24, |x, y| {
+            image::Rgb([(x * 7) as u8, (y * 5) as u8, ((x + y) * 3) as u8])
+        })
+        .save(dir.join("photo.png"))
+        .unwrap();
+        let options = ConversionOptions::new(dir.clone())
+            .with_output_dir(dir.join("out"))
+            .with_overwrite(true)
+            .with_generate_report(true)
+            .with_report_format(ReportFormat::JsonLines);
+        let record_types = |path: &Path| -> Vec<String> {
+            std::fs::read_to_string(path)
+                .unwrap()
+                .lines()
+                .map(|line| {
+                    let value: serde_json::Value = serde_json::from_str(line).unwrap();
+                    value["type"].as_str().unwrap().to_string()
+                })
+                .collect()
+        };
+
+        for _ in 0..2 {
+            let report = WebpifyCore::new(options.clone()).run().unwrap();
+            let path = generate_report(&report, &ReportFormat::JsonLines, None).unwrap();
+            assert_eq!(record_types(&path), ["file", "summary"]);
+        }
+
+        // A report exported without a streamed run replaces the old file
+        let report = WebpifyCore::new(options.with_generate_report(false))
+            .run()
+            .unwrap();
+        let path = generate_report(&report, &ReportFormat::JsonLines, None).unwrap();
+        assert_eq!(record_types(&path), ["summary"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,11 +1,16 @@
-use anyhow::Result;
-use clap::{CommandFactory, Parser, ValueEnum};
+use anyhow::{Result, bail};
+use chrono::{DateTime, Utc};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
 use std::path::PathBuf;
+use std::time::Duration;
 
 // Use the library
 use webpify::{
-    CompressionMode, ConversionReport, ReplaceInputMode, ReportFormat, WebpifyCore,
-    config::ConversionOptions, generate_report,
+    CollisionPolicy, CompressionMode, Config, ConversionReport, FileProgressReporter,
+    IcoSizeSelection, RecycleFallback, ReplaceInputMode, ReportFormat, ReportOptions, ResizeFilter,
+    SortOrder, TiffPageSelection, WebpifyCore, config::ConversionOptions, format_summary,
+    generate_report_with_options,
 };
 
 #[cfg(feature = "cli")]
@@ -42,9 +47,17 @@ Features:
                                   \/_/                  \/__/ 
 "#)]
 pub struct Args {
-    /// Input directory path
+    /// Input directory path (repeat to convert several directories in one run)
     #[arg(short, long, value_name = "DIR")]
-    pub input: PathBuf,
+    pub input: Vec<PathBuf>,
+
+    /// Read input file paths from a file, one per line ("-" reads stdin)
+    #[arg(long, value_name = "FILE")]
+    pub input_list: Option<PathBuf>,
+
+    /// Base directory that listed input files are made relative to (defaults to their common ancestor)
+    #[arg(long, value_name = "DIR", requires = "input_list")]
+    pub base_dir: Option<PathBuf>,
 
     /// Output directory path (defaults to input_dir/webp_output)
     #[arg(short, long, value_name = "DIR")]
@@ -62,18 +75,133 @@ pub struct Args {
     #[arg(short, long, default_value = "lossless", value_enum)]
     pub mode: CompressionModeArg,
 
-    /// Supported input formats (defaults to common formats)
-    #[arg(long, value_delimiter = ',', default_values = ["jpg", "jpeg", "png", "gif", "bmp", "tiff", "webp"])]
+    /// Encoder effort from 0 (fastest) to 6 (slowest, smallest files)
+    #[arg(long, default_value = "4", value_name = "0-6", value_parser = clap::value_parser!(u8).range(0..=6))]
+    pub effort: u8,
+
+    /// Alpha channel quality for lossy encoding (0-100, 100 keeps transparency lossless)
+    #[arg(long, default_value = "100", value_name = "QUALITY", value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub alpha_quality: u8,
+
+    /// Lossless compression level from 0 (fastest) to 9 (smallest); overrides --effort for lossless encodes
+    #[arg(long, value_name = "0-9", value_parser = clap::value_parser!(u8).range(0..=9))]
+    pub lossless_level: Option<u8>,
+
+    /// Lossy deblocking filter strength from 0 (off, sharper blocks) to 100 (smoothest) [default: encoder default, 60]
+    #[arg(long, value_name = "0-100", value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub filter_strength: Option<u8>,
+
+    /// Lossy filter sharpness from 0 (smoothest) to 7 (sharpest) [default: encoder default, 0]
+    #[arg(long, value_name = "0-7", value_parser = clap::value_parser!(u8).range(0..=7))]
+    pub sharpness: Option<u8>,
+
+    /// Auto mode encodes images with fewer distinct sampled colors than this losslessly [default: 64]
+    #[arg(long, value_name = "COLORS")]
+    pub auto_color_threshold: Option<usize>,
+
+    /// Number of pixels auto mode samples to count colors [default: 4096]
+    #[arg(long, value_name = "PIXELS", value_parser = clap::value_parser!(u32).range(1..))]
+    pub auto_sample_size: Option<u32>,
+
+    /// Target SSIM for lossy encoding (0-1]; searches the lowest quality per image that reaches it (implies --mode lossy)
+    #[arg(long, value_name = "SSIM", value_parser = parse_target_ssim)]
+    pub target_ssim: Option<f64>,
+
+    /// With --mode smallest, only pick the lossy result when its SSIM reaches this (0-1]
+    #[arg(long, value_name = "SSIM", value_parser = parse_target_ssim)]
+    pub smallest_min_ssim: Option<f64>,
+
+    /// Downscale images so the longest edge is at most this many pixels (smaller images are left as is)
+    #[arg(long, value_name = "PX", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_dimension: Option<u32>,
+
+    /// Resampling filter used when downscaling
+    #[arg(long, value_enum, default_value = "lanczos3")]
+    pub resize_filter: ResizeFilterArg,
+
+    /// Flatten transparent images onto this color, e.g. #ffffff (output is opaque)
+    #[arg(long, value_name = "#RRGGBB", value_parser = parse_color)]
+    pub background: Option<image::Rgba<u8>>,
+
+    /// Supported input formats (defaults to common formats; `all` for every supported one)
+    #[arg(long, value_delimiter = ',', default_values = webpify::config::DEFAULT_FORMATS.iter().copied())]
     pub formats: Vec<String>,
 
+    /// Mode and quality for one input format, e.g. png:lossless:100 (repeatable; others use --mode/--quality)
+    #[arg(long, value_name = "EXT:MODE:QUALITY", value_parser = parse_format_override)]
+    pub format_override: Vec<(String, CompressionMode, u8)>,
+
+    /// Input formats to leave out, e.g. --formats all --exclude-formats gif,ico
+    #[arg(long, value_delimiter = ',', value_name = "FORMATS")]
+    pub exclude_formats: Vec<String>,
+
+    /// Detect input formats from the file header, converting images with a wrong or missing extension
+    #[arg(long)]
+    pub detect_by_content: bool,
+
     /// Overwrite existing files
     #[arg(long)]
     pub overwrite: bool,
 
-    /// Preserve original directory structure
-    #[arg(long, default_value = "true")]
+    /// Copy the original to the output instead when its WebP would be larger
+    #[arg(long, default_value_t = false)]
+    pub keep_smaller: bool,
+
+    /// Give each output the modification time of its source (keeps date sorting intact)
+    #[arg(long, default_value_t = false)]
+    pub preserve_timestamps: bool,
+
+    /// Skip files whose WebP output is newer than the source; re-convert stale outputs
+    #[arg(long, default_value_t = false)]
+    pub incremental: bool,
+
+    /// Keep a content-hash manifest in the output directory and skip unchanged files
+    #[arg(long, default_value_t = false)]
+    pub manifest: bool,
+
+    /// Skip files completed by an interrupted run with the same settings
+    #[arg(long, default_value_t = false)]
+    pub resume: bool,
+
+    /// Preserve original directory structure (use --preserve-structure=false to flatten output)
+    #[arg(
+        long,
+        default_value_t = true,
+        action = clap::ArgAction::Set,
+        num_args = 0..=1,
+        default_missing_value = "true",
+        value_name = "BOOL"
+    )]
     pub preserve_structure: bool,
 
+    /// Write each WebP next to its source instead of into the output directory
+    #[arg(long, default_value_t = false)]
+    pub in_place: bool,
+
+    /// Output file name template with {stem}, {ext}, {parent} and {index} tokens (e.g. "{stem}_optimized.webp")
+    #[arg(long, value_name = "TEMPLATE")]
+    pub filename_template: Option<String>,
+
+    /// Extension of the written files (e.g. "WEBP"); the contents are WebP either way
+    #[arg(long, value_name = "EXT")]
+    pub output_extension: Option<String>,
+
+    /// How inputs that map to the same flattened output are handled (detected during prescan)
+    #[arg(long, value_enum, default_value = "rename")]
+    pub collision_policy: CollisionPolicyArg,
+
+    /// Order in which prescanned files are converted and numbered for {index}
+    #[arg(long, value_enum, default_value = "none")]
+    pub sort: SortOrderArg,
+
+    /// Convert only the first NUM matching files (after --sort), e.g. to try settings
+    #[arg(long, value_name = "NUM")]
+    pub max_files: Option<usize>,
+
+    /// Convert prescanned files in scan order instead of starting with the largest ones
+    #[arg(long, default_value_t = false)]
+    pub no_largest_first: bool,
+
     /// Maximum file size limit (MB)
     #[arg(long, value_name = "SIZE")]
     pub max_size: Option<u64>,
@@ -82,10 +210,50 @@ pub struct Args {
     #[arg(long, default_value = "1", value_name = "SIZE")]
     pub min_size: u64,
 
+    /// Copy files smaller than SIZE KB to the output unchanged instead of converting them
+    #[arg(long, value_name = "SIZE")]
+    pub passthrough_below: Option<u64>,
+
+    /// Only convert files whose path relative to the input directory matches this glob (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Skip files whose path relative to the input directory matches this glob (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Only convert files modified on or after this date (YYYY-MM-DD or RFC 3339)
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    pub modified_since: Option<DateTime<Utc>>,
+
+    /// Only convert files modified before this date (YYYY-MM-DD or RFC 3339)
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    pub modified_before: Option<DateTime<Utc>>,
+
+    /// Skip images narrower or shorter than this, e.g. 64x64 (read from the image header)
+    #[arg(long, value_name = "WxH", value_parser = parse_dimensions)]
+    pub skip_smaller_than: Option<(u32, u32)>,
+
+    /// Skip images wider or taller than this, e.g. 10000x10000 (read from the image header)
+    #[arg(long, value_name = "WxH", value_parser = parse_dimensions)]
+    pub skip_larger_than: Option<(u32, u32)>,
+
+    /// Follow symlinked files and directories while scanning (cycles are skipped)
+    #[arg(long, default_value_t = false)]
+    pub follow_symlinks: bool,
+
+    /// Maximum directory depth to scan below the input directory (0 = only files directly in it)
+    #[arg(long, value_name = "DEPTH")]
+    pub max_depth: Option<usize>,
+
     /// Enable pre-processing scan
     #[arg(long, default_value = "true")]
     pub prescan: bool,
 
+    /// Start even if the estimated output exceeds the free space on the output volume (only warn)
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
+
     /// Verbose output mode
     #[arg(short, long)]
     pub verbose: bool,
@@ -94,6 +262,14 @@ pub struct Args {
     #[arg(long, conflicts_with = "verbose")]
     pub quiet: bool,
 
+    /// Print the conversion report as JSON to stdout as the only output (for scripting)
+    #[arg(long, default_value_t = false)]
+    pub json_stdout: bool,
+
+    /// Keep a JSON status file ({total, processed, failed, eta_seconds}) up to date for dashboards
+    #[arg(long, value_name = "PATH")]
+    pub progress_file: Option<PathBuf>,
+
     /// Generate conversion report
     #[arg(long)]
     pub report: bool,
@@ -102,6 +278,30 @@ pub struct Args {
     #[arg(long, default_value = "json", value_enum)]
     pub report_format: ReportFormatArg,
 
+    /// Report file or directory (defaults to the output directory)
+    #[arg(long, value_name = "PATH")]
+    pub report_path: Option<PathBuf>,
+
+    /// Insert the run's start time into the report file name (webpify_report_20240601_120000.json)
+    #[arg(long, default_value_t = false)]
+    pub report_timestamp: bool,
+
+    /// Write report errors to a separate <report>.errors.json file (JSON reports only)
+    #[arg(long, default_value_t = false)]
+    pub split_report_errors: bool,
+
+    /// List every written output file in the report (output_files)
+    #[arg(long, default_value_t = false)]
+    pub track_outputs: bool,
+
+    /// Collect a result per file in the report (CSV reports get a <report>.files.csv)
+    #[arg(long, default_value_t = false)]
+    pub track_files: bool,
+
+    /// Append timestamped per-file results to this file
+    #[arg(long, value_name = "FILE")]
+    pub log_file: Option<PathBuf>,
+
     /// Configuration file path
     #[arg(short, long, value_name = "FILE")]
     pub config: Option<PathBuf>,
@@ -114,13 +314,82 @@ pub struct Args {
     #[arg(long, value_enum, default_value = "off")]
     pub replace_input: ReplaceInputModeArg,
 
-    /// Force re-encoding of WebP files (by default, .webp files are skipped)
+    /// Keep the source when its output is below this fraction of its size (replace-input safety) [default: 0.01]
+    #[arg(long, value_name = "RATIO")]
+    pub replace_min_ratio: Option<f64>,
+
+    /// Keep the source when its output is above this fraction of its size (replace-input safety) [default: 2.0]
+    #[arg(long, value_name = "RATIO")]
+    pub replace_max_ratio: Option<f64>,
+
+    /// Move sources the recycle bin refuses into this directory instead of keeping them in place
+    #[arg(long, value_name = "DIR")]
+    pub recycle_quarantine: Option<PathBuf>,
+
+    /// After converting, decode every written WebP again and check its dimensions (report mismatches as errors)
+    #[arg(long, default_value_t = false)]
+    pub verify: bool,
+
+    /// Force re-encoding of WebP files (by default, .webp files are skipped); they are
+    /// re-encoded losslessly unless --mode is given or --format-override sets webp, and kept if not smaller
     #[arg(long, default_value_t = false)]
     pub reencode_webp: bool,
 
-    /// Dry run mode - preview operations without making changes
+    /// Dry run mode - preview operations without making changes (output sizes are not measured)
     #[arg(long, default_value_t = false)]
     pub dry_run: bool,
+
+    /// Print the files that would be converted, one per line, without converting anything
+    #[arg(long, default_value_t = false)]
+    pub list_only: bool,
+
+    /// Dry run that encodes every image in memory to report exact output sizes (slower)
+    #[arg(long, default_value_t = false)]
+    pub dry_run_accurate: bool,
+
+    /// Keep ICC color profiles and EXIF data (orientation is applied to the pixels)
+    #[arg(long, default_value_t = false)]
+    pub preserve_metadata: bool,
+
+    /// Write no ICC, EXIF or XMP metadata (removes camera details and GPS locations)
+    #[arg(long, default_value_t = false)]
+    pub strip_metadata: bool,
+
+    /// Retry files that fail with transient I/O errors (e.g. locked files) up to this many times
+    #[arg(long, default_value = "0", value_name = "NUM")]
+    pub max_retries: u32,
+
+    /// Give up on a file after this many seconds (the stuck conversion keeps running in the background)
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub timeout: Option<u64>,
+
+    /// Flatten animated GIF/APNG and HEIC sequence inputs to their first frame instead of animated WebP
+    #[arg(long, default_value_t = false)]
+    pub no_animation: bool,
+
+    /// Encode pixels as stored instead of rotating them upright by their EXIF orientation
+    #[arg(long, default_value_t = false)]
+    pub no_auto_orient: bool,
+
+    /// Which resolutions of ICO files to convert (all writes one file per size, e.g. icon_32x32.webp)
+    #[arg(long, value_enum, default_value = "largest")]
+    pub ico_size: IcoSizeArg,
+
+    /// Which pages of multi-page TIFF files to convert (all writes one file per page, e.g. scan_p2.webp)
+    #[arg(long, value_enum, default_value = "first")]
+    pub tiff_pages: TiffPagesArg,
+
+    /// Limit output write bandwidth (MB/s, unlimited by default)
+    #[arg(long, value_name = "MB/s", value_parser = clap::value_parser!(u64).range(1..))]
+    pub write_limit: Option<u64>,
+
+    /// Cap the memory of images decoded at once (MB, estimated as width x height x 4; unlimited by default)
+    #[arg(long, value_name = "MB", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_memory: Option<u64>,
+
+    /// Run at reduced process priority to keep the system responsive
+    #[arg(long, default_value_t = false)]
+    pub nice: bool,
 }
 
 #[derive(Debug, Clone, ValueEnum)]
@@ -131,6 +400,10 @@ pub enum CompressionModeArg {
     Lossy,
     /// Auto mode (intelligently choose based on image characteristics)
     Auto,
+    /// Near-lossless compression (quality sets the level, 100 = lossless)
+    NearLossless,
+    /// Encode lossless and lossy, keep the smaller (about twice as slow)
+    Smallest,
 }
 
 impl From<CompressionModeArg> for CompressionMode {
@@ -139,6 +412,8 @@ impl From<CompressionModeArg> for CompressionMode {
             CompressionModeArg::Lossless => CompressionMode::Lossless,
             CompressionModeArg::Lossy => CompressionMode::Lossy,
             CompressionModeArg::Auto => CompressionMode::Auto,
+            CompressionModeArg::NearLossless => CompressionMode::NearLossless,
+            CompressionModeArg::Smallest => CompressionMode::Smallest,
         }
     }
 }
@@ -148,6 +423,10 @@ pub enum ReportFormatArg {
     Json,
     Csv,
     Html,
+    #[value(name = "jsonl")]
+    JsonLines,
+    Xml,
+    Text,
 }
 
 impl From<ReportFormatArg> for ReportFormat {
@@ -156,6 +435,112 @@ impl From<ReportFormatArg> for ReportFormat {
             ReportFormatArg::Json => ReportFormat::Json,
             ReportFormatArg::Csv => ReportFormat::Csv,
             ReportFormatArg::Html => ReportFormat::Html,
+            ReportFormatArg::JsonLines => ReportFormat::JsonLines,
+            ReportFormatArg::Xml => ReportFormat::Xml,
+            ReportFormatArg::Text => ReportFormat::Text,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum ResizeFilterArg {
+    /// Nearest neighbor (fastest, keeps hard edges for pixel art)
+    Nearest,
+    /// Linear interpolation (fast, slightly soft)
+    Triangle,
+    /// Cubic interpolation (sharp, good for photos)
+    CatmullRom,
+    /// Gaussian blur (smooth, no ringing)
+    Gaussian,
+    /// Lanczos with window 3 (sharpest, slowest)
+    Lanczos3,
+}
+
+impl From<ResizeFilterArg> for ResizeFilter {
+    fn from(filter: ResizeFilterArg) -> Self {
+        match filter {
+            ResizeFilterArg::Nearest => ResizeFilter::Nearest,
+            ResizeFilterArg::Triangle => ResizeFilter::Triangle,
+            ResizeFilterArg::CatmullRom => ResizeFilter::CatmullRom,
+            ResizeFilterArg::Gaussian => ResizeFilter::Gaussian,
+            ResizeFilterArg::Lanczos3 => ResizeFilter::Lanczos3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum IcoSizeArg {
+    /// Convert only the largest embedded image
+    Largest,
+    /// Convert only the smallest embedded image
+    Smallest,
+    /// Convert every embedded resolution
+    All,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum TiffPagesArg {
+    /// Convert only the first page
+    First,
+    /// Convert every page
+    All,
+}
+
+impl From<TiffPagesArg> for TiffPageSelection {
+    fn from(pages: TiffPagesArg) -> Self {
+        match pages {
+            TiffPagesArg::First => TiffPageSelection::First,
+            TiffPagesArg::All => TiffPageSelection::All,
+        }
+    }
+}
+
+impl From<IcoSizeArg> for IcoSizeSelection {
+    fn from(size: IcoSizeArg) -> Self {
+        match size {
+            IcoSizeArg::Largest => IcoSizeSelection::Largest,
+            IcoSizeArg::Smallest => IcoSizeSelection::Smallest,
+            IcoSizeArg::All => IcoSizeSelection::All,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum CollisionPolicyArg {
+    /// Keep the first name and add a numeric suffix to the others
+    Rename,
+    /// Abort and list the conflicting inputs
+    Error,
+}
+
+impl From<CollisionPolicyArg> for CollisionPolicy {
+    fn from(policy: CollisionPolicyArg) -> Self {
+        match policy {
+            CollisionPolicyArg::Rename => CollisionPolicy::Rename,
+            CollisionPolicyArg::Error => CollisionPolicy::Error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum SortOrderArg {
+    /// Directory walk order (platform dependent)
+    None,
+    /// Alphabetical by path
+    Path,
+    /// Smallest files first
+    SizeAsc,
+    /// Largest files first
+    SizeDesc,
+}
+
+impl From<SortOrderArg> for SortOrder {
+    fn from(sort: SortOrderArg) -> Self {
+        match sort {
+            SortOrderArg::None => SortOrder::None,
+            SortOrderArg::Path => SortOrder::PathAlphabetical,
+            SortOrderArg::SizeAsc => SortOrder::SizeAscending,
+            SortOrderArg::SizeDesc => SortOrder::SizeDescending,
         }
     }
 }
@@ -180,6 +565,33 @@ impl From<ReplaceInputModeArg> for ReplaceInputMode {
     }
 }
 
+fn parse_target_ssim(value: &str) -> Result<f64, String> {
+    let target: f64 = value
+        .parse()
+        .map_err(|_| format!("`{value}` is not a number"))?;
+    if target > 0.0 && target <= 1.0 {
+        Ok(target)
+    } else {
+        Err(format!("target SSIM must be in (0, 1], got {target}"))
+    }
+}
+
+fn parse_date(value: &str) -> Result<DateTime<Utc>, String> {
+    webpify::utils::parse_date_time(value).map_err(|e| e.to_string())
+}
+
+fn parse_dimensions(value: &str) -> Result<(u32, u32), String> {
+    webpify::utils::parse_dimensions(value).map_err(|e| e.to_string())
+}
+
+fn parse_format_override(value: &str) -> Result<(String, CompressionMode, u8), String> {
+    webpify::utils::parse_format_override(value).map_err(|e| e.to_string())
+}
+
+fn parse_color(value: &str) -> Result<image::Rgba<u8>, String> {
+    webpify::utils::parse_color(value).map_err(|e| e.to_string())
+}
+
 fn main() -> Result<()> {
     if std::env::args().len() == 1 {
         Args::command().print_help()?;
@@ -187,43 +599,73 @@ fn main() -> Result<()> {
         std::process::exit(0);
     }
 
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches)?;
+
+    let config_path = args.config.clone().or_else(Config::discover);
+    let mut config = config_path.as_deref().map(Config::from_file).transpose()?;
+    if args.profile.is_some() {
+        config
+            .get_or_insert_with(Config::default)
+            .load_profile_files(config_path.as_deref())?;
+    }
+    let output_config = config.as_ref().and_then(|config| config.output.as_ref());
+
+    // Explicit CLI flags win over the config file
+    let verbose = if is_explicit(&matches, "verbose") {
+        args.verbose
+    } else {
+        output_config
+            .and_then(|output| output.verbose)
+            .unwrap_or(false)
+    };
+    let quiet = if is_explicit(&matches, "quiet") {
+        args.quiet
+    } else {
+        !verbose
+            && output_config
+                .and_then(|output| output.quiet)
+                .unwrap_or(false)
+    };
 
     // Initialize logging
-    if args.verbose {
+    if verbose {
         env_logger::Builder::from_default_env()
             .filter_level(log::LevelFilter::Debug)
             .init();
-    } else if !args.quiet {
+    } else if !quiet {
         env_logger::Builder::from_default_env()
             .filter_level(log::LevelFilter::Info)
             .init();
     }
 
-    // Convert CLI args to library configuration
-    let mut options = ConversionOptions::new(args.input)
-        .with_quality(args.quality)
-        .with_mode(args.mode.into())
-        .with_dry_run(args.dry_run)
-        .with_overwrite(args.overwrite);
-
-    if let Some(output) = args.output {
-        options = options.with_output_dir(output);
+    if let Some(path) = &config_path {
+        log::debug!("Loaded config file: {}", path.display());
     }
 
-    if let Some(threads) = args.threads {
-        options = options.with_threads(threads);
-    }
+    // In JSON mode stdout carries nothing but the report; logs still go to stderr
+    let human_output = !quiet && !args.json_stdout;
+
+    let options = build_options(&args, &matches, config.as_ref())?;
+    let generate_report = options.generate_report;
+    let report_format = options.report_format.clone();
+    let report_path = options.report_path.clone();
+    let report_timestamp = options.report_timestamp;
 
     // Create and run the core engine
     let mut core = WebpifyCore::new(options);
 
-    #[cfg(feature = "cli")]
-    let progress_reporter = if !args.quiet {
-        let reporter = ConsoleProgressReporter::new();
-        if !args.quiet {
-            print_ascii_banner();
+    if args.list_only {
+        for path in core.list_candidates()? {
+            println!("{}", path.display());
         }
+        return Ok(());
+    }
+
+    #[cfg(feature = "cli")]
+    let progress_reporter = if human_output {
+        let reporter = ConsoleProgressReporter::new().with_verbose(verbose);
+        print_ascii_banner();
         Some(Box::new(reporter) as Box<dyn webpify::ProgressReporter>)
     } else {
         None
@@ -232,22 +674,344 @@ fn main() -> Result<()> {
     #[cfg(not(feature = "cli"))]
     let progress_reporter = None;
 
+    // The status file is written in quiet and JSON modes too
+    let progress_reporter = match &args.progress_file {
+        Some(path) => {
+            let mut reporter = FileProgressReporter::new(path);
+            if let Some(inner) = progress_reporter {
+                reporter = reporter.with_inner(inner);
+            }
+            Some(Box::new(reporter) as Box<dyn webpify::ProgressReporter>)
+        }
+        None => progress_reporter,
+    };
+
     // Run conversion
     let report = core.run_with_progress(progress_reporter)?;
 
     // Generate report if requested
-    if args.report {
-        generate_report(&report, &args.report_format.into())?;
+    if generate_report {
+        let report_options = ReportOptions {
+            split_errors: args.split_report_errors,
+            output_path: report_path,
+            timestamp: report_timestamp,
+        };
+        let report_path = generate_report_with_options(&report, &report_format, &report_options)?;
+        if human_output {
+            println!("Report saved to: {}", report_path.display());
+        }
     }
 
-    // Print summary if not quiet
-    if !args.quiet {
+    if args.json_stdout {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if human_output {
+        // Print summary if not quiet
         print_results_summary(&report);
     }
 
     Ok(())
 }
 
+/// Whether `id` was given on the command line rather than taken from its default
+fn is_explicit(matches: &ArgMatches, id: &str) -> bool {
+    matches.value_source(id) == Some(ValueSource::CommandLine)
+}
+
+/// Build conversion options from the config file (if any) with CLI flags layered on top
+fn build_options(
+    args: &Args,
+    matches: &ArgMatches,
+    config: Option<&Config>,
+) -> Result<ConversionOptions> {
+    let mut options = match config {
+        Some(config) => ConversionOptions::from_config(config)?,
+        None => ConversionOptions::default(),
+    };
+
+    // Profile settings sit between the config file and explicit CLI flags
+    if let Some(name) = &args.profile {
+        let profile = config.unwrap_or(&Config::default()).resolve_profile(name)?;
+        options = options.with_profile(&profile)?;
+    }
+
+    if !args.input.is_empty() {
+        options = options.with_input_dirs(args.input.clone());
+    }
+    if let Some(list) = &args.input_list {
+        let files = webpify::utils::read_path_list(list)?;
+        if files.is_empty() {
+            bail!("Input list {} contains no paths", list.display());
+        }
+        if let Some(base_dir) = &args.base_dir {
+            options = options.with_base_dir(base_dir.clone());
+        }
+        options = options.with_input_files(files);
+        if args.input.is_empty() {
+            // Relative paths in the working directory share an empty ancestor
+            let base_dir = options.base_dir.clone().unwrap_or_default();
+            let input_dir = if base_dir.as_os_str().is_empty() {
+                PathBuf::from(".")
+            } else {
+                base_dir
+            };
+            options = options.with_input_dirs(vec![input_dir]);
+        }
+    }
+    if options.input_dir.as_os_str().is_empty() {
+        bail!(
+            "No input directory given; pass --input or --input-list, or set general.input_dir in the config file"
+        );
+    }
+
+    if is_explicit(matches, "quality") {
+        options = options.with_quality(args.quality);
+    }
+    if is_explicit(matches, "mode") {
+        options = options.with_mode(args.mode.clone().into());
+    }
+    if is_explicit(matches, "formats") {
+        options = options.with_supported_formats(args.formats.clone());
+    }
+    for (extension, mode, quality) in &args.format_override {
+        options = options.with_format_override(extension, mode.clone(), *quality);
+    }
+    if is_explicit(matches, "exclude_formats") {
+        options = options.with_excluded_formats(args.exclude_formats.clone());
+    }
+    if is_explicit(matches, "detect_by_content") {
+        options = options.with_detect_by_content(args.detect_by_content);
+    }
+    if is_explicit(matches, "overwrite") {
+        options = options.with_overwrite(args.overwrite);
+    }
+    if is_explicit(matches, "keep_smaller") {
+        options = options.with_keep_smaller(args.keep_smaller);
+    }
+    if is_explicit(matches, "preserve_timestamps") {
+        options = options.with_preserve_timestamps(args.preserve_timestamps);
+    }
+    if is_explicit(matches, "preserve_structure") {
+        options = options.with_preserve_structure(args.preserve_structure);
+    }
+    if is_explicit(matches, "in_place") {
+        options = options.with_in_place(args.in_place);
+    }
+    if is_explicit(matches, "incremental") {
+        options = options.with_incremental(args.incremental);
+    }
+    if is_explicit(matches, "manifest") {
+        options = options.with_manifest(args.manifest);
+    }
+    if is_explicit(matches, "resume") {
+        options = options.with_resume(args.resume);
+    }
+    if is_explicit(matches, "collision_policy") {
+        options = options.with_collision_policy(args.collision_policy.clone().into());
+    }
+    if is_explicit(matches, "sort") {
+        options = options.with_sort_order(args.sort.clone().into());
+    }
+    if let Some(max_files) = args.max_files {
+        options = options.with_max_files(max_files)?;
+    }
+    if args.no_largest_first {
+        options = options.with_largest_first(false);
+    }
+    if let Some(output_extension) = &args.output_extension {
+        options = options.with_output_extension(output_extension.clone())?;
+    }
+    if let Some(filename_template) = &args.filename_template {
+        options = options.with_filename_template(filename_template.clone())?;
+    }
+    if let Some(max_size) = args.max_size {
+        options = options.with_max_size_mb(max_size);
+    }
+    if is_explicit(matches, "min_size") {
+        options = options.with_min_size_kb(args.min_size);
+    }
+    if let Some(passthrough_below) = args.passthrough_below {
+        options = options.with_passthrough_below(Some(passthrough_below));
+    }
+    if !args.include.is_empty() {
+        options = options.with_include_globs(args.include.clone());
+    }
+    if !args.exclude.is_empty() {
+        options = options.with_exclude_globs(args.exclude.clone());
+    }
+    if let Some(modified_since) = args.modified_since {
+        options = options.with_modified_after(modified_since);
+    }
+    if let Some(modified_before) = args.modified_before {
+        options = options.with_modified_before(modified_before);
+    }
+    if let Some((width, height)) = args.skip_smaller_than {
+        options = options.with_min_dimensions(width, height);
+    }
+    if let Some((width, height)) = args.skip_larger_than {
+        options = options.with_max_dimensions(width, height);
+    }
+    if is_explicit(matches, "follow_symlinks") {
+        options = options.with_follow_symlinks(args.follow_symlinks);
+    }
+    if let Some(max_depth) = args.max_depth {
+        options = options.with_max_depth(Some(max_depth));
+    }
+    if is_explicit(matches, "prescan") {
+        options = options.with_prescan(args.prescan);
+    }
+    if args.force {
+        options = options.with_space_check(false);
+    }
+    if is_explicit(matches, "replace_input") {
+        options = options.with_replace_input_mode(args.replace_input.clone().into());
+    }
+    if args.replace_min_ratio.is_some() || args.replace_max_ratio.is_some() {
+        let min_ratio = args.replace_min_ratio.unwrap_or(options.replace_min_ratio);
+        let max_ratio = args.replace_max_ratio.unwrap_or(options.replace_max_ratio);
+        options = options.with_replace_size_limits(min_ratio, max_ratio)?;
+    }
+    if let Some(quarantine_dir) = &args.recycle_quarantine {
+        options =
+            options.with_recycle_fallback(RecycleFallback::Quarantine(quarantine_dir.clone()))?;
+    }
+    if is_explicit(matches, "verify") {
+        options = options.with_verify(args.verify);
+    }
+    if is_explicit(matches, "reencode_webp") {
+        options = options.with_reencode_webp(args.reencode_webp);
+    }
+    if is_explicit(matches, "dry_run") {
+        options = options.with_dry_run(args.dry_run);
+    }
+    if is_explicit(matches, "dry_run_accurate") {
+        options = options
+            .with_dry_run(true)
+            .with_dry_run_accurate(args.dry_run_accurate);
+    }
+    if is_explicit(matches, "report") {
+        options = options.with_generate_report(args.report);
+    }
+    if is_explicit(matches, "report_format") {
+        options = options.with_report_format(args.report_format.clone().into());
+    }
+    if is_explicit(matches, "track_outputs") {
+        options = options.with_track_outputs(args.track_outputs);
+    }
+    if is_explicit(matches, "track_files") {
+        options = options.with_track_files(args.track_files);
+    }
+    if is_explicit(matches, "report_path") {
+        options = options.with_report_path(args.report_path.clone());
+    }
+    if is_explicit(matches, "report_timestamp") {
+        options = options.with_report_timestamp(args.report_timestamp);
+    }
+    if let Some(log_file) = &args.log_file {
+        options = options.with_log_file(log_file.clone());
+    }
+
+    if is_explicit(matches, "preserve_metadata") {
+        options = options.with_preserve_metadata(args.preserve_metadata);
+    }
+    if is_explicit(matches, "strip_metadata") {
+        options = options.with_strip_metadata(args.strip_metadata);
+    }
+
+    if is_explicit(matches, "max_retries") {
+        options = options.with_max_retries(args.max_retries);
+    }
+
+    if args.no_animation {
+        options = options.with_animation(false);
+    }
+    if args.no_auto_orient {
+        options = options.with_auto_orient(false);
+    }
+
+    if is_explicit(matches, "ico_size") {
+        options = options.with_ico_size_selection(args.ico_size.clone().into());
+    }
+    if is_explicit(matches, "tiff_pages") {
+        options = options.with_tiff_page_selection(args.tiff_pages.clone().into());
+    }
+
+    if is_explicit(matches, "nice") {
+        options = options.with_low_priority(args.nice);
+    }
+
+    if let Some(output) = &args.output {
+        options = options.with_output_dir(output.clone());
+    }
+
+    if let Some(threads) = args.threads {
+        options = options.with_threads(threads);
+    }
+
+    if let Some(max_dimension) = args.max_dimension {
+        options = options.with_max_dimension(max_dimension)?;
+    }
+
+    if is_explicit(matches, "effort") {
+        options = options.with_effort(args.effort);
+    }
+    if let Some(lossless_level) = args.lossless_level {
+        options = options.with_lossless_level(lossless_level);
+    }
+    if let Some(filter_strength) = args.filter_strength {
+        options = options.with_filter_strength(filter_strength);
+    }
+    if let Some(sharpness) = args.sharpness {
+        options = options.with_sharpness(sharpness);
+    }
+    if let Some(auto_color_threshold) = args.auto_color_threshold {
+        options = options.with_auto_color_threshold(auto_color_threshold);
+    }
+    if let Some(auto_sample_size) = args.auto_sample_size {
+        options = options.with_auto_sample_size(auto_sample_size);
+    }
+
+    if is_explicit(matches, "alpha_quality") {
+        options = options.with_alpha_quality(args.alpha_quality);
+    }
+
+    if is_explicit(matches, "resize_filter") {
+        options = options.with_resize_filter(args.resize_filter.clone().into());
+    }
+
+    if let Some(background) = args.background {
+        options = options.with_background(Some(background));
+    }
+
+    if let Some(write_limit) = args.write_limit {
+        options = options.with_write_limit_mb(write_limit);
+    }
+    if let Some(max_memory) = args.max_memory {
+        options = options.with_max_memory_mb(max_memory);
+    }
+    if let Some(timeout) = args.timeout {
+        options = options.with_per_file_timeout(Duration::from_secs(timeout));
+    }
+
+    if let Some(target_ssim) = args.target_ssim {
+        // Lossless encodes have no quality to search, so the target would do nothing
+        if matches!(
+            options.mode,
+            CompressionMode::Lossless | CompressionMode::NearLossless
+        ) {
+            if is_explicit(matches, "mode") {
+                bail!("--target-ssim needs a lossy mode (--mode lossy, auto or smallest)");
+            }
+            options = options.with_mode(CompressionMode::Lossy);
+        }
+        options = options.with_target_ssim(target_ssim);
+    }
+    if let Some(min_ssim) = args.smallest_min_ssim {
+        options = options.with_smallest_min_ssim(min_ssim);
+    }
+
+    Ok(options)
+}
+
 fn print_ascii_banner() {
     println!(
         r#"
@@ -268,45 +1032,5 @@ fn print_ascii_banner() {
 }
 
 fn print_results_summary(report: &ConversionReport) {
-    use humansize::{DECIMAL, format_size};
-
-    println!("\n🎉 Conversion completed!");
-    println!("📊 Results Summary:");
-    println!("  ✅ Processed: {} files", report.processed_files);
-    if report.failed_files > 0 {
-        println!("  ❌ Failed: {} files", report.failed_files);
-    }
-    if report.skipped_files > 0 {
-        println!("  ⏭️ Skipped: {} files", report.skipped_files);
-    }
-
-    if report.original_size > 0 {
-        println!("\n💾 Space Analysis:");
-        println!(
-            "  📦 Original size: {}",
-            format_size(report.original_size, DECIMAL)
-        );
-        println!(
-            "  🗜️ Compressed size: {}",
-            format_size(report.compressed_size, DECIMAL)
-        );
-        println!("  💾 Space saved: {:.1}%", report.compression_ratio * 100.0);
-    }
-
-    println!("\n⏱️ Performance:");
-    println!("  🕐 Duration: {:.1}s", report.duration.as_secs_f64());
-    println!("  🚀 Speed: {:.1} files/sec", report.files_per_second);
-    println!("  🧵 Threads used: {}", report.thread_count);
-
-    if !report.errors.is_empty() && report.errors.len() <= 5 {
-        println!("\n❌ Errors:");
-        for error in &report.errors {
-            println!("  • {error}");
-        }
-    } else if report.errors.len() > 5 {
-        println!(
-            "\n❌ {} errors occurred (use --report for full details)",
-            report.errors.len()
-        );
-    }
+    print!("\n{}", format_summary(report, Some(5)));
 }
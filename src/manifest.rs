@@ -0,0 +1,125 @@
+//! Content-hash manifest for incremental conversion across runs
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::utils::write_atomically;
+
+/// File name of the manifest inside the output directory
+pub const MANIFEST_FILE_NAME: &str = ".webpify-manifest.json";
+
+/// Format version written to new manifests; other versions are ignored on load
+const MANIFEST_VERSION: u32 = 1;
+
+/// Hash and output of a source file as of its last successful conversion
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// BLAKE3 hash of the source file contents (hex)
+    pub hash: String,
+    pub output: PathBuf,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ManifestFile {
+    version: u32,
+    entries: HashMap<PathBuf, ManifestEntry>,
+}
+
+/// Sidecar manifest mapping source paths to content hashes and outputs
+///
+/// Lookups and updates may happen from worker threads; the manifest is written back
+/// once with [`save`](Self::save) at the end of the run.
+#[derive(Debug)]
+pub struct Manifest {
+    path: PathBuf,
+    entries: Mutex<HashMap<PathBuf, ManifestEntry>>,
+}
+
+impl Manifest {
+    /// Load the manifest from `output_dir`, starting empty if there is none
+    ///
+    /// An unreadable or corrupt manifest is logged and ignored, so every file is checked
+    /// again as on a first run.
+    pub fn load(output_dir: &Path) -> Self {
+        let path = output_dir.join(MANIFEST_FILE_NAME);
+        let entries = match Self::read_entries(&path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("Ignoring manifest and converting all files: {e:#}");
+                HashMap::new()
+            }
+        };
+
+        Self {
+            path,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    fn read_entries(path: &Path) -> Result<HashMap<PathBuf, ManifestEntry>> {
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest: {}", path.display()))?;
+        let file: ManifestFile = serde_json::from_str(&contents)
+            .with_context(|| format!("Corrupt manifest: {}", path.display()))?;
+        if file.version != MANIFEST_VERSION {
+            anyhow::bail!(
+                "Unsupported manifest version {} in {}",
+                file.version,
+                path.display()
+            );
+        }
+        Ok(file.entries)
+    }
+
+    /// Whether `source` still hashes to `hash` and was last converted to `output`
+    ///
+    /// Returns `None` if the manifest has no entry for `source`.
+    pub fn is_unchanged(&self, source: &Path, hash: &str, output: &Path) -> Option<bool> {
+        let entries = self.entries.lock().ok()?;
+        entries
+            .get(source)
+            .map(|entry| entry.hash == hash && entry.output == output)
+    }
+
+    /// Record a successful conversion of `source`
+    pub fn record(&self, source: &Path, hash: String, output: PathBuf) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(source.to_path_buf(), ManifestEntry { hash, output });
+        }
+    }
+
+    /// Write the manifest back to the output directory
+    pub fn save(&self) -> Result<()> {
+        let entries = self
+            .entries
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Manifest lock poisoned"))?;
+        let file = ManifestFile {
+            version: MANIFEST_VERSION,
+            entries: entries.clone(),
+        };
+        let contents = serde_json::to_string_pretty(&file)?;
+
+        // An interrupted save keeps the old manifest
+        write_atomically(&self.path, contents.as_bytes())
+            .with_context(|| format!("Failed to write manifest: {}", self.path.display()))
+    }
+}
+
+/// BLAKE3 hash of a file's contents as a hex string
+///
+/// The file is streamed through the hasher, so large sources are never held in memory.
+pub fn hash_file(path: &Path) -> Result<String> {
+    let read_error = || format!("Failed to read file: {}", path.display());
+    let file = std::fs::File::open(path).with_context(read_error)?;
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(file).with_context(read_error)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
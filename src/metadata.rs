@@ -0,0 +1,311 @@
+//! Reading image metadata and embedding it into WebP output
+
+use anyhow::{Context, Result, bail};
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use std::io::{BufRead, Cursor, Seek};
+use std::path::Path;
+
+/// VP8X feature flags (WebP extended format)
+const VP8X_FLAG_ICC: u8 = 0x20;
+const VP8X_FLAG_ALPHA: u8 = 0x10;
+const VP8X_FLAG_EXIF: u8 = 0x08;
+const VP8X_FLAG_XMP: u8 = 0x04;
+
+/// EXIF orientation tag and its "normal" value
+const EXIF_TAG_ORIENTATION: u16 = 0x0112;
+const EXIF_ORIENTATION_NORMAL: u16 = 1;
+
+/// Metadata carried over from a source image
+#[derive(Debug, Clone, Default)]
+pub struct ImageMetadata {
+    /// Raw ICC color profile
+    pub icc_profile: Option<Vec<u8>>,
+    /// Raw EXIF data (TIFF structure, without the JPEG `Exif\0\0` prefix)
+    pub exif: Option<Vec<u8>>,
+}
+
+impl ImageMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.icc_profile.is_none() && self.exif.is_none()
+    }
+}
+
+/// Decode an image file and turn it upright according to its EXIF orientation
+///
+/// All eight orientations (rotation by 90, 180 or 270 degrees, each optionally mirrored)
+/// are applied to the pixels. No EXIF is returned, so the tag is dropped with it. The
+/// format is taken from the data, so a wrong extension does not break decoding.
+pub fn open_oriented(path: &Path) -> Result<DynamicImage> {
+    decode_oriented(ImageReader::open(path)?.with_guessed_format()?)
+        .with_context(|| format!("Failed to read image: {}", path.display()))
+}
+
+/// Like [`open_oriented`], for encoded image data held in memory
+pub fn load_oriented_from_memory(data: &[u8], format: ImageFormat) -> Result<DynamicImage> {
+    decode_oriented(ImageReader::with_format(Cursor::new(data), format))
+        .context("Failed to decode image data")
+}
+
+fn decode_oriented<R: BufRead + Seek>(reader: ImageReader<R>) -> Result<DynamicImage> {
+    let mut decoder = reader.into_decoder()?;
+    // Images without EXIF (or with an unreadable tag) are left as they are
+    let orientation = decoder.orientation().ok();
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    if let Some(orientation) = orientation {
+        img.apply_orientation(orientation);
+    }
+    Ok(img)
+}
+
+/// Decode an image together with its ICC profile and EXIF data
+///
+/// With `auto_orient`, the EXIF orientation is applied to the pixels and the orientation
+/// tag is reset to "normal" in the returned EXIF, so viewers do not rotate the output a
+/// second time. Otherwise the pixels and the tag are kept as stored.
+pub fn open_with_metadata(path: &Path, auto_orient: bool) -> Result<(DynamicImage, ImageMetadata)> {
+    let mut decoder = ImageReader::open(path)?
+        .with_guessed_format()?
+        .into_decoder()
+        .with_context(|| format!("Failed to read image: {}", path.display()))?;
+
+    // Metadata is best effort: a broken profile should not fail the conversion
+    let icc_profile = decoder.icc_profile().ok().flatten();
+    let mut exif = decoder
+        .exif_metadata()
+        .ok()
+        .flatten()
+        .map(strip_exif_prefix);
+    let orientation = decoder.orientation().ok().filter(|_| auto_orient);
+
+    let mut img = DynamicImage::from_decoder(decoder)
+        .with_context(|| format!("Failed to read image: {}", path.display()))?;
+
+    if let Some(orientation) = orientation {
+        img.apply_orientation(orientation);
+        if let Some(exif) = &mut exif {
+            reset_exif_orientation(exif);
+        }
+    }
+
+    Ok((img, ImageMetadata { icc_profile, exif }))
+}
+
+/// Re-wrap an encoded WebP file in the extended (VP8X) format with ICC and EXIF chunks
+pub fn embed_metadata(webp: &[u8], metadata: &ImageMetadata) -> Result<Vec<u8>> {
+    if webp.len() < 12 || &webp[0..4] != b"RIFF" || &webp[8..12] != b"WEBP" {
+        bail!("Encoder output is not a WebP file");
+    }
+
+    let chunks = parse_chunks(&webp[12..])?;
+    let mut flags = 0u8;
+    let mut canvas = None;
+    let mut image_chunks = Vec::new();
+
+    for (fourcc, payload) in chunks {
+        match &fourcc {
+            b"VP8X" => {
+                if payload.len() < 10 {
+                    bail!("Truncated VP8X chunk in encoder output");
+                }
+                flags |= payload[0] & VP8X_FLAG_ALPHA;
+                canvas = Some((read_u24(&payload[4..7]) + 1, read_u24(&payload[7..10]) + 1));
+            }
+            b"ALPH" => {
+                flags |= VP8X_FLAG_ALPHA;
+                image_chunks.push((fourcc, payload));
+            }
+            b"VP8L" => {
+                // Bit 28 after the signature byte is the "alpha is used" hint
+                if payload.len() >= 5 {
+                    let bits = u32::from_le_bytes([payload[1], payload[2], payload[3], payload[4]]);
+                    if bits & (1 << 28) != 0 {
+                        flags |= VP8X_FLAG_ALPHA;
+                    }
+                }
+                canvas = canvas.or_else(|| bitstream_dimensions(&fourcc, payload));
+                image_chunks.push((fourcc, payload));
+            }
+            b"VP8 " => {
+                canvas = canvas.or_else(|| bitstream_dimensions(&fourcc, payload));
+                image_chunks.push((fourcc, payload));
+            }
+            // Any metadata the encoder wrote is replaced by the source metadata
+            b"ICCP" | b"EXIF" | b"XMP " => {}
+            _ => image_chunks.push((fourcc, payload)),
+        }
+    }
+
+    let (width, height) = canvas.context("Could not determine WebP canvas size")?;
+
+    if metadata.icc_profile.is_some() {
+        flags |= VP8X_FLAG_ICC;
+    }
+    if metadata.exif.is_some() {
+        flags |= VP8X_FLAG_EXIF;
+    }
+
+    let mut vp8x = [0u8; 10];
+    vp8x[0] = flags;
+    vp8x[4..7].copy_from_slice(&(width - 1).to_le_bytes()[..3]);
+    vp8x[7..10].copy_from_slice(&(height - 1).to_le_bytes()[..3]);
+
+    // Chunk order required by the container spec: VP8X, ICCP, image data, EXIF
+    let mut body = Vec::with_capacity(webp.len() + 64);
+    write_chunk(&mut body, b"VP8X", &vp8x);
+    if let Some(icc_profile) = &metadata.icc_profile {
+        write_chunk(&mut body, b"ICCP", icc_profile);
+    }
+    for (fourcc, payload) in image_chunks {
+        write_chunk(&mut body, &fourcc, payload);
+    }
+    if let Some(exif) = &metadata.exif {
+        write_chunk(&mut body, b"EXIF", exif);
+    }
+
+    let riff_size = u32::try_from(body.len() + 4).context("WebP output too large")?;
+    let mut output = Vec::with_capacity(body.len() + 12);
+    output.extend_from_slice(b"RIFF");
+    output.extend_from_slice(&riff_size.to_le_bytes());
+    output.extend_from_slice(b"WEBP");
+    output.extend_from_slice(&body);
+    Ok(output)
+}
+
+/// Copy a WebP file without its ICC, EXIF and XMP chunks
+pub fn strip_metadata(webp: &[u8]) -> Result<Vec<u8>> {
+    if webp.len() < 12 || &webp[0..4] != b"RIFF" || &webp[8..12] != b"WEBP" {
+        bail!("Source is not a WebP file");
+    }
+
+    let mut body = Vec::with_capacity(webp.len());
+    for (fourcc, payload) in parse_chunks(&webp[12..])? {
+        match &fourcc {
+            b"VP8X" => {
+                let mut vp8x = payload.to_vec();
+                if let Some(flags) = vp8x.first_mut() {
+                    *flags &= !(VP8X_FLAG_ICC | VP8X_FLAG_EXIF | VP8X_FLAG_XMP);
+                }
+                write_chunk(&mut body, &fourcc, &vp8x);
+            }
+            b"ICCP" | b"EXIF" | b"XMP " => {}
+            _ => write_chunk(&mut body, &fourcc, payload),
+        }
+    }
+
+    let riff_size = u32::try_from(body.len() + 4).context("WebP file too large")?;
+    let mut output = Vec::with_capacity(body.len() + 12);
+    output.extend_from_slice(b"RIFF");
+    output.extend_from_slice(&riff_size.to_le_bytes());
+    output.extend_from_slice(b"WEBP");
+    output.extend_from_slice(&body);
+    Ok(output)
+}
+
+/// Split the body of a RIFF WebP file into (fourcc, payload) chunks
+fn parse_chunks(mut data: &[u8]) -> Result<Vec<([u8; 4], &[u8])>> {
+    let mut chunks = Vec::new();
+
+    while data.len() >= 8 {
+        let fourcc = [data[0], data[1], data[2], data[3]];
+        let size = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        let end = 8 + size;
+        if end > data.len() {
+            bail!("Truncated chunk in encoder output");
+        }
+
+        chunks.push((fourcc, &data[8..end]));
+        // Chunks are padded to an even size
+        data = &data[(end + size % 2).min(data.len())..];
+    }
+
+    Ok(chunks)
+}
+
+fn write_chunk(output: &mut Vec<u8>, fourcc: &[u8; 4], payload: &[u8]) {
+    output.extend_from_slice(fourcc);
+    output.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    output.extend_from_slice(payload);
+    if payload.len() % 2 == 1 {
+        output.push(0);
+    }
+}
+
+/// Read the image size from a VP8 (lossy) or VP8L (lossless) bitstream header
+fn bitstream_dimensions(fourcc: &[u8; 4], payload: &[u8]) -> Option<(u32, u32)> {
+    match fourcc {
+        b"VP8L" if payload.len() >= 5 && payload[0] == 0x2f => {
+            let bits = u32::from_le_bytes([payload[1], payload[2], payload[3], payload[4]]);
+            Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+        }
+        b"VP8 " if payload.len() >= 10 && payload[3..6] == [0x9d, 0x01, 0x2a] => {
+            let width = u16::from_le_bytes([payload[6], payload[7]]) & 0x3fff;
+            let height = u16::from_le_bytes([payload[8], payload[9]]) & 0x3fff;
+            Some((width as u32, height as u32))
+        }
+        _ => None,
+    }
+}
+
+fn read_u24(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0])
+}
+
+/// Drop the `Exif\0\0` marker some containers put in front of the TIFF structure
+fn strip_exif_prefix(mut exif: Vec<u8>) -> Vec<u8> {
+    if exif.starts_with(b"Exif\0\0") {
+        exif.drain(..6);
+    }
+    exif
+}
+
+/// Set the orientation tag in the first IFD of an EXIF (TIFF) block to "normal"
+fn reset_exif_orientation(exif: &mut [u8]) {
+    let little_endian = match exif.get(0..2) {
+        Some(b"II") => true,
+        Some(b"MM") => false,
+        _ => return,
+    };
+    let read_u16 = |bytes: &[u8]| {
+        let bytes = [bytes[0], bytes[1]];
+        if little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        }
+    };
+    let read_u32 = |bytes: &[u8]| {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        }
+    };
+
+    let Some(ifd_offset) = exif.get(4..8).map(read_u32) else {
+        return;
+    };
+    let ifd = ifd_offset as usize;
+    let Some(entry_count) = exif.get(ifd..ifd + 2).map(read_u16) else {
+        return;
+    };
+
+    for index in 0..entry_count as usize {
+        let entry = ifd + 2 + index * 12;
+        let Some(tag) = exif.get(entry..entry + 2).map(read_u16) else {
+            return;
+        };
+        if tag != EXIF_TAG_ORIENTATION || entry + 10 > exif.len() {
+            continue;
+        }
+
+        // Orientation is a single SHORT stored inline in the value field
+        let value = if little_endian {
+            EXIF_ORIENTATION_NORMAL.to_le_bytes()
+        } else {
+            EXIF_ORIENTATION_NORMAL.to_be_bytes()
+        };
+        exif[entry + 8..entry + 10].copy_from_slice(&value);
+        return;
+    }
+}
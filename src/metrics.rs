@@ -0,0 +1,65 @@
+//! Perceptual quality metrics used to compare encoded output with its source
+
+use image::DynamicImage;
+
+/// Side length of the square windows SSIM is averaged over
+const SSIM_WINDOW: u32 = 8;
+
+/// Stabilizing constants from the original SSIM paper for 8-bit samples
+const SSIM_C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const SSIM_C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+/// Mean structural similarity (SSIM) of two images, computed on luma over 8x8 windows
+///
+/// Returns `1.0` for identical images and lower values as the distortion grows.
+/// Images of different sizes are compared over their overlapping area.
+pub fn ssim(reference: &DynamicImage, distorted: &DynamicImage) -> f64 {
+    let reference = reference.to_luma8();
+    let distorted = distorted.to_luma8();
+
+    let width = reference.width().min(distorted.width());
+    let height = reference.height().min(distorted.height());
+    if width == 0 || height == 0 {
+        return 0.0;
+    }
+
+    let mut total = 0.0;
+    let mut windows = 0u64;
+
+    for y in (0..height).step_by(SSIM_WINDOW as usize) {
+        let window_height = SSIM_WINDOW.min(height - y);
+
+        for x in (0..width).step_by(SSIM_WINDOW as usize) {
+            let window_width = SSIM_WINDOW.min(width - x);
+            let samples = (window_width * window_height) as f64;
+
+            let (mut sum_a, mut sum_b) = (0.0, 0.0);
+            let (mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0);
+
+            for wy in y..y + window_height {
+                for wx in x..x + window_width {
+                    let a = reference.get_pixel(wx, wy).0[0] as f64;
+                    let b = distorted.get_pixel(wx, wy).0[0] as f64;
+                    sum_a += a;
+                    sum_b += b;
+                    sum_aa += a * a;
+                    sum_bb += b * b;
+                    sum_ab += a * b;
+                }
+            }
+
+            let mean_a = sum_a / samples;
+            let mean_b = sum_b / samples;
+            let variance_a = sum_aa / samples - mean_a * mean_a;
+            let variance_b = sum_bb / samples - mean_b * mean_b;
+            let covariance = sum_ab / samples - mean_a * mean_b;
+
+            total += ((2.0 * mean_a * mean_b + SSIM_C1) * (2.0 * covariance + SSIM_C2))
+                / ((mean_a * mean_a + mean_b * mean_b + SSIM_C1)
+                    * (variance_a + variance_b + SSIM_C2));
+            windows += 1;
+        }
+    }
+
+    total / windows as f64
+}
@@ -0,0 +1,57 @@
+//! Placement of output files
+
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+
+use crate::config::OUTPUT_FORMAT;
+
+/// Decides where the output of each input file is written
+///
+/// Set a resolver with
+/// [`ConversionOptions::with_output_path_resolver`](crate::ConversionOptions::with_output_path_resolver)
+/// for layouts the built-in options don't cover, e.g. folders sharded by a hash of the
+/// name or by date. A custom resolver takes the place of the output directory, in-place
+/// output, structure preservation and filename templates. Prescanned runs still detect
+/// colliding outputs, and an output resolved onto its own source is rejected.
+pub trait OutputPathResolver: Send + Sync + Debug {
+    /// The output path for `input`, which was found below `input_root` (the innermost
+    /// input directory containing it)
+    fn resolve(&self, input: &Path, input_root: &Path) -> PathBuf;
+}
+
+/// The built-in layout: the input tree mirrored below `output_dir` (or flattened into it)
+/// with the extension changed to `.webp` (or `extension`)
+#[derive(Debug, Clone)]
+pub struct DefaultOutputPathResolver {
+    pub output_dir: PathBuf,
+    pub preserve_structure: bool,
+    pub extension: String,
+}
+
+impl DefaultOutputPathResolver {
+    pub fn new(output_dir: PathBuf, preserve_structure: bool) -> Self {
+        Self {
+            output_dir,
+            preserve_structure,
+            extension: OUTPUT_FORMAT.to_string(),
+        }
+    }
+
+    /// Use `extension` (without the leading dot) instead of `webp`
+    pub fn with_extension(mut self, extension: impl Into<String>) -> Self {
+        self.extension = extension.into();
+        self
+    }
+}
+
+impl OutputPathResolver for DefaultOutputPathResolver {
+    fn resolve(&self, input: &Path, input_root: &Path) -> PathBuf {
+        let output_path = match input.strip_prefix(input_root) {
+            Ok(relative_path) if self.preserve_structure => self.output_dir.join(relative_path),
+            _ => self
+                .output_dir
+                .join(input.file_name().unwrap_or(input.as_os_str())),
+        };
+        output_path.with_extension(&self.extension)
+    }
+}
@@ -1,3 +1,41 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{ConversionReport, converter::ConversionOutcome, utils::write_atomically};
+
+/// Typed progress event yielded by [`WebpifyCore::events`](crate::WebpifyCore::events)
+/// and passed to [`WebpifyCore::run_with_callback`](crate::WebpifyCore::run_with_callback)
+#[derive(Debug, Clone)]
+pub enum ProgressEvent {
+    /// Input scanning is under way; `files_found` is the running count of candidates
+    ScanProgress { files_found: usize },
+    /// Conversion is starting; `total` is known only when the inputs were scanned first
+    Started { total: Option<usize> },
+    /// A worker started converting `path`
+    FileStart { path: PathBuf },
+    /// `path` was converted (or analyzed in dry run mode)
+    FileDone {
+        path: PathBuf,
+        result: ConversionOutcome,
+    },
+    /// `path` was skipped because its output already exists
+    Skip { path: PathBuf },
+    /// A file failed to convert, or the run itself failed when `path` is `None`
+    Error {
+        path: Option<PathBuf>,
+        message: String,
+    },
+    /// Another tenth of the batch has been handled
+    Milestone {
+        percent: u8,
+        processed: usize,
+        total: usize,
+    },
+    /// The run completed; always the last event of a successful run
+    Finished { report: Box<ConversionReport> },
+}
+
 /// Trait for reporting conversion progress
 /// This allows different interfaces (CLI, GUI) to implement their own progress display
 pub trait ProgressReporter: Send + Sync {
@@ -7,6 +45,27 @@ pub trait ProgressReporter: Send + Sync {
     /// Update current progress
     fn update_progress(&self, processed: usize, failed: usize);
 
+    /// Report live totals after each file for throughput display
+    ///
+    /// `bytes_in` and `bytes_out` are the source and output bytes so far. `eta` is `None`
+    /// while the total is unknown (streaming without prescan) or nothing has finished yet.
+    fn update_stats(
+        &self,
+        _processed: usize,
+        _failed: usize,
+        _bytes_in: u64,
+        _bytes_out: u64,
+        _eta: Option<Duration>,
+    ) {
+    }
+
+    /// Report the source bytes of the files handled so far against those found by the scan
+    ///
+    /// Lets a progress bar weight files by size, since one large file can take as long
+    /// as many small ones. `total_bytes` keeps growing while inputs are still being
+    /// scanned (streaming without prescan).
+    fn update_bytes(&self, _processed_bytes: u64, _total_bytes: u64) {}
+
     /// Report that conversion has started
     fn start_conversion(&self) {}
 
@@ -14,10 +73,23 @@ pub trait ProgressReporter: Send + Sync {
     fn finish_conversion(&self) {}
 
     /// Report an error for a specific file
+    ///
+    /// Called from worker threads as soon as the file fails.
     fn report_error(&self, _file_path: &str, _error: &str) {}
 
     /// Report successful conversion of a file
+    ///
+    /// Called from worker threads as soon as the file is written; not called for
+    /// files skipped because their output already exists.
     fn report_success(&self, _file_path: &str, _original_size: u64, _compressed_size: u64) {}
+
+    /// Report that worker `thread_id` started converting a file
+    ///
+    /// Called from the worker thread itself; `thread_id` is its index in the pool.
+    fn file_started(&self, _thread_id: usize, _file_path: &str) {}
+
+    /// Report that worker `thread_id` is done with its current file, whatever the outcome
+    fn file_finished(&self, _thread_id: usize) {}
 }
 
 /// A no-op progress reporter for when progress reporting is not needed
@@ -28,11 +100,195 @@ impl ProgressReporter for NoOpProgressReporter {
     fn update_progress(&self, _processed: usize, _failed: usize) {}
 }
 
+/// Redraw interval of the per-worker spinners of [`ConsoleProgressReporter`]
+#[cfg(feature = "cli")]
+const WORKER_TICK_INTERVAL: Duration = Duration::from_millis(120);
+
+/// Minimum time between two writes of the progress file
+const PROGRESS_FILE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Progress reporter that keeps a small JSON status file up to date for other processes
+///
+/// The file holds `{"total": .., "processed": .., "failed": .., "eta_seconds": ..}`, where
+/// `total` and `eta_seconds` are `null` while unknown. It is replaced atomically, so a
+/// reader never sees a half-written file, and rewritten at most every 500 ms; the final
+/// state is always written. Another reporter can be wrapped to keep the console or GUI
+/// display as well.
+pub struct FileProgressReporter {
+    path: PathBuf,
+    state: Mutex<FileProgressState>,
+    inner: Option<Box<dyn ProgressReporter>>,
+}
+
+#[derive(Default)]
+struct FileProgressState {
+    total: Option<usize>,
+    processed: usize,
+    failed: usize,
+    eta: Option<Duration>,
+    last_write: Option<Instant>,
+    /// Whether the state changed since it was last written
+    dirty: bool,
+}
+
+impl FileProgressReporter {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            state: Mutex::new(FileProgressState::default()),
+            inner: None,
+        }
+    }
+
+    /// Forward every update to `reporter` as well
+    pub fn with_inner(mut self, reporter: Box<dyn ProgressReporter>) -> Self {
+        self.inner = Some(reporter);
+        self
+    }
+
+    /// Apply `change` to the state and write it unless the last write was too recent
+    fn update(&self, change: impl FnOnce(&mut FileProgressState)) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        change(&mut state);
+        state.dirty = true;
+
+        let finished = state
+            .total
+            .is_some_and(|total| state.processed + state.failed >= total);
+        let due = state
+            .last_write
+            .is_none_or(|last_write| last_write.elapsed() >= PROGRESS_FILE_INTERVAL);
+        if finished || due {
+            self.write(&mut state);
+        }
+    }
+
+    /// Write the state if an update was held back by the throttle
+    fn flush(&self) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        if state.dirty {
+            self.write(&mut state);
+        }
+    }
+
+    fn write(&self, state: &mut FileProgressState) {
+        let contents = serde_json::json!({
+            "total": state.total,
+            "processed": state.processed,
+            "failed": state.failed,
+            "eta_seconds": state.eta.map(|eta| eta.as_secs()),
+        });
+        if let Err(e) = write_atomically(&self.path, contents.to_string().as_bytes()) {
+            log::warn!(
+                "Failed to write progress file {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+        state.last_write = Some(Instant::now());
+        state.dirty = false;
+    }
+}
+
+impl Drop for FileProgressReporter {
+    fn drop(&mut self) {
+        // Updates skipped by the throttle must not be lost at the end of the run
+        self.flush();
+    }
+}
+
+impl ProgressReporter for FileProgressReporter {
+    fn set_total_files(&self, total: usize) {
+        self.update(|state| state.total = Some(total));
+        if let Some(inner) = &self.inner {
+            inner.set_total_files(total);
+        }
+    }
+
+    fn update_progress(&self, processed: usize, failed: usize) {
+        self.update(|state| {
+            state.processed = processed;
+            state.failed = failed;
+        });
+        if let Some(inner) = &self.inner {
+            inner.update_progress(processed, failed);
+        }
+    }
+
+    fn update_stats(
+        &self,
+        processed: usize,
+        failed: usize,
+        bytes_in: u64,
+        bytes_out: u64,
+        eta: Option<Duration>,
+    ) {
+        self.update(|state| {
+            state.processed = processed;
+            state.failed = failed;
+            state.eta = eta;
+        });
+        if let Some(inner) = &self.inner {
+            inner.update_stats(processed, failed, bytes_in, bytes_out, eta);
+        }
+    }
+
+    fn update_bytes(&self, processed_bytes: u64, total_bytes: u64) {
+        if let Some(inner) = &self.inner {
+            inner.update_bytes(processed_bytes, total_bytes);
+        }
+    }
+
+    fn start_conversion(&self) {
+        if let Some(inner) = &self.inner {
+            inner.start_conversion();
+        }
+    }
+
+    fn finish_conversion(&self) {
+        self.flush();
+        if let Some(inner) = &self.inner {
+            inner.finish_conversion();
+        }
+    }
+
+    fn report_error(&self, file_path: &str, error: &str) {
+        if let Some(inner) = &self.inner {
+            inner.report_error(file_path, error);
+        }
+    }
+
+    fn report_success(&self, file_path: &str, original_size: u64, compressed_size: u64) {
+        if let Some(inner) = &self.inner {
+            inner.report_success(file_path, original_size, compressed_size);
+        }
+    }
+
+    fn file_started(&self, thread_id: usize, file_path: &str) {
+        if let Some(inner) = &self.inner {
+            inner.file_started(thread_id, file_path);
+        }
+    }
+
+    fn file_finished(&self, thread_id: usize) {
+        if let Some(inner) = &self.inner {
+            inner.file_finished(thread_id);
+        }
+    }
+}
+
 /// Console-based progress reporter using indicatif
 #[cfg(feature = "cli")]
 pub struct ConsoleProgressReporter {
     progress_bar: indicatif::ProgressBar,
     multi_progress: indicatif::MultiProgress,
+    verbose: bool,
+    /// Spinners below the main bar, one per worker, showing the file it is encoding
+    worker_bars: Mutex<std::collections::HashMap<usize, indicatif::ProgressBar>>,
 }
 
 #[cfg(feature = "cli")]
@@ -50,7 +306,7 @@ impl ConsoleProgressReporter {
         progress_bar.set_style(
             indicatif::ProgressStyle::default_bar()
                 .template(
-                    "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
+                    "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}",
                 )
                 .unwrap()
                 .progress_chars("#>-"),
@@ -59,9 +315,18 @@ impl ConsoleProgressReporter {
         Self {
             progress_bar,
             multi_progress,
+            verbose: false,
+            worker_bars: Mutex::new(std::collections::HashMap::new()),
         }
     }
 
+    /// Print a line for every converted file, not only for failures, and show the file
+    /// each worker is encoding below the main bar
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
     pub fn get_multi_progress(&self) -> &indicatif::MultiProgress {
         &self.multi_progress
     }
@@ -77,11 +342,40 @@ impl ProgressReporter for ConsoleProgressReporter {
         self.progress_bar.set_position(processed as u64);
     }
 
+    fn update_stats(
+        &self,
+        processed: usize,
+        failed: usize,
+        bytes_in: u64,
+        _bytes_out: u64,
+        eta: Option<Duration>,
+    ) {
+        let elapsed = self.progress_bar.elapsed().as_secs_f64();
+        if elapsed <= 0.0 {
+            return;
+        }
+
+        let mut message = format!(
+            "{}/s, {:.1} files/s",
+            humansize::format_size((bytes_in as f64 / elapsed) as u64, humansize::DECIMAL),
+            (processed + failed) as f64 / elapsed
+        );
+        if let Some(eta) = eta {
+            message.push_str(&format!(", ETA {}", crate::utils::format_duration(eta)));
+        }
+        self.progress_bar.set_message(message);
+    }
+
     fn start_conversion(&self) {
         self.progress_bar.set_message("Converting images...");
     }
 
     fn finish_conversion(&self) {
+        if let Ok(mut worker_bars) = self.worker_bars.lock() {
+            for (_, bar) in worker_bars.drain() {
+                bar.finish_and_clear();
+            }
+        }
         self.progress_bar
             .finish_with_message("Conversion completed!");
     }
@@ -92,8 +386,13 @@ impl ProgressReporter for ConsoleProgressReporter {
     }
 
     fn report_success(&self, file_path: &str, original_size: u64, compressed_size: u64) {
+        if !self.verbose {
+            return;
+        }
+
+        // Negative when the WebP output is larger than the source
         let ratio = if original_size > 0 {
-            ((original_size - compressed_size) as f64 / original_size as f64) * 100.0
+            (1.0 - compressed_size as f64 / original_size as f64) * 100.0
         } else {
             0.0
         };
@@ -105,4 +404,43 @@ impl ProgressReporter for ConsoleProgressReporter {
             ratio
         ));
     }
+
+    fn file_started(&self, thread_id: usize, file_path: &str) {
+        if !self.verbose {
+            return;
+        }
+        let Ok(mut worker_bars) = self.worker_bars.lock() else {
+            return;
+        };
+
+        let bar = worker_bars.entry(thread_id).or_insert_with(|| {
+            let bar = self
+                .multi_progress
+                .add(indicatif::ProgressBar::new_spinner());
+            bar.set_style(
+                indicatif::ProgressStyle::default_spinner()
+                    .template("  {spinner:.blue} [{prefix}] {wide_msg}")
+                    .unwrap(),
+            );
+            bar.set_prefix(format!("worker {thread_id}"));
+            bar.enable_steady_tick(WORKER_TICK_INTERVAL);
+            bar
+        });
+        bar.set_message(file_path.to_string());
+    }
+
+    fn file_finished(&self, thread_id: usize) {
+        if !self.verbose {
+            return;
+        }
+        // Keep the bar so the layout doesn't jump between files
+        if let Some(bar) = self
+            .worker_bars
+            .lock()
+            .ok()
+            .and_then(|worker_bars| worker_bars.get(&thread_id).cloned())
+        {
+            bar.set_message("idle");
+        }
+    }
 }
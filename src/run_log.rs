@@ -0,0 +1,50 @@
+//! Timestamped per-file log of a conversion run
+//!
+//! This is separate from the `log` facade so a library user gets a persistent record of
+//! every file without installing a logger; the binaries keep logging to stderr as before.
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Appends timestamped lines to a log file while the conversion runs
+///
+/// The file is opened in append mode, so consecutive runs accumulate in one log. Every
+/// line is flushed as soon as it is written.
+pub(crate) struct RunLog {
+    file: Mutex<std::io::LineWriter<std::fs::File>>,
+}
+
+impl RunLog {
+    /// Open (or create) the log file at `path`
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create log directory: {}", parent.display()))?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open log file: {}", path.display()))?;
+
+        Ok(Self {
+            file: Mutex::new(std::io::LineWriter::new(file)),
+        })
+    }
+
+    /// Append one line at `level` (e.g. `INFO`, `ERROR`)
+    pub(crate) fn write(&self, level: &str, message: &str) {
+        let timestamp = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z");
+        let written = self
+            .file
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Log file lock poisoned"))
+            .and_then(|mut file| Ok(writeln!(file, "{timestamp} {level:<5} {message}")?));
+
+        if let Err(e) = written {
+            log::warn!("Failed to write to log file: {e:#}");
+        }
+    }
+}
@@ -1,19 +1,37 @@
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
+use crate::converter::QualitySearchResult;
+
 #[derive(Debug, Clone)]
 pub struct ConversionStats {
     pub processed_count: Arc<AtomicU64>,
     pub error_count: Arc<AtomicU64>,
     pub skipped_count: Arc<AtomicU64>,
+    /// Files whose source was kept because the WebP would have been larger
+    pub kept_original_count: Arc<AtomicU64>,
+    /// WebP inputs that were re-encoded, and those kept because re-encoding didn't shrink them
+    pub webp_reencoded_count: Arc<AtomicU64>,
+    pub webp_kept_count: Arc<AtomicU64>,
+    /// Files below the passthrough size, copied to the output without converting
+    pub passed_through_count: Arc<AtomicU64>,
 
     pub retry_count: Arc<AtomicU64>,
+    /// Source bytes of every file handled so far, whether converted, skipped or failed
+    pub processed_bytes: Arc<AtomicU64>,
     pub original_size: Arc<AtomicU64>,
     pub compressed_size: Arc<AtomicU64>,
     format_stats: Arc<Mutex<HashMap<String, u64>>>,
     errors: Arc<Mutex<Vec<ErrorRecord>>>,
+    skip_reasons: Arc<Mutex<HashMap<SkipReason, u64>>>,
+    quality_searches: Arc<Mutex<HashMap<String, QualitySearchResult>>>,
+    output_files: Arc<Mutex<Vec<PathBuf>>>,
+    file_results: Arc<Mutex<Vec<FileResult>>>,
     start_time: Arc<Mutex<Option<Instant>>>,
 }
 
@@ -22,6 +40,181 @@ pub struct ErrorRecord {
     pub file_path: String,
     pub error_message: String,
     pub retry_count: u32,
+    /// Category of the failure; `None` for warnings that did not fail a file
+    pub kind: Option<ErrorKind>,
+}
+
+/// Outcome of a single file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Converted,
+    /// The output was already there (or the file was done before a resume)
+    Skipped,
+    /// The WebP would have been larger, so the source was copied instead
+    KeptOriginal,
+    /// The source was below the passthrough size and copied without converting
+    PassedThrough,
+    Failed,
+}
+
+/// Result of one file, collected when per-file results are enabled
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileResult {
+    pub path: PathBuf,
+    /// Lowercase source extension
+    pub format: String,
+    pub original_bytes: u64,
+    pub output_bytes: u64,
+    pub status: FileStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Files written for this input; empty for skipped and failed files and dry runs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<PathBuf>,
+}
+
+/// Category of a failed file, so reports can tell failure causes apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// Reading or writing a file failed
+    Io,
+    /// The decoder rejected the image data
+    Decode,
+    /// The file looks like a supported image but is empty, truncated or damaged
+    Corrupt,
+    /// The WebP encoder failed
+    Encode,
+    /// The image uses a format or feature that is not supported
+    UnsupportedFormat,
+    /// The image exceeds a decoder or encoder size limit
+    SizeLimit,
+    /// The output could not be written because a file is already in the way
+    OutputExists,
+    /// The conversion did not finish within the per-file timeout
+    Timeout,
+    /// Anything else
+    Other,
+}
+
+/// Why a file was not converted
+///
+/// Files skipped at conversion time also count towards `skipped_count`; files left out
+/// by the scan filters never become part of the run and are only counted here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The output already exists (or is up to date in incremental mode)
+    OutputExists,
+    /// The file was converted before an interrupted run stopped
+    AlreadyConverted,
+    /// A WebP input left out because re-encoding WebP files is disabled
+    WebpNoReencode,
+    /// Smaller than the minimum file size
+    BelowMinSize,
+    /// Larger than the maximum file size
+    AboveMaxSize,
+    /// Modified outside the date range
+    OutsideDateRange,
+    /// Pixel dimensions outside the dimension limits
+    OutsideDimensions,
+    /// The extension is not one of the selected formats
+    FormatFiltered,
+    /// Left out by an include or exclude glob
+    GlobExcluded,
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SkipReason::OutputExists => "output exists",
+            SkipReason::AlreadyConverted => "converted before resume",
+            SkipReason::WebpNoReencode => "webp not re-encoded",
+            SkipReason::BelowMinSize => "below min size",
+            SkipReason::AboveMaxSize => "above max size",
+            SkipReason::OutsideDateRange => "outside date range",
+            SkipReason::OutsideDimensions => "outside dimension limits",
+            SkipReason::FormatFiltered => "format filtered",
+            SkipReason::GlobExcluded => "glob excluded",
+        })
+    }
+}
+
+/// An error tagged with its category where it is raised
+///
+/// Create one with [`ErrorKind::error`]; [`ErrorKind::classify`] finds it anywhere in
+/// an error chain.
+#[derive(Debug)]
+pub struct CategorizedError {
+    pub kind: ErrorKind,
+    message: String,
+}
+
+impl std::fmt::Display for CategorizedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CategorizedError {}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ErrorKind::Io => "I/O error",
+            ErrorKind::Decode => "decode error",
+            ErrorKind::Corrupt => "corrupt or truncated",
+            ErrorKind::Encode => "encode error",
+            ErrorKind::UnsupportedFormat => "unsupported format",
+            ErrorKind::SizeLimit => "size limit",
+            ErrorKind::OutputExists => "output exists",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Other => "other",
+        })
+    }
+}
+
+impl ErrorKind {
+    /// Create an error of this category with `message`
+    pub fn error(self, message: impl Into<String>) -> anyhow::Error {
+        anyhow::Error::new(CategorizedError {
+            kind: self,
+            message: message.into(),
+        })
+    }
+
+    /// Categorize an error by the most specific cause in its chain
+    pub fn classify(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            if let Some(categorized) = cause.downcast_ref::<CategorizedError>() {
+                return categorized.kind;
+            }
+            if let Some(image_error) = cause.downcast_ref::<image::ImageError>() {
+                return match image_error {
+                    image::ImageError::Decoding(_) => ErrorKind::Decode,
+                    image::ImageError::Encoding(_) => ErrorKind::Encode,
+                    image::ImageError::Limits(_) => ErrorKind::SizeLimit,
+                    image::ImageError::Unsupported(_) => ErrorKind::UnsupportedFormat,
+                    image::ImageError::IoError(io_error) => Self::from_io(io_error),
+                    image::ImageError::Parameter(_) => ErrorKind::Other,
+                };
+            }
+            if let Some(io_error) = cause.downcast_ref::<io::Error>() {
+                return Self::from_io(io_error);
+            }
+        }
+        ErrorKind::Other
+    }
+
+    fn from_io(error: &io::Error) -> Self {
+        match error.kind() {
+            // Data ending early means the file was cut off
+            io::ErrorKind::UnexpectedEof => ErrorKind::Corrupt,
+            io::ErrorKind::AlreadyExists => ErrorKind::OutputExists,
+            _ => ErrorKind::Io,
+        }
+    }
 }
 
 impl Default for ConversionStats {
@@ -36,11 +229,20 @@ impl ConversionStats {
             processed_count: Arc::new(AtomicU64::new(0)),
             error_count: Arc::new(AtomicU64::new(0)),
             skipped_count: Arc::new(AtomicU64::new(0)),
+            kept_original_count: Arc::new(AtomicU64::new(0)),
+            webp_reencoded_count: Arc::new(AtomicU64::new(0)),
+            webp_kept_count: Arc::new(AtomicU64::new(0)),
+            passed_through_count: Arc::new(AtomicU64::new(0)),
             retry_count: Arc::new(AtomicU64::new(0)),
+            processed_bytes: Arc::new(AtomicU64::new(0)),
             original_size: Arc::new(AtomicU64::new(0)),
             compressed_size: Arc::new(AtomicU64::new(0)),
             format_stats: Arc::new(Mutex::new(HashMap::new())),
             errors: Arc::new(Mutex::new(Vec::new())),
+            skip_reasons: Arc::new(Mutex::new(HashMap::new())),
+            quality_searches: Arc::new(Mutex::new(HashMap::new())),
+            output_files: Arc::new(Mutex::new(Vec::new())),
+            file_results: Arc::new(Mutex::new(Vec::new())),
             start_time: Arc::new(Mutex::new(None)),
         }
     }
@@ -58,16 +260,16 @@ impl ConversionStats {
             return None;
         }
 
-        if let Ok(start_time) = self.start_time.lock() {
-            if let Some(start) = *start_time {
-                let elapsed = start.elapsed();
-                let rate = processed as f64 / elapsed.as_secs_f64();
-                let remaining_files = total_files.saturating_sub(processed);
+        if let Ok(start_time) = self.start_time.lock()
+            && let Some(start) = *start_time
+        {
+            let elapsed = start.elapsed();
+            let rate = processed as f64 / elapsed.as_secs_f64();
+            let remaining_files = total_files.saturating_sub(processed);
 
-                if rate > 0.0 {
-                    let eta_seconds = remaining_files as f64 / rate;
-                    return Some(std::time::Duration::from_secs_f64(eta_seconds));
-                }
+            if rate > 0.0 {
+                let eta_seconds = remaining_files as f64 / rate;
+                return Some(std::time::Duration::from_secs_f64(eta_seconds));
             }
         }
 
@@ -82,29 +284,87 @@ impl ConversionStats {
             .fetch_add(compressed_size, Ordering::Relaxed);
     }
 
-    pub fn record_error(&self, file_path: String, error: String) {
+    /// Count a failed file; `retry_count` is the number of retries made before giving up
+    pub fn record_error(
+        &self,
+        file_path: String,
+        kind: ErrorKind,
+        error: String,
+        retry_count: u32,
+    ) {
         self.error_count.fetch_add(1, Ordering::Relaxed);
         if let Ok(mut errors) = self.errors.lock() {
             errors.push(ErrorRecord {
                 file_path,
                 error_message: error,
-                retry_count: 0,
+                retry_count,
+                kind: Some(kind),
             });
         }
     }
 
-    pub fn record_retry(&self, file_path: &str) {
-        self.retry_count.fetch_add(1, Ordering::Relaxed);
+    /// Add an entry to the report errors list without counting a failed file
+    pub fn record_warning(&self, file_path: String, message: String) {
         if let Ok(mut errors) = self.errors.lock() {
-            // Update retry count for the most recent error for this file
-            if let Some(error_record) = errors.iter_mut().rev().find(|e| e.file_path == file_path) {
-                error_record.retry_count += 1;
-            }
+            errors.push(ErrorRecord {
+                file_path,
+                error_message: message,
+                retry_count: 0,
+                kind: None,
+            });
         }
     }
 
-    pub fn record_skip(&self) {
+    /// Count a retry; the file's own count is recorded with its error if it fails for good
+    pub fn record_retry(&self) {
+        self.retry_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_skip(&self, reason: SkipReason) {
         self.skipped_count.fetch_add(1, Ordering::Relaxed);
+        self.record_filtered(reason);
+    }
+
+    /// Count a file the scan left out, without counting it as skipped
+    pub fn record_filtered(&self, reason: SkipReason) {
+        if let Ok(mut skip_reasons) = self.skip_reasons.lock() {
+            *skip_reasons.entry(reason).or_insert(0) += 1;
+        }
+    }
+
+    pub fn record_kept_original(&self) {
+        self.kept_original_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_passed_through(&self) {
+        self.passed_through_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a handled WebP input as re-encoded, or as kept when its source was kept
+    pub fn record_webp_input(&self, kept: bool) {
+        if kept {
+            self.webp_kept_count.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.webp_reencoded_count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_quality_search(&self, file_path: String, result: QualitySearchResult) {
+        if let Ok(mut quality_searches) = self.quality_searches.lock() {
+            quality_searches.insert(file_path, result);
+        }
+    }
+
+    pub fn record_outputs(&self, paths: &[PathBuf]) {
+        if let Ok(mut output_files) = self.output_files.lock() {
+            output_files.extend_from_slice(paths);
+        }
+    }
+
+    pub fn record_file_result(&self, result: FileResult) {
+        if let Ok(mut file_results) = self.file_results.lock() {
+            file_results.push(result);
+        }
     }
 
     pub fn record_format(&self, format: &str) {
@@ -113,14 +373,24 @@ impl ConversionStats {
         }
     }
 
-    pub fn get_compression_ratio(&self) -> f64 {
+    /// Fraction of the original size saved so far; negative when outputs are larger
+    pub fn get_space_saved_ratio(&self) -> f64 {
+        if self.original_size.load(Ordering::Relaxed) == 0 {
+            0.0
+        } else {
+            1.0 - self.get_size_ratio()
+        }
+    }
+
+    /// Output size so far as a fraction of the original size (0 before any input)
+    pub fn get_size_ratio(&self) -> f64 {
         let original = self.original_size.load(Ordering::Relaxed);
         let compressed = self.compressed_size.load(Ordering::Relaxed);
 
         if original == 0 {
             0.0
         } else {
-            1.0 - (compressed as f64 / original as f64)
+            compressed as f64 / original as f64
         }
     }
 
@@ -141,4 +411,60 @@ impl ConversionStats {
             Vec::new()
         }
     }
+
+    /// Every failed file and warning recorded so far
+    pub fn get_error_records(&self) -> Vec<ErrorRecord> {
+        self.errors
+            .lock()
+            .map(|errors| errors.clone())
+            .unwrap_or_default()
+    }
+
+    /// Number of failed files per error category
+    pub fn get_error_counts(&self) -> HashMap<ErrorKind, u64> {
+        let mut counts = HashMap::new();
+        if let Ok(errors) = self.errors.lock() {
+            for kind in errors.iter().filter_map(|e| e.kind) {
+                *counts.entry(kind).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    /// Number of files not converted per reason, including those left out by the scan
+    pub fn get_skip_reasons(&self) -> HashMap<SkipReason, u64> {
+        self.skip_reasons
+            .lock()
+            .map(|skip_reasons| skip_reasons.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn get_quality_searches(&self) -> HashMap<String, QualitySearchResult> {
+        self.quality_searches
+            .lock()
+            .map(|searches| searches.clone())
+            .unwrap_or_default()
+    }
+
+    /// Per-file results recorded so far, sorted by path
+    pub fn get_file_results(&self) -> Vec<FileResult> {
+        let mut file_results = self
+            .file_results
+            .lock()
+            .map(|file_results| file_results.clone())
+            .unwrap_or_default();
+        file_results.sort_by(|a, b| a.path.cmp(&b.path));
+        file_results
+    }
+
+    /// Output files written so far, sorted by path
+    pub fn get_output_files(&self) -> Vec<PathBuf> {
+        let mut output_files = self
+            .output_files
+            .lock()
+            .map(|output_files| output_files.clone())
+            .unwrap_or_default();
+        output_files.sort();
+        output_files
+    }
 }
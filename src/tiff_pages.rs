@@ -0,0 +1,101 @@
+//! Decoding the individual pages of multi-page TIFF files
+
+use anyhow::{Context, Result};
+use image::{DynamicImage, ImageBuffer};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use tiff::ColorType;
+use tiff::decoder::{Decoder, DecodingResult};
+
+use crate::stats::ErrorKind;
+
+/// Whether the path has a `.tif` or `.tiff` extension
+pub fn is_tiff_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("tiff") || ext.eq_ignore_ascii_case("tif"))
+}
+
+fn open_decoder(path: &Path) -> Result<Decoder<BufReader<File>>> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to read image: {}", path.display()))?;
+    Decoder::new(BufReader::new(file))
+        .with_context(|| format!("Failed to read TIFF: {}", path.display()))
+}
+
+/// Number of pages (image file directories) in a TIFF file
+///
+/// Only the page headers are read, not the image data.
+pub fn page_count(path: &Path) -> Result<usize> {
+    let mut decoder = open_decoder(path)?;
+    let mut count = 1;
+    while decoder.more_images() {
+        decoder.next_image().with_context(|| {
+            format!("Failed to read TIFF page {}: {}", count + 1, path.display())
+        })?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Decode every page of a TIFF file, in file order
+///
+/// Pages with 8 or 16 bits per channel in gray, gray+alpha, RGB or RGBA are supported.
+pub fn decode_pages(path: &Path) -> Result<Vec<DynamicImage>> {
+    let mut decoder = open_decoder(path)?;
+    let mut pages = Vec::new();
+    loop {
+        let page = decode_page(&mut decoder).with_context(|| {
+            format!(
+                "Failed to decode TIFF page {}: {}",
+                pages.len() + 1,
+                path.display()
+            )
+        })?;
+        pages.push(page);
+
+        if !decoder.more_images() {
+            return Ok(pages);
+        }
+        decoder.next_image()?;
+    }
+}
+
+fn decode_page(decoder: &mut Decoder<BufReader<File>>) -> Result<DynamicImage> {
+    let (width, height) = decoder.dimensions()?;
+    let color_type = decoder.colortype()?;
+
+    let image = match (color_type, decoder.read_image()?) {
+        (ColorType::Gray(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma8)
+        }
+        (ColorType::GrayA(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA8)
+        }
+        (ColorType::RGB(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8)
+        }
+        (ColorType::RGBA(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8)
+        }
+        (ColorType::Gray(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma16)
+        }
+        (ColorType::GrayA(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA16)
+        }
+        (ColorType::RGB(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb16)
+        }
+        (ColorType::RGBA(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba16)
+        }
+        (color_type, _) => {
+            return Err(ErrorKind::UnsupportedFormat
+                .error(format!("Unsupported TIFF page color type {color_type:?}")));
+        }
+    };
+
+    image.ok_or_else(|| ErrorKind::Corrupt.error("TIFF page data is shorter than its dimensions"))
+}
@@ -10,10 +10,288 @@ impl fmt::Display for ImageValidationError {
         }
     }
 }
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, Utc};
 use std::fs::File;
-use std::io::{self, Read};
-use std::path::Path;
-use std::time::Duration;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Nice value used for low-priority runs on Unix
+#[cfg(unix)]
+const LOW_PRIORITY_NICE: libc::c_int = 10;
+
+/// Lower the scheduling priority so interactive applications stay responsive
+///
+/// On Linux this raises the nice value of the calling thread only, and threads spawned
+/// afterwards inherit it. Other Unix systems (macOS included) keep one nice value for
+/// the whole process, and unprivileged processes cannot raise their priority again, so
+/// there the change lasts until the process exits. On Windows the calling thread moves
+/// to below-normal priority.
+pub fn lower_current_priority() -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        // SAFETY: getpriority/setpriority only read and update scheduler state for the calling thread
+        let current = unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+        if current >= LOW_PRIORITY_NICE {
+            return Ok(());
+        }
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, LOW_PRIORITY_NICE) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Threading::{
+            GetCurrentThread, SetThreadPriority, THREAD_PRIORITY_BELOW_NORMAL,
+        };
+
+        // SAFETY: GetCurrentThread returns a pseudo handle that is always valid
+        if unsafe { SetThreadPriority(GetCurrentThread(), THREAD_PRIORITY_BELOW_NORMAL) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether an error looks temporary and the operation is worth retrying
+///
+/// Only I/O failures such as interruptions, timeouts and files locked by another
+/// process qualify; decode errors and invalid files are permanent.
+pub fn is_transient_error(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .filter_map(|cause| {
+            // The decoder wraps I/O failures, and its error source skips the wrapped error
+            match cause.downcast_ref::<image::ImageError>() {
+                Some(image::ImageError::IoError(io_error)) => Some(io_error),
+                _ => cause.downcast_ref::<io::Error>(),
+            }
+        })
+        .any(|io_error| {
+            // Windows reports locked files as sharing (32) or lock (33) violations
+            if cfg!(windows) && matches!(io_error.raw_os_error(), Some(32 | 33)) {
+                return true;
+            }
+            matches!(
+                io_error.kind(),
+                io::ErrorKind::Interrupted
+                    | io::ErrorKind::WouldBlock
+                    | io::ErrorKind::TimedOut
+                    | io::ErrorKind::ResourceBusy
+            )
+        })
+}
+
+/// Token bucket that caps the aggregate rate of output writes across threads
+///
+/// Each write reserves a time slot proportional to its size; callers sleep until their
+/// slot starts, so the combined rate of all writers never exceeds the limit.
+#[derive(Debug)]
+pub struct WriteThrottle {
+    bytes_per_second: u64,
+    next_slot: Mutex<Instant>,
+}
+
+impl WriteThrottle {
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second: bytes_per_second.max(1),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Block until `bytes` may be written without exceeding the configured rate
+    pub fn acquire(&self, bytes: u64) {
+        let cost = Duration::from_secs_f64(bytes as f64 / self.bytes_per_second as f64);
+        let slot = {
+            let mut next_slot = self
+                .next_slot
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            // Idle time does not accumulate into a burst allowance
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + cost;
+            slot
+        };
+
+        let wait = slot.saturating_duration_since(Instant::now());
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+}
+
+/// Caps the memory held by images that are decoded at the same time
+///
+/// Each conversion reserves its estimated decoded size and blocks while that would push
+/// the combined reservations over the budget. An image larger than the whole budget
+/// still runs, but only once no other reservation is held.
+#[derive(Debug)]
+pub struct MemoryBudget {
+    limit_bytes: u64,
+    reserved: Mutex<u64>,
+    released: Condvar,
+}
+
+impl MemoryBudget {
+    pub fn new(limit_bytes: u64) -> Self {
+        Self {
+            limit_bytes,
+            reserved: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Block until `bytes` fit into the budget; they are returned when the guard drops
+    ///
+    /// The guard owns a handle to the budget, so it can be moved to another thread.
+    pub fn acquire(self: &Arc<Self>, bytes: u64) -> MemoryReservation {
+        let mut reserved = self.reserved.lock().unwrap_or_else(PoisonError::into_inner);
+        while *reserved > 0 && *reserved + bytes > self.limit_bytes {
+            reserved = self
+                .released
+                .wait(reserved)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *reserved += bytes;
+        MemoryReservation {
+            budget: Arc::clone(self),
+            bytes,
+        }
+    }
+}
+
+/// Memory reserved from a [`MemoryBudget`] for one decoded image
+#[derive(Debug)]
+pub struct MemoryReservation {
+    budget: Arc<MemoryBudget>,
+    bytes: u64,
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        let mut reserved = self
+            .budget
+            .reserved
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *reserved = reserved.saturating_sub(self.bytes);
+        self.budget.released.notify_all();
+    }
+}
+
+/// Estimated memory of an image once decoded to RGBA (width * height * 4 bytes)
+///
+/// Falls back to the file size when the header can't be read (e.g. HEIF).
+pub fn estimate_decoded_bytes(path: &Path) -> u64 {
+    match image::image_dimensions(path) {
+        Ok((width, height)) => u64::from(width) * u64::from(height) * 4,
+        Err(_) => std::fs::metadata(path).map_or(0, |metadata| metadata.len()),
+    }
+}
+
+/// Write `contents` to a temporary file next to `path` and rename it into place
+///
+/// Readers never see a half-written file, and a failed or interrupted write leaves any
+/// existing file at `path` untouched. The contents are flushed to disk before the
+/// rename, so a power loss cannot leave an empty file in place of an older one. The
+/// temporary file is removed when the write or the rename fails; only a crash can leave
+/// one behind.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+    // Unique per write, since two workers may target the same output in flattened runs
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        file_name,
+        std::process::id(),
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let result = File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|()| replace_file(&temp_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Rename `from` over `to`, replacing an existing file
+fn replace_file(from: &Path, to: &Path) -> io::Result<()> {
+    match std::fs::rename(from, to) {
+        // Windows refuses to replace read-only files; clear the flag and try again
+        #[cfg(windows)]
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied && to.is_file() => {
+            let mut permissions = std::fs::metadata(to)?.permissions();
+            if !permissions.readonly() {
+                return Err(e);
+            }
+            #[allow(clippy::permissions_set_readonly_false)]
+            permissions.set_readonly(false);
+            std::fs::set_permissions(to, permissions)?;
+            std::fs::rename(from, to)
+        }
+        result => result,
+    }
+}
+
+/// Read newline-separated paths from `source`, or from stdin when it is `-`
+///
+/// Blank lines are skipped and surrounding whitespace (including `\r`) is trimmed.
+pub fn read_path_list(source: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    use anyhow::Context;
+
+    let contents = if source == Path::new("-") {
+        let mut contents = String::new();
+        io::stdin()
+            .read_to_string(&mut contents)
+            .context("Failed to read input list from stdin")?;
+        contents
+    } else {
+        std::fs::read_to_string(source)
+            .with_context(|| format!("Failed to read input list: {}", source.display()))?
+    };
+
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// `path` resolved against the working directory, without touching the filesystem
+///
+/// Falls back to `path` itself when the working directory is unavailable.
+pub fn absolute_path(path: PathBuf) -> PathBuf {
+    std::path::absolute(&path).unwrap_or(path)
+}
+
+/// Deepest directory containing every path in `paths` (empty if there is none)
+pub fn common_ancestor(paths: &[PathBuf]) -> PathBuf {
+    let Some(first) = paths.first() else {
+        return PathBuf::new();
+    };
+    let mut ancestor = first.parent().map(Path::to_path_buf).unwrap_or_default();
+    for path in &paths[1..] {
+        while !path.starts_with(&ancestor) {
+            if !ancestor.pop() {
+                return PathBuf::new();
+            }
+        }
+    }
+    ancestor
+}
 
 /// Format duration in human-readable format
 pub fn format_duration(duration: Duration) -> String {
@@ -31,6 +309,65 @@ pub fn format_duration(duration: Duration) -> String {
     }
 }
 
+/// Parse a date (`2024-01-31`, midnight local time) or an RFC 3339 timestamp
+pub fn parse_date_time(value: &str) -> anyhow::Result<DateTime<Utc>> {
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date_time.with_timezone(&Utc));
+    }
+
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        anyhow::anyhow!("Invalid date '{value}' (expected YYYY-MM-DD or an RFC 3339 timestamp)")
+    })?;
+    date.and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|date_time| date_time.with_timezone(&Utc))
+        .ok_or_else(|| anyhow::anyhow!("Date '{value}' does not exist in the local time zone"))
+}
+
+/// Parse image dimensions written as `WIDTHxHEIGHT`, e.g. `640x480`
+pub fn parse_dimensions(value: &str) -> anyhow::Result<(u32, u32)> {
+    value
+        .split_once(['x', 'X'])
+        .and_then(|(width, height)| Some((width.trim().parse().ok()?, height.trim().parse().ok()?)))
+        .ok_or_else(|| anyhow::anyhow!("Invalid dimensions '{value}' (expected WIDTHxHEIGHT)"))
+}
+
+/// Parse a per-format override written as `EXT:MODE:QUALITY`, e.g. `png:lossless:100`
+pub fn parse_format_override(value: &str) -> anyhow::Result<(String, crate::CompressionMode, u8)> {
+    let parts: Vec<&str> = value.split(':').map(str::trim).collect();
+    let [extension, mode, quality] = parts[..] else {
+        anyhow::bail!("Invalid format override '{value}' (expected EXT:MODE:QUALITY)");
+    };
+    let extension = extension.trim_start_matches('.');
+    if extension.is_empty() {
+        anyhow::bail!("Invalid format override '{value}' (missing extension)");
+    }
+    let quality = quality
+        .parse()
+        .ok()
+        .filter(|quality| *quality <= 100)
+        .ok_or_else(|| {
+            anyhow::anyhow!("Invalid quality in format override '{value}' (expected 0-100)")
+        })?;
+    Ok((extension.to_lowercase(), mode.parse()?, quality))
+}
+
+/// Parse a background color written as `#RRGGBB`, e.g. `#ffffff`
+pub fn parse_color(value: &str) -> anyhow::Result<image::Rgba<u8>> {
+    let hex = value.trim().strip_prefix('#').unwrap_or(value.trim());
+    if hex.len() != 6 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        anyhow::bail!("Invalid color '{value}' (expected #RRGGBB)");
+    }
+    let channel = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16);
+    Ok(image::Rgba([
+        channel(0)?,
+        channel(2)?,
+        channel(4)?,
+        u8::MAX,
+    ]))
+}
+
 /// Get file extension (lowercase)
 fn get_file_extension(path: &Path) -> Option<String> {
     path.extension()
@@ -38,6 +375,14 @@ fn get_file_extension(path: &Path) -> Option<String> {
         .map(|ext| ext.to_lowercase())
 }
 
+/// Every input extension webpify can read, as expanded from the `all` format keyword
+pub fn supported_formats() -> Vec<&'static str> {
+    IMAGE_SIGNATURES
+        .iter()
+        .map(|(extension, _)| *extension)
+        .collect()
+}
+
 /// Supported image formats with their magic number signatures
 const IMAGE_SIGNATURES: &[(&str, &[&[u8]])] = &[
     ("jpg", &[&[0xFF, 0xD8]]),
@@ -49,7 +394,12 @@ const IMAGE_SIGNATURES: &[(&str, &[&[u8]])] = &[
         "tiff",
         &[&[0x49, 0x49, 0x2A, 0x00], &[0x4D, 0x4D, 0x00, 0x2A]],
     ),
+    ("ico", &[&[0x00, 0x00, 0x01, 0x00]]),
     ("webp", &[]), // WebP needs special handling
+    #[cfg(feature = "heic")]
+    ("heic", &[]), // HEIF brand is checked after the ftyp box
+    #[cfg(feature = "heic")]
+    ("heif", &[]),
 ];
 
 /// Error types for image validation
@@ -100,15 +450,7 @@ fn is_supported_extension(extension: &str) -> bool {
 fn validate_image_header(path: &Path, extension: &str) -> Result<(), ImageValidationError> {
     let mut file = File::open(path)?;
 
-    // Determine required header size
-    let header_size = match extension {
-        "webp" => 12,
-        "png" => 8,
-        "gif" => 6,
-        "tiff" => 4,
-        _ => 2,
-    };
-
+    let header_size = header_size(extension);
     let mut header = vec![0u8; header_size];
     let bytes_read = file.read(&mut header)?;
 
@@ -116,22 +458,182 @@ fn validate_image_header(path: &Path, extension: &str) -> Result<(), ImageValida
         return Err(ImageValidationError::FileTooSmall);
     }
 
-    let is_valid = match extension {
+    if header_matches(extension, &header) {
+        Ok(())
+    } else {
+        Err(ImageValidationError::InvalidHeader)
+    }
+}
+
+/// Identify a supported image format from the file header, ignoring the extension
+///
+/// Returns the first matching extension of [`supported_formats`], e.g. `jpg` for JPEG
+/// data. Files too short for any signature fail with `InvalidHeader`.
+pub fn detect_image_format(path: &Path) -> Result<&'static str, ImageValidationError> {
+    let mut header = [0u8; 12];
+    let bytes_read = File::open(path)?.read(&mut header)?;
+
+    supported_formats()
+        .into_iter()
+        .find(|extension| {
+            let header_size = header_size(extension);
+            bytes_read >= header_size && header_matches(extension, &header[..header_size])
+        })
+        .ok_or(ImageValidationError::InvalidHeader)
+}
+
+/// Lowercase format of an input file
+///
+/// With `detect_by_content` the format comes from the header when it is recognized and
+/// the extension names a different one, so a PNG saved as `.jpg` is handled as a PNG;
+/// otherwise it is the extension.
+pub fn input_format(path: &Path, detect_by_content: bool) -> String {
+    let extension = get_file_extension(path);
+    if detect_by_content
+        && let Ok(format) = detect_image_format(path)
+        && !extension
+            .as_deref()
+            .is_some_and(|extension| is_same_format(extension, format))
+    {
+        return format.to_string();
+    }
+    extension.unwrap_or_default()
+}
+
+/// Whether two extensions name the same image format, e.g. `jpg` and `jpeg`
+pub fn is_same_format(a: &str, b: &str) -> bool {
+    fn canonical(extension: &str) -> &str {
+        match extension {
+            "jpeg" => "jpg",
+            "tif" => "tiff",
+            "heif" => "heic",
+            other => other,
+        }
+    }
+    canonical(&a.to_lowercase()) == canonical(&b.to_lowercase())
+}
+
+/// Number of header bytes needed to recognize the format of `extension`
+fn header_size(extension: &str) -> usize {
+    match extension {
+        "webp" | "heic" | "heif" => 12,
+        "png" => 8,
+        "gif" => 6,
+        "tiff" | "ico" => 4,
+        _ => 2,
+    }
+}
+
+/// Whether `header` (at least `header_size(extension)` bytes) carries the signature of
+/// `extension`
+fn header_matches(extension: &str, header: &[u8]) -> bool {
+    match extension {
         "jpg" | "jpeg" => header[0] == 0xFF && header[1] == 0xD8,
-        "png" => header == [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A],
+        "png" => header[0..8] == [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A],
         "gif" => header[0..6] == *b"GIF87a" || header[0..6] == *b"GIF89a",
         "bmp" => header[0..2] == *b"BM",
         "tiff" => {
             header[0..4] == [0x49, 0x49, 0x2A, 0x00] || // little-endian
             header[0..4] == [0x4D, 0x4D, 0x00, 0x2A] // big-endian
         }
+        "ico" => header[0..4] == [0x00, 0x00, 0x01, 0x00],
         "webp" => header[0..4] == *b"RIFF" && header[8..12] == *b"WEBP",
-        _ => return Err(ImageValidationError::InvalidExtension),
-    };
+        "heic" | "heif" => {
+            header[4..8] == *b"ftyp"
+                && crate::heif::HEIF_BRANDS
+                    .iter()
+                    .any(|brand| header[8..12] == **brand)
+        }
+        _ => false,
+    }
+}
 
-    if is_valid {
-        Ok(())
-    } else {
-        Err(ImageValidationError::InvalidHeader)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn lowering_priority_raises_the_nice_value() {
+        // Run on a fresh thread, since Linux applies nice values per thread
+        let (before, after) = thread::spawn(|| {
+            // SAFETY: getpriority only reads scheduler state for the calling thread
+            let nice = || unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+            let before = nice();
+            lower_current_priority().unwrap();
+            (before, nice())
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(after, before.max(LOW_PRIORITY_NICE));
+    }
+
+    #[test]
+    fn write_throttle_caps_the_aggregate_rate() {
+        // 4 writers x 5 writes x 5 KB = 100 KB at 200 KB/s; the last write may start
+        // once the first 95 KB are paid for
+        let throttle = Arc::new(WriteThrottle::new(200_000));
+        let start = Instant::now();
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let throttle = Arc::clone(&throttle);
+                thread::spawn(move || {
+                    for _ in 0..5 {
+                        throttle.acquire(5_000);
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(470), "{elapsed:?}");
+        assert!(elapsed < Duration::from_millis(1500), "{elapsed:?}");
+    }
+
+    #[test]
+    fn failed_atomic_writes_leave_no_temporary_file() {
+        let dir = std::env::temp_dir().join(format!("webpify-atomic-write-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("photo.webp");
+        write_atomically(&path, b"first").unwrap();
+        write_atomically(&path, b"second").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+
+        // The rename fails because a non-empty directory is in the way
+        let blocked = dir.join("blocked.webp");
+        std::fs::create_dir_all(blocked.join("inside")).unwrap();
+        assert!(write_atomically(&blocked, b"data").is_err());
+        assert!(blocked.join("inside").is_dir());
+
+        let mut names: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["blocked.webp", "photo.webp"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn decoder_wrapped_io_errors_are_transient() {
+        let wrapped = |kind: io::ErrorKind| {
+            anyhow::Error::new(image::ImageError::IoError(io::Error::from(kind)))
+                .context("Failed to read image: photo.png")
+        };
+        assert!(is_transient_error(&wrapped(io::ErrorKind::WouldBlock)));
+        assert!(is_transient_error(&wrapped(io::ErrorKind::Interrupted)));
+        assert!(!is_transient_error(&wrapped(io::ErrorKind::NotFound)));
+
+        #[cfg(windows)]
+        {
+            let sharing_violation = image::ImageError::IoError(io::Error::from_raw_os_error(32));
+            assert!(is_transient_error(&anyhow::Error::new(sharing_violation)));
+        }
     }
 }